@@ -0,0 +1,89 @@
+use std::{collections::HashMap, env, error::Error};
+
+use axum::{body::Body, extract::Request, http::HeaderMap};
+use opentelemetry::{
+    propagation::{Extractor, TextMapPropagator},
+    trace::TracerProvider as _,
+    KeyValue,
+};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{propagation::TraceContextPropagator, runtime, trace::TracerProvider, Resource};
+use tracing::{Level, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{fmt, prelude::*, EnvFilter};
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Flushes buffered spans when dropped.
+pub struct TracingGuard {
+    tracer_provider: TracerProvider,
+}
+
+impl Drop for TracingGuard {
+    fn drop(&mut self) {
+        if let Err(e) = self.tracer_provider.shutdown() {
+            eprintln!("Failed to flush spans: {:?}", e);
+        }
+    }
+}
+
+/// Spans are exported when `OTEL_EXPORTER_OTLP_ENDPOINT` is set. Either way
+/// they carry a trace context that is passed on to the auth service.
+pub fn init_tracing() -> Result<TracingGuard, Box<dyn Error>> {
+    let service_name = env::var("OTEL_SERVICE_NAME").unwrap_or("app-service".to_owned());
+
+    let mut tracer_provider = TracerProvider::builder()
+        .with_resource(Resource::new([KeyValue::new("service.name", service_name.clone())]));
+
+    if let Some(endpoint) = env::var("OTEL_EXPORTER_OTLP_ENDPOINT").ok().filter(|value| !value.is_empty()) {
+        let exporter = SpanExporter::builder().with_tonic().with_endpoint(endpoint).build()?;
+        tracer_provider = tracer_provider.with_batch_exporter(exporter, runtime::Tokio);
+    }
+
+    let tracer_provider = tracer_provider.build();
+
+    tracing_subscriber::registry()
+        .with(EnvFilter::try_from_default_env().or_else(|_| EnvFilter::try_new("info"))?)
+        .with(fmt::layer().compact())
+        .with(tracing_opentelemetry::layer().with_tracer(tracer_provider.tracer(service_name)))
+        .init();
+
+    Ok(TracingGuard { tracer_provider })
+}
+
+pub fn make_span(request: &Request<Body>) -> Span {
+    let span = tracing::span!(
+        Level::INFO,
+        "[REQUEST]",
+        method = tracing::field::display(request.method()),
+        uri = tracing::field::display(request.uri()),
+        request_id = tracing::field::display(request_id(request.headers()).unwrap_or_default())
+    );
+
+    span.set_parent(TraceContextPropagator::new().extract(&HeaderExtractor(request.headers())));
+    span
+}
+
+pub fn request_id(headers: &HeaderMap) -> Option<&str> {
+    headers.get(REQUEST_ID_HEADER).and_then(|value| value.to_str().ok())
+}
+
+/// `traceparent` and `tracestate` headers for the current span, so calls to
+/// the auth service join this trace.
+pub fn trace_context_headers() -> HashMap<String, String> {
+    let mut headers = HashMap::new();
+    TraceContextPropagator::new().inject_context(&Span::current().context(), &mut headers);
+    headers
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
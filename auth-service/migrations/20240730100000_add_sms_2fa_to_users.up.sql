@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE users ADD COLUMN phone_number TEXT;
+ALTER TABLE users ADD COLUMN two_fa_channel TEXT NOT NULL DEFAULT 'email' CHECK (two_fa_channel IN ('email', 'sms'));
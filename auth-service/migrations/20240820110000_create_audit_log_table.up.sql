@@ -0,0 +1,11 @@
+-- Add up migration script here
+-- Emails are not foreign keys so entries outlive the accounts they describe.
+CREATE TABLE IF NOT EXISTS audit_log(
+   id BIGSERIAL PRIMARY KEY,
+   event TEXT NOT NULL,
+   actor TEXT,
+   subject TEXT,
+   occurred_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS audit_log_subject_idx ON audit_log(subject, id DESC);
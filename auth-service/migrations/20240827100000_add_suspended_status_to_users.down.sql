@@ -0,0 +1,8 @@
+-- Add down migration script here
+ALTER TABLE users
+    DROP COLUMN status_reason,
+    DROP COLUMN status_changed_at;
+
+UPDATE users SET status = 'disabled' WHERE status = 'suspended';
+ALTER TABLE users DROP CONSTRAINT users_status_check;
+ALTER TABLE users ADD CONSTRAINT users_status_check CHECK (status IN ('active', 'disabled'));
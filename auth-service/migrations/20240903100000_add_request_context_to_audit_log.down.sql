@@ -0,0 +1,9 @@
+-- Add down migration script here
+DROP TRIGGER IF EXISTS audit_log_no_truncate ON audit_log;
+DROP TRIGGER IF EXISTS audit_log_append_only ON audit_log;
+DROP FUNCTION IF EXISTS reject_audit_log_change();
+
+ALTER TABLE audit_log
+   DROP COLUMN IF EXISTS request_id,
+   DROP COLUMN IF EXISTS user_agent,
+   DROP COLUMN IF EXISTS ip;
@@ -0,0 +1,20 @@
+-- Add up migration script here
+ALTER TABLE audit_log
+   ADD COLUMN ip TEXT,
+   ADD COLUMN user_agent TEXT,
+   ADD COLUMN request_id TEXT;
+
+-- Entries are evidence, so the table only ever grows.
+CREATE OR REPLACE FUNCTION reject_audit_log_change() RETURNS TRIGGER AS $$
+BEGIN
+   RAISE EXCEPTION 'audit_log is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_log_append_only
+   BEFORE UPDATE OR DELETE ON audit_log
+   FOR EACH ROW EXECUTE FUNCTION reject_audit_log_change();
+
+CREATE TRIGGER audit_log_no_truncate
+   BEFORE TRUNCATE ON audit_log
+   FOR EACH STATEMENT EXECUTE FUNCTION reject_audit_log_change();
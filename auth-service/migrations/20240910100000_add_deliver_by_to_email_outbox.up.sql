@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE email_outbox ADD COLUMN deliver_by TIMESTAMPTZ;
@@ -0,0 +1,5 @@
+-- Add down migration script here
+ALTER TABLE email_outbox DROP CONSTRAINT email_outbox_pending_has_body;
+UPDATE email_outbox SET html_body = '', text_body = '' WHERE html_body IS NULL OR text_body IS NULL;
+ALTER TABLE email_outbox ALTER COLUMN html_body SET NOT NULL;
+ALTER TABLE email_outbox ALTER COLUMN text_body SET NOT NULL;
@@ -0,0 +1,8 @@
+-- Add up migration script here
+-- Bodies hold 2FA codes and reset links, so dead-lettered emails keep only
+-- their metadata.
+ALTER TABLE email_outbox ALTER COLUMN html_body DROP NOT NULL;
+ALTER TABLE email_outbox ALTER COLUMN text_body DROP NOT NULL;
+UPDATE email_outbox SET html_body = NULL, text_body = NULL WHERE status = 'dead';
+ALTER TABLE email_outbox ADD CONSTRAINT email_outbox_pending_has_body
+   CHECK (status = 'dead' OR (html_body IS NOT NULL AND text_body IS NOT NULL));
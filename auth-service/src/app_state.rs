@@ -1,33 +1,101 @@
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use metrics_exporter_prometheus::PrometheusHandle;
+use webauthn_rs::Webauthn;
 
-use crate::domain::{BannedTokenStore, EmailClient, TwoFACodeStore, UserStore};
+use crate::{
+    domain::{
+        AuditLog, BannedTokenStore, BreachedPasswordChecker, EmailClient, EmailOutbox, HealthCheck, PasskeyStore, PasswordPolicy,
+        PasswordResetTokenStore, PhoneVerificationStore, SmsClient, TrustedDeviceStore, TwoFACodeStore, UserStore, WebAuthnChallengeStore
+    },
+    services::{AccountStatusCache, EmailTemplates, Health},
+    utils::settings::Settings
+};
 
-pub type UserStoreType = Arc<RwLock<dyn UserStore + Send + Sync>>;
-pub type BannedTokenStoreType = Arc<RwLock<dyn BannedTokenStore + Send + Sync>>;
-pub type TwoFACodeStoreType = Arc<RwLock<dyn TwoFACodeStore + Send + Sync>>;
-pub type EmailClientType = Arc<RwLock<dyn EmailClient + Send + Sync>>;
+pub type UserStoreType = Arc<dyn UserStore + Send + Sync>;
+pub type BannedTokenStoreType = Arc<dyn BannedTokenStore + Send + Sync>;
+pub type TwoFACodeStoreType = Arc<dyn TwoFACodeStore + Send + Sync>;
+pub type EmailClientType = Arc<dyn EmailClient + Send + Sync>;
+pub type EmailOutboxType = Arc<dyn EmailOutbox + Send + Sync>;
+pub type PasskeyStoreType = Arc<dyn PasskeyStore + Send + Sync>;
+pub type WebAuthnChallengeStoreType = Arc<dyn WebAuthnChallengeStore + Send + Sync>;
+pub type TrustedDeviceStoreType = Arc<dyn TrustedDeviceStore + Send + Sync>;
+pub type SmsClientType = Arc<dyn SmsClient + Send + Sync>;
+pub type PhoneVerificationStoreType = Arc<dyn PhoneVerificationStore + Send + Sync>;
+pub type BreachedPasswordCheckerType = Arc<dyn BreachedPasswordChecker + Send + Sync>;
+pub type PasswordResetTokenStoreType = Arc<dyn PasswordResetTokenStore + Send + Sync>;
+pub type AuditLogType = Arc<dyn AuditLog + Send + Sync>;
+pub type HealthCheckType = Arc<dyn HealthCheck + Send + Sync>;
 
 #[derive(Clone)]
 pub struct AppState {
     pub user_store: UserStoreType,
     pub banned_token_store: BannedTokenStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
-    pub email_client: EmailClientType
+    pub email_outbox: EmailOutboxType,
+    pub passkey_store: PasskeyStoreType,
+    pub webauthn_challenge_store: WebAuthnChallengeStoreType,
+    pub webauthn: Arc<Webauthn>,
+    pub trusted_device_store: TrustedDeviceStoreType,
+    pub email_templates: Arc<EmailTemplates>,
+    pub sms_client: SmsClientType,
+    pub phone_verification_store: PhoneVerificationStoreType,
+    pub password_policy: Arc<PasswordPolicy>,
+    pub breached_password_checker: BreachedPasswordCheckerType,
+    pub password_reset_token_store: PasswordResetTokenStoreType,
+    pub audit_log: AuditLogType,
+    /// Set when tokens are checked against the account status.
+    pub account_status_cache: Option<Arc<AccountStatusCache>>,
+    pub settings: Arc<Settings>,
+    pub health: Arc<Health>,
+    pub metrics: PrometheusHandle
+}
+
+/// Where the service keeps its data.
+pub struct Stores {
+    pub user_store: UserStoreType,
+    pub banned_token_store: BannedTokenStoreType,
+    pub two_fa_code_store: TwoFACodeStoreType,
+    pub email_outbox: EmailOutboxType,
+    pub passkey_store: PasskeyStoreType,
+    pub webauthn_challenge_store: WebAuthnChallengeStoreType,
+    pub trusted_device_store: TrustedDeviceStoreType,
+    pub phone_verification_store: PhoneVerificationStoreType,
+    pub password_reset_token_store: PasswordResetTokenStoreType,
+    pub audit_log: AuditLogType,
+}
+
+/// Clients and helpers the routes call on.
+pub struct Services {
+    pub webauthn: Arc<Webauthn>,
+    pub email_templates: Arc<EmailTemplates>,
+    pub sms_client: SmsClientType,
+    pub password_policy: Arc<PasswordPolicy>,
+    pub breached_password_checker: BreachedPasswordCheckerType,
+    pub account_status_cache: Option<Arc<AccountStatusCache>>,
 }
 
 impl AppState {
-    pub fn new(
-        user_store: UserStoreType,
-        banned_token_store: BannedTokenStoreType,
-        two_fa_code_store: TwoFACodeStoreType,
-        email_client: EmailClientType
-    ) -> Self {
+    pub fn new(stores: Stores, services: Services, settings: Arc<Settings>, health: Arc<Health>, metrics: PrometheusHandle) -> Self {
         Self {
-            user_store,
-            banned_token_store,
-            two_fa_code_store,
-            email_client
+            user_store: stores.user_store,
+            banned_token_store: stores.banned_token_store,
+            two_fa_code_store: stores.two_fa_code_store,
+            email_outbox: stores.email_outbox,
+            passkey_store: stores.passkey_store,
+            webauthn_challenge_store: stores.webauthn_challenge_store,
+            webauthn: services.webauthn,
+            trusted_device_store: stores.trusted_device_store,
+            email_templates: services.email_templates,
+            sms_client: services.sms_client,
+            phone_verification_store: stores.phone_verification_store,
+            password_policy: services.password_policy,
+            breached_password_checker: services.breached_password_checker,
+            password_reset_token_store: stores.password_reset_token_store,
+            audit_log: stores.audit_log,
+            account_status_cache: services.account_status_cache,
+            settings,
+            health,
+            metrics
         }
     }
 }
@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+
+/// Role seeded for operators. It grants every `users:*` permission.
+pub const ADMIN_ROLE: &str = "admin";
+
+/// Listing and viewing user accounts.
+pub const USERS_READ_PERMISSION: &str = "users:read";
+
+/// Changing user accounts on someone else's behalf.
+pub const USERS_ADMIN_PERMISSION: &str = "users:admin";
+
+/// The roles a user holds and the permissions those roles grant, sorted and
+/// without duplicates.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Access {
+    pub roles: Vec<String>,
+    pub permissions: Vec<String>,
+}
+
+impl Access {
+    pub fn has_permission(&self, permission: &str) -> bool {
+        self.permissions.iter().any(|granted| granted == permission)
+    }
+}
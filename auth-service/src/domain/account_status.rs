@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+
+/// Whether a user may sign in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccountStatus {
+    #[default]
+    Active,
+    /// Temporarily blocked by an operator, e.g. while a report is looked
+    /// into. Sessions are kept and work again once the account is enabled.
+    Suspended,
+    /// Blocked by an operator until they enable the account again. Every
+    /// session is revoked.
+    Disabled,
+}
+
+impl AccountStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccountStatus::Active => "active",
+            AccountStatus::Suspended => "suspended",
+            AccountStatus::Disabled => "disabled",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<AccountStatus> {
+        match value {
+            "active" => Some(AccountStatus::Active),
+            "suspended" => Some(AccountStatus::Suspended),
+            "disabled" => Some(AccountStatus::Disabled),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::Email;
+
+/// Something worth keeping a permanent record of.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEvent {
+    Signup,
+    /// An auth token was issued, after 2FA when the user needs it.
+    LoginSucceeded,
+    /// The email and password did not match.
+    LoginFailed,
+    /// A 2FA code was sent, or a passkey assertion was requested, after a
+    /// correct password.
+    #[serde(rename = "two_fa_issued")]
+    TwoFAIssued,
+    /// A 2FA code or passkey was accepted after a correct password.
+    #[serde(rename = "two_fa_verified")]
+    TwoFAVerified,
+    /// A passkey was accepted in place of a password.
+    PasskeyVerified,
+    Logout,
+    PasswordChanged,
+    /// The password was replaced using an emailed reset link.
+    PasswordReset,
+    PasswordResetForced,
+    AccountSuspended,
+    AccountDisabled,
+    AccountEnabled,
+    #[serde(rename = "two_fa_reset")]
+    TwoFAReset,
+    SessionsRevoked,
+}
+
+impl AuditEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditEvent::Signup => "signup",
+            AuditEvent::LoginSucceeded => "login_succeeded",
+            AuditEvent::LoginFailed => "login_failed",
+            AuditEvent::TwoFAIssued => "two_fa_issued",
+            AuditEvent::TwoFAVerified => "two_fa_verified",
+            AuditEvent::PasskeyVerified => "passkey_verified",
+            AuditEvent::Logout => "logout",
+            AuditEvent::PasswordChanged => "password_changed",
+            AuditEvent::PasswordReset => "password_reset",
+            AuditEvent::PasswordResetForced => "password_reset_forced",
+            AuditEvent::AccountSuspended => "account_suspended",
+            AuditEvent::AccountDisabled => "account_disabled",
+            AuditEvent::AccountEnabled => "account_enabled",
+            AuditEvent::TwoFAReset => "two_fa_reset",
+            AuditEvent::SessionsRevoked => "sessions_revoked",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<AuditEvent> {
+        match value {
+            "signup" => Some(AuditEvent::Signup),
+            "login_succeeded" => Some(AuditEvent::LoginSucceeded),
+            "login_failed" => Some(AuditEvent::LoginFailed),
+            "two_fa_issued" => Some(AuditEvent::TwoFAIssued),
+            "two_fa_verified" => Some(AuditEvent::TwoFAVerified),
+            "passkey_verified" => Some(AuditEvent::PasskeyVerified),
+            "logout" => Some(AuditEvent::Logout),
+            "password_changed" => Some(AuditEvent::PasswordChanged),
+            "password_reset" => Some(AuditEvent::PasswordReset),
+            "password_reset_forced" => Some(AuditEvent::PasswordResetForced),
+            "account_suspended" => Some(AuditEvent::AccountSuspended),
+            "account_disabled" => Some(AuditEvent::AccountDisabled),
+            "account_enabled" => Some(AuditEvent::AccountEnabled),
+            "two_fa_reset" => Some(AuditEvent::TwoFAReset),
+            "sessions_revoked" => Some(AuditEvent::SessionsRevoked),
+            _ => None,
+        }
+    }
+}
+
+/// Where a request came from, as far as the service can tell.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RequestContext {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
+}
+
+/// One audit log entry. `actor` is the admin who did it, an email or
+/// `api-key`, and is empty for the user's own actions. `subject` is the
+/// account it was done to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuditEntry {
+    pub event: AuditEvent,
+    pub actor: Option<String>,
+    pub subject: Option<Email>,
+    pub context: RequestContext,
+    pub occurred_at: DateTime<Utc>,
+}
+
+impl AuditEntry {
+    pub fn new(event: AuditEvent, actor: Option<String>, subject: Option<Email>) -> Self {
+        AuditEntry {
+            event,
+            actor,
+            subject,
+            context: RequestContext::default(),
+            occurred_at: Utc::now(),
+        }
+    }
+
+    pub fn with_context(self, context: RequestContext) -> Self {
+        AuditEntry { context, ..self }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_are_stored_by_name() {
+        let events = [
+            AuditEvent::Signup, AuditEvent::LoginSucceeded, AuditEvent::LoginFailed, AuditEvent::TwoFAIssued,
+            AuditEvent::TwoFAVerified, AuditEvent::PasskeyVerified, AuditEvent::Logout, AuditEvent::PasswordChanged, AuditEvent::PasswordReset,
+            AuditEvent::PasswordResetForced, AuditEvent::AccountSuspended, AuditEvent::AccountDisabled,
+            AuditEvent::AccountEnabled, AuditEvent::TwoFAReset, AuditEvent::SessionsRevoked,
+        ];
+
+        for event in events {
+            assert_eq!(AuditEvent::parse(event.as_str()), Some(event));
+            assert_eq!(serde_json::to_value(event).unwrap(), event.as_str());
+        }
+        assert_eq!(AuditEvent::parse("unknown"), None);
+    }
+}
@@ -0,0 +1,9 @@
+use color_eyre::eyre::Result;
+use secrecy::Secret;
+
+/// Looks passwords up in a corpus of breached passwords.
+#[async_trait::async_trait]
+pub trait BreachedPasswordChecker {
+    /// How often the password appears in the corpus, 0 if it was never seen.
+    async fn breach_count(&self, password: &Secret<String>) -> Result<u64>;
+}
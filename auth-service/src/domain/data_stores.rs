@@ -1,8 +1,13 @@
-use super::{Email, LoginAttemptId, Password, TwoFACode, User};
-use color_eyre::eyre::{Report, Result, eyre};
-use rand::Rng;
+use super::{
+    Access, AccountStatus, AuditEntry, Email, EmailOutboxStats, LoginAttemptId, OutboxEmail, PasskeyLogin, Password, PasswordResetToken,
+    PhoneNumber, TrustedDevice, TwoFACode, TwoFAChannel, User, UserPage
+};
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::Report;
 use secrecy::Secret;
 use thiserror::Error;
+use uuid::Uuid;
+use webauthn_rs::prelude::{Passkey, PasskeyAuthentication, PasskeyRegistration};
 
 #[derive(Debug, Error)]
 pub enum UserStoreError {
@@ -12,6 +17,10 @@ pub enum UserStoreError {
     UserNotFound,
     #[error("Invalid credentials")]
     InvalidCredentials,
+    #[error("Password was used recently")]
+    PasswordReused,
+    #[error("Role not found")]
+    RoleNotFound,
     #[error("Unexpected error")]
     UnexpectedError(#[source] Report),
 }
@@ -23,6 +32,8 @@ impl PartialEq for UserStoreError {
             (Self::UserAlreadyExists, Self::UserAlreadyExists)
             | (Self::UserNotFound, Self::UserNotFound)
             | (Self::InvalidCredentials, Self::InvalidCredentials)
+            | (Self::PasswordReused, Self::PasswordReused)
+            | (Self::RoleNotFound, Self::RoleNotFound)
             | (Self::UnexpectedError(_), Self::UnexpectedError(_))
         )
     }
@@ -30,9 +41,30 @@ impl PartialEq for UserStoreError {
 
 #[async_trait::async_trait]
 pub trait UserStore {
-    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError>;
+    async fn add_user(&self, user: User) -> Result<(), UserStoreError>;
     async fn get_user(&self, email: Email) -> Result<User, UserStoreError>;
     async fn validate_user(&self, email: Email, password: Password) -> Result<(), UserStoreError> ;
+    /// Replaces the user's password, failing with `PasswordReused` when it
+    /// matches one of their recent passwords. Clears a required reset.
+    async fn update_password(&self, email: &Email, password: Password) -> Result<(), UserStoreError>;
+    /// Saves a phone number the user has verified.
+    async fn set_phone_number(&self, email: &Email, phone_number: PhoneNumber) -> Result<(), UserStoreError>;
+    async fn set_two_fa_channel(&self, email: &Email, channel: TwoFAChannel) -> Result<(), UserStoreError>;
+    /// The user's roles and everything they grant.
+    async fn get_access(&self, email: &Email) -> Result<Access, UserStoreError>;
+    /// Gives the user a role. Assigning a role they already hold is a no-op.
+    async fn assign_role(&self, email: &Email, role: &str) -> Result<(), UserStoreError>;
+    async fn remove_role(&self, email: &Email, role: &str) -> Result<(), UserStoreError>;
+    /// Users whose email contains `search`, ordered by email.
+    async fn list_users(&self, search: Option<&str>, offset: u64, limit: u64) -> Result<UserPage, UserStoreError>;
+    /// Records the new status with the operator's reason and the time.
+    async fn set_status(&self, email: &Email, status: AccountStatus, reason: Option<String>) -> Result<(), UserStoreError>;
+    /// Just the status, for checks that run on every request.
+    async fn get_status(&self, email: &Email) -> Result<AccountStatus, UserStoreError>;
+    /// Blocks sign in until the user sets a new password.
+    async fn require_password_reset(&self, email: &Email) -> Result<(), UserStoreError>;
+    /// Forgets the user's phone number so 2FA codes go by email again.
+    async fn reset_two_fa(&self, email: &Email) -> Result<(), UserStoreError>;
 }
 
 #[derive(Debug, Error)]
@@ -44,8 +76,13 @@ pub enum BannedTokenStoreError {
 
 #[async_trait::async_trait]
 pub trait BannedTokenStore {
-    async fn store_banned_token(&mut self, token: Secret<String>) -> Result<(), BannedTokenStoreError>;
+    async fn store_banned_token(&self, token: Secret<String>) -> Result<(), BannedTokenStoreError>;
     async fn check_banned_token(&self, token: Secret<String>) -> Result<bool, BannedTokenStoreError>;
+    /// Invalidates every token issued to the user so far by moving them to
+    /// a new session epoch.
+    async fn revoke_sessions(&self, email: &Email) -> Result<(), BannedTokenStoreError>;
+    /// The epoch new tokens are minted in. Starts at 0.
+    async fn session_epoch(&self, email: &Email) -> Result<u64, BannedTokenStoreError>;
 }
 
 #[derive(Debug, Error)]
@@ -67,13 +104,13 @@ impl PartialEq for TwoFACodeStoreError {
 }
 #[async_trait::async_trait]
 pub trait TwoFACodeStore {
-    async fn add_code(&mut self, 
+    async fn add_code(&self, 
         email: Email,
         login_attempt_id: LoginAttemptId,
         code: TwoFACode
     ) -> Result<(), TwoFACodeStoreError>;
 
-    async fn remove_code(&mut self, 
+    async fn remove_code(&self, 
         email: &Email
     ) -> Result<(), TwoFACodeStoreError>;
 
@@ -83,3 +120,246 @@ pub trait TwoFACodeStore {
     ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError>;
 }
 
+#[derive(Debug, Error)]
+pub enum PasskeyStoreError {
+    #[error("Passkey already exists")]
+    PasskeyAlreadyExists,
+    #[error("Passkey not found")]
+    PasskeyNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for PasskeyStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::PasskeyAlreadyExists, Self::PasskeyAlreadyExists)
+            | (Self::PasskeyNotFound, Self::PasskeyNotFound)
+            | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[async_trait::async_trait]
+pub trait PasskeyStore {
+    async fn add_passkey(&self, email: Email, passkey: Passkey) -> Result<(), PasskeyStoreError>;
+    async fn get_passkeys(&self, email: &Email) -> Result<Vec<Passkey>, PasskeyStoreError>;
+    async fn update_passkey(&self, email: &Email, passkey: Passkey) -> Result<(), PasskeyStoreError>;
+    async fn remove_passkeys(&self, email: &Email) -> Result<(), PasskeyStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum WebAuthnChallengeStoreError {
+    #[error("Challenge not found")]
+    ChallengeNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for WebAuthnChallengeStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::ChallengeNotFound, Self::ChallengeNotFound)
+            | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+/// Holds the server side state of in-flight WebAuthn ceremonies. Each state
+/// can only be taken once so a challenge cannot be replayed. Authentications
+/// are keyed by login attempt, so starting one never replaces another.
+#[async_trait::async_trait]
+pub trait WebAuthnChallengeStore {
+    async fn add_registration(&self,
+        email: Email,
+        state: PasskeyRegistration
+    ) -> Result<(), WebAuthnChallengeStoreError>;
+
+    async fn take_registration(&self,
+        email: &Email
+    ) -> Result<PasskeyRegistration, WebAuthnChallengeStoreError>;
+
+    async fn add_authentication(&self,
+        email: Email,
+        login_attempt_id: LoginAttemptId,
+        kind: PasskeyLogin,
+        state: PasskeyAuthentication
+    ) -> Result<(), WebAuthnChallengeStoreError>;
+
+    async fn take_authentication(&self,
+        login_attempt_id: &LoginAttemptId
+    ) -> Result<(Email, PasskeyLogin, PasskeyAuthentication), WebAuthnChallengeStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum TrustedDeviceStoreError {
+    #[error("Trusted device not found")]
+    TrustedDeviceNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for TrustedDeviceStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::TrustedDeviceNotFound, Self::TrustedDeviceNotFound)
+            | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[async_trait::async_trait]
+pub trait TrustedDeviceStore {
+    async fn add_device(&self, email: Email, device: TrustedDevice) -> Result<(), TrustedDeviceStoreError>;
+    /// Lists the devices that are neither revoked nor expired.
+    async fn get_devices(&self, email: &Email) -> Result<Vec<TrustedDevice>, TrustedDeviceStoreError>;
+    async fn is_trusted(&self, email: &Email, device_id: Uuid) -> Result<bool, TrustedDeviceStoreError>;
+    async fn revoke_device(&self, email: &Email, device_id: Uuid) -> Result<(), TrustedDeviceStoreError>;
+    async fn revoke_all_devices(&self, email: &Email) -> Result<(), TrustedDeviceStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum EmailOutboxError {
+    #[error("Email not found")]
+    EmailNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for EmailOutboxError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::EmailNotFound, Self::EmailNotFound)
+            | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+/// Durable queue of outgoing emails drained by the delivery worker.
+/// Delivered emails are removed, and emails that keep failing are kept
+/// in a dead-letter state for inspection.
+#[async_trait::async_trait]
+pub trait EmailOutbox {
+    async fn enqueue(&self, email: OutboxEmail) -> Result<(), EmailOutboxError>;
+
+    /// Claims up to `limit` pending emails that are due and hides them from
+    /// other workers until `lease_until`. Claiming counts as an attempt.
+    async fn claim_due(&self,
+        limit: u32,
+        lease_until: DateTime<Utc>
+    ) -> Result<Vec<OutboxEmail>, EmailOutboxError>;
+
+    async fn mark_sent(&self, id: Uuid) -> Result<(), EmailOutboxError>;
+
+    async fn retry_later(&self,
+        id: Uuid,
+        error: &str,
+        retry_at: DateTime<Utc>
+    ) -> Result<(), EmailOutboxError>;
+
+    async fn dead_letter(&self, id: Uuid, error: &str) -> Result<(), EmailOutboxError>;
+
+    async fn stats(&self) -> Result<EmailOutboxStats, EmailOutboxError>;
+}
+
+#[derive(Debug, Error)]
+pub enum PhoneVerificationStoreError {
+    #[error("Verification code not found")]
+    CodeNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for PhoneVerificationStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::CodeNotFound, Self::CodeNotFound)
+            | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+/// Holds the code texted to a phone number that is waiting to be verified.
+/// A code can only be taken once, so a wrong guess means starting over.
+#[async_trait::async_trait]
+pub trait PhoneVerificationStore {
+    async fn add_code(&self,
+        email: Email,
+        phone_number: PhoneNumber,
+        code: TwoFACode
+    ) -> Result<(), PhoneVerificationStoreError>;
+
+    async fn take_code(&self,
+        email: &Email
+    ) -> Result<(PhoneNumber, TwoFACode), PhoneVerificationStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum PasswordResetTokenStoreError {
+    #[error("Password reset token not found")]
+    TokenNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for PasswordResetTokenStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::TokenNotFound, Self::TokenNotFound)
+            | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+/// A reset token taken from the store, with what is needed to put it back
+/// without extending its life.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TakenPasswordResetToken {
+    pub email: Email,
+    pub expires_at: DateTime<Utc>,
+    /// New passwords already rejected for this token.
+    pub rejected_attempts: u32,
+}
+
+/// Emailed password reset tokens waiting to be used. A token can only be
+/// taken once.
+#[async_trait::async_trait]
+pub trait PasswordResetTokenStore {
+    async fn add_token(&self,
+        token: PasswordResetToken,
+        email: Email
+    ) -> Result<(), PasswordResetTokenStoreError>;
+
+    async fn take_token(&self,
+        token: &PasswordResetToken
+    ) -> Result<TakenPasswordResetToken, PasswordResetTokenStoreError>;
+
+    /// Puts a taken token back until its original expiry. Does nothing once
+    /// it has expired.
+    async fn restore_token(&self,
+        token: PasswordResetToken,
+        taken: TakenPasswordResetToken
+    ) -> Result<(), PasswordResetTokenStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum AuditLogError {
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+/// Append-only record of security relevant actions. Entries are never
+/// changed or removed.
+#[async_trait::async_trait]
+pub trait AuditLog {
+    async fn record(&self, entry: AuditEntry) -> Result<(), AuditLogError>;
+
+    /// The latest entries about `subject`, newest first.
+    async fn recent_entries(&self, subject: &Email, limit: u64) -> Result<Vec<AuditEntry>, AuditLogError>;
+}
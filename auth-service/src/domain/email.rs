@@ -1,4 +1,3 @@
-use sqlx::{postgres::PgRow, Error, FromRow, Row};
 use validator::validate_email;
 use color_eyre::eyre::{eyre, Result};
 use std::hash::Hash;
@@ -10,7 +9,7 @@ pub struct Email(Secret<String>);
 impl Email {
     pub fn parse(email: Secret<String>) -> Result<Email> {
         if !validate_email(email.expose_secret()) {
-            return Err(eyre!(format!("Invalid email address: {}", email.expose_secret())));
+            Err(eyre!("Invalid email address"))
         } else {
             Ok(Self(email))
         }
@@ -60,7 +59,7 @@ mod tests {
         let email = Secret::new("user@mail.com".to_string());
 
         let result = Email::parse(email.clone()).is_ok();
-        assert_eq!(result, true)
+        assert!(result)
     }
 
     #[test]
@@ -68,7 +67,7 @@ mod tests {
         let email = Secret::new("user.mail.com".to_string());
 
         let result = Email::parse(email.clone()).is_err();
-        assert_eq!(result, true)
+        assert!(result)
     }
 
     #[test]
@@ -76,8 +75,17 @@ mod tests {
         let email = Secret::new("     @     mail.com".to_string());
 
         let result = Email::parse(email.clone()).is_err();
-        assert_eq!(result, true)
+        assert!(result)
     }
 
 
+
+    #[test]
+    fn test_email_is_redacted_in_debug_and_errors() {
+        let email = Email::parse(Secret::new("user@mail.com".to_string())).unwrap();
+        assert!(!format!("{:?}", email).contains("user@mail.com"));
+
+        let error = Email::parse(Secret::new("user.mail.com".to_string())).unwrap_err();
+        assert!(!format!("{:?}", error).contains("user.mail.com"));
+    }
 }
@@ -1,4 +1,4 @@
-use super::Email;
+use super::{Email, RenderedEmail};
 use color_eyre::eyre::Result;
 
 
@@ -9,4 +9,13 @@ pub trait EmailClient {
         subject: &str,
         content: &str
     ) -> Result<()>;
+
+    /// Sends both parts of a rendered template. Clients that cannot send
+    /// multipart messages fall back to the plain-text part.
+    async fn send_rendered_email(&self,
+        recipient: &Email,
+        email: &RenderedEmail
+    ) -> Result<()> {
+        self.send_email(recipient, &email.subject, &email.text_body).await
+    }
 }
\ No newline at end of file
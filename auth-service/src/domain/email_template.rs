@@ -0,0 +1,34 @@
+/// Transactional emails the service knows how to render.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EmailTemplate {
+    TwoFACode,
+    PasswordReset,
+    Verification,
+    SecurityAlert,
+}
+
+impl EmailTemplate {
+    pub const ALL: [EmailTemplate; 4] = [
+        EmailTemplate::TwoFACode,
+        EmailTemplate::PasswordReset,
+        EmailTemplate::Verification,
+        EmailTemplate::SecurityAlert,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EmailTemplate::TwoFACode => "two_fa_code",
+            EmailTemplate::PasswordReset => "password_reset",
+            EmailTemplate::Verification => "verification",
+            EmailTemplate::SecurityAlert => "security_alert",
+        }
+    }
+}
+
+/// A rendered email with both an HTML and a plain-text part.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderedEmail {
+    pub subject: String,
+    pub html_body: String,
+    pub text_body: String,
+}
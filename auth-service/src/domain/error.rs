@@ -1,18 +1,32 @@
 use color_eyre::eyre::Report;
 use thiserror::Error;
 
+use super::PasswordViolation;
+
 #[derive(Debug, Error)]
 pub enum AuthAPIError {
     #[error("User already exists")]
     UserAlreadyExists,
     #[error("Invalid credentials")]
     InvalidCredentials,
+    #[error("Weak password")]
+    WeakPassword(Vec<PasswordViolation>),
     #[error("Incorrect credentials")]
     IncorrectCredentials,
     #[error("Missing token")]
     MissingToken,
     #[error("Invalid token")]
     InvalidToken,
+    #[error("Forbidden")]
+    Forbidden,
+    #[error("Account suspended")]
+    AccountSuspended,
+    #[error("Account disabled")]
+    AccountDisabled,
+    #[error("Password reset required")]
+    PasswordResetRequired,
+    #[error("Not found")]
+    NotFound,
     #[error("Unexpected error")]
     UnexpectedError(#[source] Report),
 }
@@ -0,0 +1,9 @@
+use color_eyre::eyre::Result;
+
+/// A dependency the service needs in order to handle requests.
+#[async_trait::async_trait]
+pub trait HealthCheck {
+    fn name(&self) -> &'static str;
+
+    async fn check(&self) -> Result<()>;
+}
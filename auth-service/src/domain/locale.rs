@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+
+/// Languages the transactional emails are translated into.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    #[default]
+    En,
+    Es,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 2] = [Locale::En, Locale::Es];
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::Es => "es",
+        }
+    }
+
+    /// Parses a BCP 47 language tag such as `es` or `es-MX`, matching on
+    /// the primary language subtag only.
+    pub fn parse(tag: &str) -> Option<Locale> {
+        let primary = tag.trim().split(['-', '_']).next()?.to_lowercase();
+        Locale::ALL.into_iter().find(|locale| locale.code() == primary)
+    }
+
+    /// Picks the supported locale with the highest quality value from an
+    /// `Accept-Language` header.
+    pub fn from_accept_language(header: &str) -> Option<Locale> {
+        let mut candidates: Vec<(f32, Locale)> = header
+            .split(',')
+            .filter_map(|entry| {
+                let mut parts = entry.split(';');
+                let locale = Locale::parse(parts.next()?)?;
+                let quality = parts
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .map(|q| q.parse::<f32>().unwrap_or(0.0))
+                    .unwrap_or(1.0);
+                (quality > 0.0).then_some((quality, locale))
+            })
+            .collect();
+
+        // Stable sort keeps header order for equal quality values.
+        candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+        candidates.first().map(|(_, locale)| *locale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_locale() {
+        assert_eq!(Locale::parse("es-MX"), Some(Locale::Es));
+        assert_eq!(Locale::parse("EN"), Some(Locale::En));
+        assert_eq!(Locale::parse("de"), None);
+    }
+
+    #[test]
+    fn test_accept_language_picks_highest_quality() {
+        let locale = Locale::from_accept_language("de-DE, en;q=0.5, es-ES;q=0.8");
+        assert_eq!(locale, Some(Locale::Es));
+    }
+
+    #[test]
+    fn test_accept_language_keeps_order_for_equal_quality() {
+        let locale = Locale::from_accept_language("es, en");
+        assert_eq!(locale, Some(Locale::Es));
+    }
+
+    #[test]
+    fn test_accept_language_ignores_unsupported_and_rejected() {
+        assert_eq!(Locale::from_accept_language("fr, es;q=0"), None);
+        assert_eq!(Locale::from_accept_language(""), None);
+    }
+}
@@ -12,7 +12,7 @@ impl PartialEq for LoginAttemptId {
 }
 impl LoginAttemptId {
     pub fn parse(id: Secret<String>) -> Result<LoginAttemptId> {
-        let parsed_id = Uuid::parse_str(&id.expose_secret()).wrap_err("invalid login attempt id")?;
+        Uuid::parse_str(id.expose_secret()).wrap_err("invalid login attempt id")?;
         Ok(Self(id))
     }
 }
@@ -39,6 +39,6 @@ mod tests {
         let attempt_id = "ebfabdee-d0ca-416d-b4de-a0b01f5b2ec5".to_string();
 
         let result = LoginAttemptId::parse(Secret::new(attempt_id.clone())).is_ok();
-        assert_eq!(result, true)
+        assert!(result)
     }   
 }
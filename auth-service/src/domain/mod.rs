@@ -1,17 +1,47 @@
 mod user;
+pub mod access;
+pub mod account_status;
+pub mod audit_event;
 mod error;
 pub mod data_stores;
 pub mod email;
 mod password;
+pub mod password_policy;
 pub mod twofacode;
 pub mod loginattemptid;
+pub mod password_reset_token;
 pub mod email_client;
+pub mod email_template;
+pub mod locale;
+pub mod trusted_device;
+pub mod outbox_email;
+pub mod phone_number;
+pub mod sms_client;
+pub mod two_fa_channel;
+pub mod passkey_login;
+pub mod health_check;
+pub mod breached_password_checker;
 
 pub use user::*;
+pub use access::*;
+pub use account_status::*;
+pub use audit_event::*;
 pub use error::*;
 pub use data_stores::*;
 pub use email::*;
 pub use password::*;
+pub use password_policy::*;
 pub use twofacode::*;
 pub use loginattemptid::*;
+pub use password_reset_token::*;
 pub use email_client::*;
+pub use email_template::*;
+pub use locale::*;
+pub use trusted_device::*;
+pub use outbox_email::*;
+pub use phone_number::*;
+pub use sms_client::*;
+pub use two_fa_channel::*;
+pub use passkey_login::*;
+pub use health_check::*;
+pub use breached_password_checker::*;
@@ -0,0 +1,39 @@
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use super::{Email, RenderedEmail};
+
+/// An email waiting in the outbox for the delivery worker.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutboxEmail {
+    pub id: Uuid,
+    pub recipient: Email,
+    pub email: RenderedEmail,
+    /// Delivery attempts so far, including the one in progress once claimed.
+    pub attempts: u32,
+    /// When the email stops being useful, e.g. when the code in it expires.
+    /// It is dead-lettered instead of being sent after that.
+    pub deliver_by: Option<DateTime<Utc>>,
+}
+
+impl OutboxEmail {
+    pub fn new(recipient: Email, email: RenderedEmail) -> Self {
+        OutboxEmail {
+            id: Uuid::new_v4(),
+            recipient,
+            email,
+            attempts: 0,
+            deliver_by: None,
+        }
+    }
+
+    pub fn with_deadline(self, deliver_by: DateTime<Utc>) -> Self {
+        OutboxEmail { deliver_by: Some(deliver_by), ..self }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EmailOutboxStats {
+    pub pending: u64,
+    pub dead: u64,
+}
@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+/// Whether a passkey assertion follows a correct password or replaces it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PasskeyLogin {
+    SecondFactor,
+    /// Also assumed for challenges stored before the kind was recorded, so
+    /// they never count as a passed second factor.
+    #[default]
+    Passwordless,
+}
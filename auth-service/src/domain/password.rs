@@ -1,4 +1,3 @@
-use sqlx::{postgres::PgRow, Error, FromRow, Row};
 use color_eyre::eyre::{eyre, Result};
 use secrecy::{ExposeSecret, Secret};
 
@@ -12,9 +11,11 @@ impl PartialEq for Password {
 }
 
 impl Password {
+    /// Only rejects blank passwords. Length and the other rules belong to
+    /// [`super::PasswordPolicy`], which checks new passwords.
     pub fn parse(password: Secret<String>) -> Result<Password> {
-        if password.expose_secret().is_empty() || password.expose_secret().trim().to_string().capacity() < 8 {
-            return Err(eyre!("Invalid password".to_string()));
+        if password.expose_secret().trim().is_empty() {
+            Err(eyre!("Invalid password".to_string()))
         } else {
             Ok(Self(password))
         }
@@ -49,10 +50,19 @@ mod tests {
 
     #[test]
     fn test_invalid_password() {
-        let password = Secret::new("passwor".to_string());
+        assert!(Password::parse(Secret::new("".to_string())).is_err());
+        assert!(Password::parse(Secret::new("   ".to_string())).is_err());
+    }
 
-        let result = Password::parse(password.clone()).is_err();
+    #[test]
+    fn test_short_passwords_are_left_to_the_policy() {
+        assert!(Password::parse(Secret::new("pass".to_string())).is_ok());
+    }
+
+    #[test]
+    fn test_password_is_redacted_in_debug() {
+        let password = Password::parse(Secret::new("password123".to_string())).unwrap();
 
-        assert!(result);
+        assert!(!format!("{:?}", password).contains("password123"));
     }
 }
@@ -0,0 +1,308 @@
+use std::collections::HashSet;
+
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::Email;
+
+const COMMON_PASSWORDS: &str = include_str!("../../data/common_passwords.txt");
+
+/// Lowest `min_length` the policy can be configured with, in characters.
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// Sequences people walk along on a keyboard, e.g. `asdf` or `7890`.
+const KEYBOARD_ROWS: &[&str] = &["1234567890", "qwertyuiop", "asdfghjkl", "zxcvbnm"];
+
+/// Rules a new password must satisfy. Existing passwords are not checked
+/// again at login, so tightening the policy never locks anyone out.
+#[derive(Clone, Debug)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub require_lowercase: bool,
+    pub require_uppercase: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+    /// Minimum [`strength`] score, from 0 (guessable in a few tries) to 4.
+    pub min_strength: u8,
+    common_passwords: HashSet<String>,
+}
+
+/// Why a password was rejected. Serialized as `{"rule": "too_short", "min": 8}`
+/// and so on, so clients can show their own messages.
+#[derive(Clone, Debug, Error, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum PasswordViolation {
+    #[error("Use at least {min} characters")]
+    TooShort { min: usize },
+    #[error("Use at most {max} characters")]
+    TooLong { max: usize },
+    #[error("Add a lowercase letter")]
+    MissingLowercase,
+    #[error("Add an uppercase letter")]
+    MissingUppercase,
+    #[error("Add a digit")]
+    MissingDigit,
+    #[error("Add a symbol")]
+    MissingSymbol,
+    #[error("This password is too common")]
+    Common,
+    #[error("Do not use your email address in your password")]
+    ContainsEmail,
+    #[error("This password is too easy to guess")]
+    TooWeak { score: u8, min: u8 },
+    /// Reported by signup from a [`super::BreachedPasswordChecker`] rather
+    /// than by the policy itself.
+    #[error("This password has appeared in a data breach")]
+    Breached,
+    /// Reported by password changes from the user store's history.
+    #[error("Do not reuse any of your last {last} passwords")]
+    Reused { last: usize },
+}
+
+impl PasswordPolicy {
+    pub fn new(min_length: usize, max_length: usize) -> Self {
+        Self {
+            min_length,
+            max_length,
+            require_lowercase: false,
+            require_uppercase: false,
+            require_digit: false,
+            require_symbol: false,
+            min_strength: 0,
+            common_passwords: word_list(COMMON_PASSWORDS),
+        }
+    }
+
+    /// Returns every rule the password breaks, in a stable order.
+    pub fn check(&self, password: &Secret<String>, email: &Email) -> Result<(), Vec<PasswordViolation>> {
+        let password = password.expose_secret();
+        let length = password.chars().count();
+        let local_part = email_local_part(email);
+        let mut violations = Vec::new();
+
+        if length < self.min_length {
+            violations.push(PasswordViolation::TooShort { min: self.min_length });
+        }
+
+        if length > self.max_length {
+            violations.push(PasswordViolation::TooLong { max: self.max_length });
+        }
+
+        let classes = [
+            (self.require_lowercase, char::is_lowercase as fn(char) -> bool, PasswordViolation::MissingLowercase),
+            (self.require_uppercase, char::is_uppercase, PasswordViolation::MissingUppercase),
+            (self.require_digit, |c: char| c.is_ascii_digit(), PasswordViolation::MissingDigit),
+            (self.require_symbol, |c: char| !c.is_alphanumeric(), PasswordViolation::MissingSymbol),
+        ];
+
+        for (required, class, violation) in classes {
+            if required && !password.chars().any(class) {
+                violations.push(violation);
+            }
+        }
+
+        if self.common_passwords.contains(&password.to_lowercase()) {
+            violations.push(PasswordViolation::Common);
+        }
+
+        if local_part.chars().count() >= 3 && password.to_lowercase().contains(&local_part) {
+            violations.push(PasswordViolation::ContainsEmail);
+        }
+
+        let score = strength(password, &self.common_passwords, &[&local_part]);
+        if score < self.min_strength {
+            violations.push(PasswordViolation::TooWeak { score, min: self.min_strength });
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+}
+
+fn word_list(text: &str) -> HashSet<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn email_local_part(email: &Email) -> String {
+    let email = email.as_ref().expose_secret().to_lowercase();
+    email.split('@').next().unwrap_or_default().to_owned()
+}
+
+/// Scores how hard a password is to guess, from 0 to 4, in the spirit of
+/// zxcvbn. The password is split greedily into the cheapest known patterns
+/// (dictionary words, the user's own inputs, repeats, sequences and keyboard
+/// runs), anything left is brute forced, and the estimated guesses are
+/// bucketed with zxcvbn's thresholds.
+pub fn strength(password: &str, dictionary: &HashSet<String>, user_inputs: &[&str]) -> u8 {
+    let chars: Vec<char> = password.to_lowercase().chars().collect();
+    let mut log10_guesses = 0.0;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let rest = &chars[i..];
+        let (length, log10_cost) = [
+            dictionary_match(rest, dictionary.iter().map(String::as_str), dictionary.len()),
+            dictionary_match(rest, user_inputs.iter().copied(), 1),
+            repeat_match(rest),
+            sequence_match(rest),
+            keyboard_match(rest),
+        ]
+        .into_iter()
+        .flatten()
+        .max_by(|a, b| a.0.cmp(&b.0).then(b.1.total_cmp(&a.1)))
+        // zxcvbn's brute force cardinality for anything unmatched.
+        .unwrap_or((1, 1.0));
+
+        log10_guesses += log10_cost;
+        i += length;
+    }
+
+    match log10_guesses {
+        g if g < 3.0 => 0,
+        g if g < 6.0 => 1,
+        g if g < 8.0 => 2,
+        g if g < 10.0 => 3,
+        _ => 4,
+    }
+}
+
+/// The longest word that starts the rest of the password, costing about as
+/// many guesses as there are words.
+fn dictionary_match<'a>(rest: &[char], words: impl Iterator<Item = &'a str>, word_count: usize) -> Option<(usize, f64)> {
+    words
+        .filter(|word| word.chars().count() >= 3)
+        .filter(|word| word.chars().count() <= rest.len() && word.chars().zip(rest).all(|(a, b)| a == *b))
+        .map(|word| word.chars().count())
+        .max()
+        .map(|length| (length, (word_count.max(1) as f64).log10()))
+}
+
+fn repeat_match(rest: &[char]) -> Option<(usize, f64)> {
+    let length = rest.iter().take_while(|c| **c == rest[0]).count();
+    (length >= 3).then(|| (length, (10.0 * length as f64).log10()))
+}
+
+/// Runs like `abcd` or `9876`.
+fn sequence_match(rest: &[char]) -> Option<(usize, f64)> {
+    let step = (*rest.get(1)? as i64) - (rest[0] as i64);
+    if step.abs() != 1 {
+        return None;
+    }
+
+    let length = 1 + rest.windows(2)
+        .take_while(|pair| pair[1] as i64 - pair[0] as i64 == step)
+        .count();
+    (length >= 3).then(|| (length, (26.0 * length as f64).log10()))
+}
+
+fn keyboard_match(rest: &[char]) -> Option<(usize, f64)> {
+    KEYBOARD_ROWS.iter()
+        .filter_map(|row| {
+            let row: Vec<char> = row.chars().collect();
+            let start = row.iter().position(|c| *c == rest[0])?;
+            Some(row[start..].iter().zip(rest).take_while(|(a, b)| a == b).count())
+        })
+        .max()
+        .filter(|length| *length >= 4)
+        .map(|length| (length, (KEYBOARD_ROWS.len() as f64 * 10.0 * length as f64).log10()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email() -> Email {
+        Email::parse(Secret::new("jane.doe@example.com".to_owned())).unwrap()
+    }
+
+    fn policy() -> PasswordPolicy {
+        PasswordPolicy { min_strength: 3, ..PasswordPolicy::new(8, 64) }
+    }
+
+    fn check(policy: &PasswordPolicy, password: &str) -> Result<(), Vec<PasswordViolation>> {
+        policy.check(&Secret::new(password.to_owned()), &email())
+    }
+
+    fn score(password: &str) -> u8 {
+        strength(password, &word_list(COMMON_PASSWORDS), &[])
+    }
+
+    #[test]
+    fn strong_password_passes() {
+        assert_eq!(check(&policy(), "correct horse battery staple"), Ok(()));
+    }
+
+    #[test]
+    fn length_is_counted_in_characters() {
+        let policy = PasswordPolicy::new(8, 10);
+
+        assert_eq!(check(&policy, "ééééééé"), Err(vec![PasswordViolation::TooShort { min: 8 }]));
+        assert_eq!(check(&policy, "éééééééé"), Ok(()));
+        assert_eq!(check(&policy, "ééééééééééé"), Err(vec![PasswordViolation::TooLong { max: 10 }]));
+    }
+
+    #[test]
+    fn missing_character_classes_are_all_reported() {
+        let policy = PasswordPolicy {
+            require_lowercase: true,
+            require_uppercase: true,
+            require_digit: true,
+            require_symbol: true,
+            ..PasswordPolicy::new(8, 64)
+        };
+
+        assert_eq!(check(&policy, "ZQXJVKWP"), Err(vec![
+            PasswordViolation::MissingLowercase,
+            PasswordViolation::MissingDigit,
+            PasswordViolation::MissingSymbol,
+        ]));
+        assert_eq!(check(&policy, "Zq-x7JvKwp"), Ok(()));
+    }
+
+    #[test]
+    fn common_passwords_are_rejected_case_insensitively() {
+        let violations = check(&PasswordPolicy::new(8, 64), "PassWord123").unwrap_err();
+
+        assert_eq!(violations, vec![PasswordViolation::Common]);
+    }
+
+    #[test]
+    fn email_local_part_is_rejected() {
+        let violations = check(&PasswordPolicy::new(8, 64), "xx-Jane.Doe-xx").unwrap_err();
+
+        assert_eq!(violations, vec![PasswordViolation::ContainsEmail]);
+    }
+
+    #[test]
+    fn weak_password_reports_its_score() {
+        let violations = check(&policy(), "aaaaaaaaaaaa").unwrap_err();
+
+        assert_eq!(violations, vec![PasswordViolation::TooWeak { score: 0, min: 3 }]);
+    }
+
+    #[test]
+    fn patterns_score_lower_than_random_characters() {
+        assert_eq!(score("password"), 0);
+        assert_eq!(score("abcdefghijkl"), 0);
+        assert_eq!(score("qwertyuiop12"), 1);
+        assert!(score("monkeydragon") <= 2);
+        assert_eq!(score("zq8vx2mk"), 3);
+        assert_eq!(score("Tr1x-ob7q-Lum3"), 4);
+    }
+
+    #[test]
+    fn violations_serialize_with_their_rule() {
+        let json = serde_json::to_value(PasswordViolation::TooShort { min: 8 }).unwrap();
+
+        assert_eq!(json, serde_json::json!({ "rule": "too_short", "min": 8 }));
+    }
+}
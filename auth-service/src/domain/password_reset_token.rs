@@ -0,0 +1,64 @@
+use color_eyre::eyre::{eyre, Result};
+use rand::{distributions::Alphanumeric, Rng};
+use secrecy::{ExposeSecret, Secret};
+
+const TOKEN_LENGTH: usize = 32;
+
+/// Single-use token sent by email that lets its holder choose a new
+/// password without knowing the old one.
+#[derive(Debug, Clone)]
+pub struct PasswordResetToken(Secret<String>);
+
+impl PartialEq for PasswordResetToken {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+impl PasswordResetToken {
+    pub fn parse(token: Secret<String>) -> Result<PasswordResetToken> {
+        let value = token.expose_secret();
+        if value.len() == TOKEN_LENGTH && value.chars().all(|c| c.is_ascii_alphanumeric()) {
+            Ok(Self(token))
+        } else {
+            Err(eyre!("invalid password reset token"))
+        }
+    }
+}
+
+impl Default for PasswordResetToken {
+    fn default() -> Self {
+        let token: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(TOKEN_LENGTH)
+            .map(char::from)
+            .collect();
+        Self(Secret::new(token))
+    }
+}
+
+impl AsRef<Secret<String>> for PasswordResetToken {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_tokens_parse() {
+        let token = PasswordResetToken::default();
+
+        assert!(PasswordResetToken::parse(token.as_ref().clone()).is_ok());
+        assert_ne!(token, PasswordResetToken::default());
+    }
+
+    #[test]
+    fn malformed_tokens_are_rejected() {
+        for token in ["", "short", "not-alphanumeric-but-32-chars-xx"] {
+            assert!(PasswordResetToken::parse(Secret::new(token.to_owned())).is_err());
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use color_eyre::eyre::{eyre, Result};
+use secrecy::{ExposeSecret, Secret};
+
+/// A phone number in E.164 format, e.g. `+14155550100`.
+#[derive(Clone, Debug)]
+pub struct PhoneNumber(Secret<String>);
+
+impl PhoneNumber {
+    /// Accepts common separators (spaces, dashes, dots and parentheses) and
+    /// stores the number without them.
+    pub fn parse(phone_number: Secret<String>) -> Result<PhoneNumber> {
+        let normalized: String = phone_number
+            .expose_secret()
+            .chars()
+            .filter(|c| !matches!(c, ' ' | '-' | '.' | '(' | ')'))
+            .collect();
+
+        let is_valid = normalized
+            .strip_prefix('+')
+            .filter(|digits| (2..=15).contains(&digits.len()))
+            .filter(|digits| digits.chars().all(|c| c.is_ascii_digit()))
+            .is_some_and(|digits| !digits.starts_with('0'));
+
+        if is_valid {
+            Ok(Self(Secret::new(normalized)))
+        } else {
+            Err(eyre!("Invalid phone number"))
+        }
+    }
+}
+
+impl PartialEq for PhoneNumber {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+impl Eq for PhoneNumber {}
+
+impl AsRef<Secret<String>> for PhoneNumber {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phone_number_parse() {
+        let phone_number = PhoneNumber::parse(Secret::new("+1 (415) 555-0100".to_string())).unwrap();
+
+        assert_eq!(phone_number.as_ref().expose_secret(), "+14155550100");
+    }
+
+    #[test]
+    fn test_invalid_phone_numbers() {
+        let test_cases = ["4155550100", "+0155550100", "+1", "+1415555010012345", "+1415abc0100", ""];
+
+        for test_case in test_cases {
+            assert!(PhoneNumber::parse(Secret::new(test_case.to_string())).is_err(), "Failed for {}", test_case);
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+/// A browser that completed 2FA with "remember this device" checked.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TrustedDevice {
+    pub id: Uuid,
+    #[serde(rename = "userAgent")]
+    pub user_agent: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: DateTime<Utc>,
+    #[serde(rename = "expiresAt")]
+    pub expires_at: DateTime<Utc>,
+}
+
+impl TrustedDevice {
+    pub fn new(user_agent: Option<String>, ttl: Duration) -> Self {
+        let created_at = Utc::now();
+
+        TrustedDevice {
+            id: Uuid::new_v4(),
+            user_agent,
+            created_at,
+            expires_at: created_at + ttl,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= Utc::now()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_trusted_device_is_not_expired() {
+        let device = TrustedDevice::new(None, Duration::days(30));
+
+        assert!(!device.is_expired());
+        assert_eq!(device.expires_at - device.created_at, Duration::days(30));
+    }
+
+    #[test]
+    fn test_trusted_device_expired() {
+        let device = TrustedDevice::new(None, Duration::seconds(-1));
+
+        assert!(device.is_expired());
+    }
+}
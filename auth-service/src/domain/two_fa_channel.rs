@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+
+/// Where a user's 2FA codes are sent.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TwoFAChannel {
+    #[default]
+    Email,
+    Sms,
+}
+
+impl TwoFAChannel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TwoFAChannel::Email => "email",
+            TwoFAChannel::Sms => "sms",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<TwoFAChannel> {
+        match value {
+            "email" => Some(TwoFAChannel::Email),
+            "sms" => Some(TwoFAChannel::Sms),
+            _ => None,
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use rand::Rng;
-use color_eyre::eyre::{eyre, Context, Report, Result};
+use color_eyre::eyre::{eyre, Context, Result};
 use secrecy::{ExposeSecret, Secret};
 #[derive(Clone, Debug)]
 pub struct TwoFACode(Secret<String>);
@@ -46,6 +46,6 @@ mod tests {
         let fa_code = Secret::new("999999".to_string());
 
         let result = TwoFACode::parse(fa_code).is_ok();
-        assert_eq!(result, true)
+        assert!(result)
     }
 }
@@ -1,6 +1,7 @@
+use chrono::{DateTime, Utc};
 use sqlx::FromRow;
 
-use super::{Email, Password};
+use super::{AccountStatus, Email, Locale, Password, PhoneNumber, TwoFAChannel};
 
 #[derive(Clone, Debug, PartialEq, FromRow)]
 pub struct User {
@@ -8,7 +9,28 @@ pub struct User {
     pub email: Email,
     #[sqlx(flatten)]
     pub password: Password,
-    pub requires2fa: bool
+    pub requires2fa: bool,
+    /// Preferred language for emails. `None` falls back to the
+    /// `Accept-Language` header of the request.
+    #[sqlx(skip)]
+    pub locale: Option<Locale>,
+    /// Only set once the user has proven they own the number.
+    #[sqlx(skip)]
+    pub phone_number: Option<PhoneNumber>,
+    #[sqlx(skip)]
+    pub two_fa_channel: TwoFAChannel,
+    #[sqlx(skip)]
+    pub status: AccountStatus,
+    /// Why an operator last changed the status, for other operators.
+    #[sqlx(skip)]
+    pub status_reason: Option<String>,
+    /// `None` until the status is first changed.
+    #[sqlx(skip)]
+    pub status_changed_at: Option<DateTime<Utc>>,
+    /// Set by an operator. The user cannot sign in until they choose a new
+    /// password through the emailed reset link.
+    #[sqlx(skip)]
+    pub password_reset_required: bool
 }
 
 impl User {
@@ -16,8 +38,27 @@ impl User {
         User {
             email,
             password,
-            requires2fa
+            requires2fa,
+            locale: None,
+            phone_number: None,
+            two_fa_channel: TwoFAChannel::default(),
+            status: AccountStatus::default(),
+            status_reason: None,
+            status_changed_at: None,
+            password_reset_required: false
         }
     }
+
+    pub fn with_locale(mut self, locale: Option<Locale>) -> Self {
+        self.locale = locale;
+        self
+    }
 }
 
+/// One page of users matching an admin search.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UserPage {
+    pub users: Vec<User>,
+    /// Matching users across all pages.
+    pub total: u64,
+}
@@ -1,23 +1,35 @@
-use std::error::Error;
+use std::{error::Error, io, net::SocketAddr, sync::Arc, time::Duration};
 use axum::{
+    extract::ConnectInfo,
+    middleware::{self, AddExtension},
     response::{IntoResponse, Response},
-    routing::post,
-    serve::Serve, Router,
-    http::{Method, StatusCode},
+    routing::{delete, get, post},
+    Extension, Router,
+    http::{HeaderValue, Method, StatusCode},
     Json
 };
-use redis::{Client, RedisResult};
+use hyper_util::{rt::{TokioExecutor, TokioIo}, server::conn::auto::Builder, service::TowerToHyperService};
+use redis::{aio::ConnectionManager, Client, RedisResult};
 use sqlx::{postgres::PgPoolOptions, PgPool};
-use tower_http::{services::ServeDir, cors::CorsLayer, trace::TraceLayer};
+use tokio::{net::{TcpListener, TcpStream}, sync::watch, task::JoinSet};
+use tower::Layer;
+use tower_http::{
+    services::ServeDir, cors::CorsLayer, trace::TraceLayer,
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer}
+};
 use app_state::AppState;
-use domain::AuthAPIError;
+use domain::{AuthAPIError, PasswordViolation};
+use services::Health;
 use serde::{Deserialize, Serialize};
+use utils::constants::{REDIS_CONNECTION_TIMEOUT, REDIS_RECONNECT_ATTEMPTS, REDIS_RESPONSE_TIMEOUT};
+use utils::metrics::track_http_metrics;
 use utils::tracing::{
     make_span_with_request_id,
     on_request,
     on_response
 };
 use secrecy::{ExposeSecret, Secret};
+use webauthn_rs::{prelude::{Url, WebauthnError}, Webauthn, WebauthnBuilder};
 
 pub mod routes;
 pub mod services;
@@ -28,109 +40,335 @@ pub mod utils;
 #[derive(Serialize, Deserialize)]
 pub struct ErrorResponse {
     pub error: String,
+    /// Every password rule that was broken, when the error is a weak password.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub violations: Vec<ViolationResponse>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ViolationResponse {
+    #[serde(flatten)]
+    pub violation: PasswordViolation,
+    pub message: String,
 }
 
 impl IntoResponse for AuthAPIError {
     fn into_response(self) -> Response {
         log_error_chain(&self);
+        let violations = match &self {
+            AuthAPIError::WeakPassword(violations) => violations.iter()
+                .map(|violation| ViolationResponse { violation: violation.clone(), message: violation.to_string() })
+                .collect(),
+            _ => Vec::new(),
+        };
         let (status, error_message) = match self {
             AuthAPIError::UserAlreadyExists => (StatusCode::CONFLICT, "User already exists"),
             AuthAPIError::InvalidCredentials => (StatusCode::BAD_REQUEST, "Invalid credentials"),
+            AuthAPIError::WeakPassword(_) => (StatusCode::BAD_REQUEST, "Weak password"),
             AuthAPIError::UnexpectedError(_) => {
                 (StatusCode::INTERNAL_SERVER_ERROR, "Uexpected error")
             },
             AuthAPIError::IncorrectCredentials => (StatusCode::UNAUTHORIZED, "Incorrect credentials"),
             AuthAPIError::InvalidToken => (StatusCode::UNAUTHORIZED, "Invalid token"),
-            AuthAPIError::MissingToken => (StatusCode::BAD_REQUEST, "Missing token")
+            AuthAPIError::Forbidden => (StatusCode::FORBIDDEN, "Forbidden"),
+            AuthAPIError::AccountSuspended => (StatusCode::FORBIDDEN, "Account suspended"),
+            AuthAPIError::AccountDisabled => (StatusCode::FORBIDDEN, "Account disabled"),
+            AuthAPIError::PasswordResetRequired => (StatusCode::FORBIDDEN, "Password reset required"),
+            AuthAPIError::MissingToken => (StatusCode::BAD_REQUEST, "Missing token"),
+            AuthAPIError::NotFound => (StatusCode::NOT_FOUND, "Not found")
         };
 
         let body = Json(ErrorResponse {
             error: error_message.to_string(),
+            violations,
         });
     
         (status, body).into_response()
     }
 }
 
+/// Logs the error with its causes, outermost first, as a single
+/// `error.chain` field.
 fn log_error_chain(e: &(dyn Error + 'static)) {
-    let separator = "\n-----------------------------------------------------------------------------------\n";
-    let mut report = format!("{}{:?}\n", separator, e);
-
+    let mut chain = vec![e.to_string()];
     let mut current = e.source();
 
     while let Some(cause) = current {
-        let str = format!("Caused by \n\n{:?}", cause);
-        report = format!("{}\n{}", report, str);
+        chain.push(cause.to_string());
         current = cause.source();
     }
 
-    report = format!("{}\n{}", report, separator);
-    tracing::error!("{}", report);
+    tracing::error!(error = %e, error.chain = ?chain, "request failed");
 }
 
 pub struct Application {
-    server: Serve<Router, Router>,
+    listener: TcpListener,
+    router: Router,
+    health: Arc<Health>,
+    shutdown: ShutdownHandle,
+    readiness_grace: Duration,
+    drain_timeout: Duration,
     // address is exposed as public field
     // so we have access to it in tests.
     pub address: String,
 }
 
 impl Application {
-    pub async fn build(app_state: AppState, address: &str) -> Result<Self, Box<dyn Error>> {
-        // Move the Router difinition from main.rs to here 
-        // Also, remove the `hello` route
-        // we dont need it at this point!
-        let allowed_origins = [
-            "http://localhost:8000".parse()?,
-            "http://159.223.168.210:8000".parse()?
-        ];
+    pub async fn build(app_state: AppState) -> Result<Self, Box<dyn Error>> {
+        let settings = app_state.settings.clone();
+
+        let allowed_origins = settings.application.allowed_origins
+            .iter()
+            .map(|origin| origin.parse::<HeaderValue>())
+            .collect::<Result<Vec<_>, _>>()?;
 
         let cors = CorsLayer::new()
-            .allow_methods([Method::GET, Method::POST])
+            .allow_methods([Method::GET, Method::POST, Method::DELETE])
             .allow_credentials(true)
             .allow_origin(allowed_origins);
 
         let app = Router::new()
             .nest_service("/", ServeDir::new("assets"))
+            .route("/health/live", get(routes::health_live))
+            .route("/health/ready", get(routes::health_ready))
+            .route("/metrics", get(routes::metrics))
             .route("/signup", post(routes::signup))
             .route("/login", post(routes::login))
             .route("/logout", post(routes::logout))
+            .route("/change-password", post(routes::change_password))
+            .route("/reset-password", post(routes::reset_password))
             .route("/verify-2fa", post(routes::verify_2fa))
             .route("/verify-token", post(routes::verify_token))
+            .route("/webauthn/register/start", post(routes::webauthn_register_start))
+            .route("/webauthn/register/finish", post(routes::webauthn_register_finish))
+            .route("/webauthn/login/start", post(routes::webauthn_login_start))
+            .route("/webauthn/login/finish", post(routes::webauthn_login_finish))
+            .route("/trusted-devices", get(routes::list_trusted_devices))
+            .route("/trusted-devices/:id", delete(routes::revoke_trusted_device))
+            .route("/phone", post(routes::add_phone_number))
+            .route("/phone/verify", post(routes::verify_phone_number))
+            .route("/2fa-channel", post(routes::set_two_fa_channel))
+            .route("/activity", get(routes::recent_activity))
+            .nest("/admin", admin_router())
             .with_state(app_state.clone())
+            .layer(middleware::from_fn(track_http_metrics))
             .layer(cors)
             .layer(
                 TraceLayer::new_for_http()
                     .make_span_with(make_span_with_request_id)
                     .on_request(on_request)
                     .on_response(on_response)
-            );
+            )
+            .layer(PropagateRequestIdLayer::x_request_id())
+            .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid));
 
         let router = app;
 
-        let listener = tokio::net::TcpListener::bind(address).await?;
+        let listener = TcpListener::bind(&settings.application.address).await?;
         let address = listener.local_addr()?.to_string();
-        let server = axum::serve(listener, router);
 
         let app_inst = Application {
-            server,
+            listener,
+            router,
+            health: app_state.health.clone(),
+            shutdown: ShutdownHandle::default(),
+            readiness_grace: Duration::from_secs(settings.application.readiness_grace_secs),
+            drain_timeout: Duration::from_secs(settings.application.drain_timeout_secs),
             address
         };
 
         Ok(app_inst)
     }
 
-    pub async fn run(self) -> Result<(), std::io::Error> {
+    /// Stops the server the same way SIGTERM does.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Serves until a shutdown is requested, keeps accepting connections for
+    /// the readiness grace period, then stops accepting them and waits up to
+    /// the drain timeout for in-flight requests to finish. Connections still
+    /// open after that are aborted, so once this returns nothing holds the
+    /// app state any more.
+    pub async fn run(self) -> Result<(), io::Error> {
         tracing::info!("listening on {}", &self.address);
-        self.server.await
+
+        let Application { listener, router, health, shutdown, readiness_grace, drain_timeout, .. } = self;
+        let signal = shutdown_signal(health, shutdown, readiness_grace);
+        tokio::pin!(signal);
+
+        let (draining, _) = watch::channel(false);
+        let mut connections = JoinSet::new();
+
+        loop {
+            let (stream, remote_addr) = tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) if is_connection_error(&e) => continue,
+                    Err(e) => {
+                        // Most likely out of file descriptors, which closing
+                        // connections will free up.
+                        tracing::error!(error = %e, "failed to accept connection");
+                        tokio::time::sleep(ACCEPT_ERROR_BACKOFF).await;
+                        continue;
+                    }
+                },
+                _ = &mut signal => break,
+            };
+
+            // Client addresses are recorded in the audit log.
+            let service = Extension(ConnectInfo(remote_addr)).layer(router.clone());
+            connections.spawn(serve_connection(stream, service, draining.subscribe()));
+
+            while connections.try_join_next().is_some() {}
+        }
+
+        drop(listener);
+        draining.send_replace(true);
+
+        let drained = async {
+            while connections.join_next().await.is_some() {}
+        };
+        if tokio::time::timeout(drain_timeout, drained).await.is_ok() {
+            tracing::info!("all connections drained");
+        } else {
+            tracing::warn!(?drain_timeout, open = connections.len(), "drain timeout elapsed, aborting open connections");
+            connections.shutdown().await;
+        }
+
+        Ok(())
+    }
+}
+
+const ACCEPT_ERROR_BACKOFF: Duration = Duration::from_secs(1);
+
+fn is_connection_error(e: &io::Error) -> bool {
+    matches!(e.kind(), io::ErrorKind::ConnectionRefused | io::ErrorKind::ConnectionAborted | io::ErrorKind::ConnectionReset)
+}
+
+/// Serves one connection, finishing its in-flight request and closing it
+/// once `draining` turns true.
+async fn serve_connection(
+    stream: TcpStream,
+    service: AddExtension<Router, ConnectInfo<SocketAddr>>,
+    mut draining: watch::Receiver<bool>
+) {
+    let builder = Builder::new(TokioExecutor::new());
+    let connection = builder.serve_connection_with_upgrades(TokioIo::new(stream), TowerToHyperService::new(service));
+    tokio::pin!(connection);
+
+    let mut closing = false;
+    loop {
+        tokio::select! {
+            outcome = connection.as_mut() => {
+                if let Err(e) = outcome {
+                    tracing::debug!(error = %e, "connection closed with an error");
+                }
+                break;
+            }
+            _ = draining.wait_for(|draining| *draining), if !closing => {
+                connection.as_mut().graceful_shutdown();
+                closing = true;
+            }
+        }
+    }
+}
+
+/// Requests a graceful shutdown of a running `Application`.
+#[derive(Clone)]
+pub struct ShutdownHandle(Arc<watch::Sender<bool>>);
+
+impl Default for ShutdownHandle {
+    fn default() -> Self {
+        Self(Arc::new(watch::channel(false).0))
     }
 }
 
-pub async fn get_postgres_pool(url: &Secret<String>) -> Result<PgPool, sqlx::Error> {
-    PgPoolOptions::new().max_connections(5).connect(url.expose_secret()).await
+impl ShutdownHandle {
+    pub fn shutdown(&self) {
+        self.0.send_replace(true);
+    }
+
+    async fn requested(&self) {
+        let mut requested = self.0.subscribe();
+        // The sender lives in `self`, so the channel can't close while waiting.
+        let _ = requested.wait_for(|requested| *requested).await;
+    }
+}
+
+/// Resolves on Ctrl+C, SIGTERM or a `ShutdownHandle` request, once readiness
+/// has been off for `readiness_grace` so load balancers have stopped sending
+/// new requests.
+async fn shutdown_signal(health: Arc<Health>, shutdown: ShutdownHandle, readiness_grace: Duration) {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c().await.expect("Failed to listen for Ctrl+C");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+        _ = shutdown.requested() => {},
+    }
+
+    tracing::info!("shutdown signal received");
+    health.begin_shutdown();
+    shutdown.shutdown();
+    tokio::time::sleep(readiness_grace).await;
+}
+
+/// User management for support staff and scripts. Every route checks the
+/// caller itself through `AdminActor`.
+fn admin_router() -> Router<AppState> {
+    Router::new()
+        .route("/users", get(routes::admin_list_users))
+        .route("/users/:email", get(routes::admin_get_user))
+        .route("/users/:email/force-password-reset", post(routes::admin_force_password_reset))
+        .route("/users/:email/suspend", post(routes::admin_suspend_user))
+        .route("/users/:email/disable", post(routes::admin_disable_user))
+        .route("/users/:email/enable", post(routes::admin_enable_user))
+        .route("/users/:email/reset-2fa", post(routes::admin_reset_two_fa))
+        .route("/users/:email/revoke-sessions", post(routes::admin_revoke_sessions))
+}
+
+pub async fn get_postgres_pool(url: &Secret<String>, max_connections: u32) -> Result<PgPool, sqlx::Error> {
+    PgPoolOptions::new().max_connections(max_connections).connect(url.expose_secret()).await
 }
 
 pub fn get_redis_client(redis_hostname: String) -> RedisResult<Client> {
     let redis_url = format!("redis://{}/", redis_hostname);
     redis::Client::open(redis_url)
+}
+
+/// Multiplexed connection shared by every Redis store. A command that hits a
+/// broken connection fails, and the manager reconnects in the background with
+/// exponential backoff (200ms, 400ms, ...) so later commands succeed once
+/// Redis is back.
+pub async fn get_redis_connection(redis_hostname: String) -> RedisResult<ConnectionManager> {
+    ConnectionManager::new_with_backoff_and_timeouts(
+        get_redis_client(redis_hostname)?,
+        2,
+        100,
+        REDIS_RECONNECT_ATTEMPTS,
+        REDIS_RESPONSE_TIMEOUT,
+        REDIS_CONNECTION_TIMEOUT,
+    )
+    .await
+}
+
+pub fn get_webauthn(rp_id: &str, rp_origin: &str) -> Result<Webauthn, WebauthnError> {
+    let rp_origin = Url::parse(rp_origin).map_err(|_| WebauthnError::Configuration)?;
+    WebauthnBuilder::new(rp_id, &rp_origin)?
+        .rp_name("Auth Service")
+        .build()
 }
\ No newline at end of file
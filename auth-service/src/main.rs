@@ -1,46 +1,262 @@
-use std::sync::Arc;
+use std::{fs::File, path::Path, sync::Arc, time::Duration};
+use clap::Parser;
+use color_eyre::eyre::{eyre, Context, Result};
+use redis::aio::ConnectionManager;
 use sqlx::PgPool;
-use tokio::sync::RwLock;
 
-use auth_service::{app_state::AppState, get_postgres_pool, get_redis_client, services, utils::{constants::{prod, DATABASE_URL, REDIS_HOST_NAME}, tracing::init_tracing}, Application};
+use auth_service::{
+    app_state::{AppState, BreachedPasswordCheckerType, EmailClientType, Services, SmsClientType, Stores}, domain::{Email, PhoneNumber, UserStore, UserStoreError}, get_postgres_pool, get_redis_connection, get_webauthn, services,
+    utils::{
+        constants::prod,
+        metrics::prometheus_handle,
+        settings::{Cli, Command, EmailProvider, Settings, SmsProvider},
+        tracing::init_tracing
+    },
+    Application
+};
+use secrecy::Secret;
 
 #[tokio::main]
-async fn main() {
-    color_eyre::install().expect("Failed to install color_eyre");
-    init_tracing().expect("Failed to initialize tracing");
-    
-    let pg_pool = configure_postgresql().await;
-    let redis_client = Arc::new(RwLock::new(configure_redis()));
-
-    let user_store = Arc::new(RwLock::new(services::PostgresUserStore::new(pg_pool)));
-    let banned_token_store = Arc::new(RwLock::new(services::RedisBannedTokenStore::new(redis_client.clone())));
-    let two_fa_code_store = Arc::new(RwLock::new(services::RedisTwoFACodeStore::new(redis_client)));
-    let email_client = Arc::new(RwLock::new(services::MockEmailClient::default()));
-
-    let app_state = AppState::new(
-        user_store, banned_token_store, two_fa_code_store, email_client);
-    let app = Application::build(app_state, prod::APP_ADDRESS)
+async fn main() -> Result<()> {
+    color_eyre::install()?;
+    let cli = Cli::parse();
+    let settings = Arc::new(Settings::load(&cli)?);
+
+    let _tracing = init_tracing(&settings.telemetry).wrap_err("failed to initialize tracing")?;
+
+    match &cli.command {
+        Some(Command::ImportUsers { path, format }) => return import_users(&settings, path, *format).await,
+        Some(Command::AssignRole { email, role }) => return assign_role(&settings, email, role).await,
+        None => {}
+    }
+    let metrics = prometheus_handle();
+
+    let pg_pool = configure_postgresql(&settings).await?;
+    let redis_conn = configure_redis(&settings).await?;
+    let health = Arc::new(services::Health::new(vec![
+        Arc::new(services::PostgresHealthCheck::new(pg_pool.clone())),
+        Arc::new(services::RedisHealthCheck::new(redis_conn.clone())),
+    ], prod::HEALTH_CHECK_TIMEOUT));
+
+    let user_store = Arc::new(services::PostgresUserStore::new(pg_pool.clone(), password_hashers(&settings)?)
+        .with_password_history(settings.auth.password_policy.history_size));
+    let banned_token_store = Arc::new(services::RedisBannedTokenStore::new(redis_conn.clone()));
+    let two_fa_code_store = Arc::new(services::RedisTwoFACodeStore::new(redis_conn.clone()));
+    let email_client = configure_email_client(&settings)?;
+    let passkey_store = Arc::new(services::PostgresPasskeyStore::new(pg_pool.clone()));
+    let trusted_device_store = Arc::new(services::PostgresTrustedDeviceStore::new(pg_pool.clone()));
+    let email_outbox = Arc::new(services::PostgresEmailOutbox::new(pg_pool.clone()));
+    let webauthn_challenge_store = Arc::new(services::RedisWebAuthnChallengeStore::new(redis_conn.clone()));
+    let phone_verification_store = Arc::new(services::RedisPhoneVerificationStore::new(redis_conn.clone()));
+    let password_reset_token_store = Arc::new(services::RedisPasswordResetTokenStore::new(redis_conn.clone()));
+    let audit_log = Arc::new(services::PostgresAuditLog::new(pg_pool.clone()));
+    let account_status_cache = settings.auth.token_status_check.then(|| Arc::new(services::AccountStatusCache::new(
+        user_store.clone(), Duration::from_secs(settings.auth.account_status_cache_secs))));
+    let sms_client = configure_sms_client(&settings)?;
+    let webauthn = Arc::new(
+        get_webauthn(&settings.webauthn.rp_id, &settings.webauthn.rp_origin).wrap_err("failed to configure WebAuthn")?);
+    let password_policy = Arc::new(settings.auth.password_policy.policy());
+    let breached_password_checker = configure_breached_password_checker(&settings)?;
+    let email_templates = Arc::new(
+        services::EmailTemplates::new(settings.email.templates_dir.clone())
+            .wrap_err("failed to load email templates")?);
+
+    let outbox_worker = tokio::spawn(services::EmailOutboxWorker::new(email_outbox.clone(), email_client, services::EmailOutboxSettings {
+        batch_size: prod::EMAIL_OUTBOX_BATCH_SIZE,
+        poll_interval: prod::EMAIL_OUTBOX_POLL_INTERVAL,
+        max_attempts: prod::EMAIL_OUTBOX_MAX_ATTEMPTS,
+        retry_base_delay: prod::EMAIL_OUTBOX_RETRY_BASE_DELAY,
+        max_retry_delay: prod::EMAIL_OUTBOX_MAX_RETRY_DELAY,
+        lease: prod::EMAIL_OUTBOX_LEASE,
+    }).run());
+
+    let stores = Stores {
+        user_store,
+        banned_token_store,
+        two_fa_code_store,
+        email_outbox,
+        passkey_store,
+        webauthn_challenge_store,
+        trusted_device_store,
+        phone_verification_store,
+        password_reset_token_store,
+        audit_log,
+    };
+    let app_services = Services {
+        webauthn,
+        email_templates,
+        sms_client,
+        password_policy,
+        breached_password_checker,
+        account_status_cache,
+    };
+
+    let app_state = AppState::new(stores, app_services, settings, health, metrics);
+    let app = Application::build(app_state)
         .await
-        .expect("Failed to build app");
+        .map_err(|e| eyre!("failed to build app: {}", e))?;
+
+    app.run().await.wrap_err("failed to run app")?;
 
-    app.run().await.expect("Failed to run app");
+    // `run` has dropped the app state, its health checks and every connection
+    // task. The outbox worker only holds Postgres handles, so after it the
+    // connections below are the last ones left.
+    outbox_worker.abort();
+    let _ = outbox_worker.await;
+    close_redis(redis_conn).await;
+    close_postgresql(pg_pool).await;
+    Ok(())
 }
 
-async fn configure_postgresql() -> PgPool {
-    let pg_pool = get_postgres_pool(&DATABASE_URL)
+async fn configure_postgresql(settings: &Settings) -> Result<PgPool> {
+    let pg_pool = get_postgres_pool(&settings.database.url, settings.database.max_connections)
         .await
-        .expect("Failed to create Postgres connection");
+        .wrap_err("failed to connect to Postgres")?;
 
     sqlx::migrate!()
         .run(&pg_pool)
         .await
-        .expect("Failed to run migrations");
-    pg_pool
+        .wrap_err("failed to run migrations")?;
+    Ok(pg_pool)
+}
+
+async fn close_postgresql(pg_pool: PgPool) {
+    // Requests cut off by the drain timeout may still hold connections.
+    match tokio::time::timeout(prod::DATABASE_CLOSE_TIMEOUT, pg_pool.close()).await {
+        Ok(()) => tracing::info!("closed Postgres connections"),
+        Err(_) => tracing::warn!("timed out closing Postgres connections"),
+    }
+}
+
+async fn close_redis(mut redis_conn: ConnectionManager) {
+    // Every clone was dropped with the app state, so QUIT closes the one
+    // connection they shared.
+    let quit = redis::cmd("QUIT");
+    match tokio::time::timeout(prod::REDIS_CLOSE_TIMEOUT, quit.query_async::<_, ()>(&mut redis_conn)).await {
+        Ok(Ok(())) => tracing::info!("closed Redis connection"),
+        Ok(Err(e)) => tracing::warn!(error = %e, "failed to close Redis connection"),
+        Err(_) => tracing::warn!("timed out closing Redis connection"),
+    }
+}
+
+fn password_hashers(settings: &Settings) -> Result<services::PasswordHasherRegistry> {
+    let argon2 = services::Argon2Hasher::new(&settings.auth.password_hashing)?;
+    Ok(services::PasswordHasherRegistry::new(argon2))
+}
+
+async fn import_users(settings: &Settings, path: &Path, format: Option<services::ImportFormat>) -> Result<()> {
+    let format = format.or_else(|| services::ImportFormat::from_path(path))
+        .ok_or(eyre!("cannot tell the format of {}, pass --format", path.display()))?;
+    let file = File::open(path).wrap_err_with(|| format!("failed to open {}", path.display()))?;
+
+    let pg_pool = configure_postgresql(settings).await?;
+    let user_store = services::PostgresUserStore::new(pg_pool.clone(), password_hashers(settings)?);
+
+    let report = services::import_users(&user_store, format, file).await?;
+    for failure in &report.failed {
+        tracing::warn!(failure, "skipped invalid user");
+    }
+    tracing::info!(imported = report.imported, skipped = report.skipped, failed = report.failed.len(), "imported users");
+
+    close_postgresql(pg_pool).await;
+    Ok(())
+}
+
+async fn assign_role(settings: &Settings, email: &str, role: &str) -> Result<()> {
+    let email = Email::parse(Secret::new(email.to_owned()))?;
+
+    let pg_pool = configure_postgresql(settings).await?;
+    let user_store = services::PostgresUserStore::new(pg_pool.clone(), password_hashers(settings)?);
+
+    let result = user_store.assign_role(&email, role).await;
+    close_postgresql(pg_pool).await;
+
+    match result {
+        Ok(()) => {
+            tracing::info!(role, "assigned role");
+            Ok(())
+        }
+        Err(UserStoreError::UserNotFound) => Err(eyre!("no user has that email")),
+        Err(UserStoreError::RoleNotFound) => Err(eyre!("role {:?} does not exist", role)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+async fn configure_redis(settings: &Settings) -> Result<ConnectionManager> {
+    get_redis_connection(settings.redis.host_name.to_owned())
+        .await
+        .wrap_err_with(|| format!("failed to connect to Redis at {}", settings.redis.host_name))
 }
 
-fn configure_redis() -> redis::Connection {
-    get_redis_client(REDIS_HOST_NAME.to_owned())
-        .expect("Failed to get Redis client")
-        .get_connection()
-        .expect("Failed to get Redis client")
+fn configure_email_client(settings: &Settings) -> Result<EmailClientType> {
+    let email = &settings.email;
+    let sender = Email::parse(Secret::new(email.sender.to_owned()))?;
+
+    let email_client: EmailClientType = match email.client {
+        EmailProvider::Mock => Arc::new(services::MockEmailClient),
+        EmailProvider::Smtp => {
+            let smtp_settings = services::SmtpSettings {
+                host: email.smtp.host.to_owned(),
+                port: email.smtp.port,
+                username: email.smtp.username.to_owned(),
+                password: email.smtp.password.to_owned(),
+                tls: email.smtp.tls,
+                sender,
+                pool_size: prod::SMTP_POOL_SIZE,
+                timeout: prod::SMTP_TIMEOUT,
+            };
+
+            Arc::new(
+                services::SmtpEmailClient::new(smtp_settings).wrap_err("failed to configure SMTP email client")?)
+        }
+        EmailProvider::Postmark => {
+            let postmark_settings = services::PostmarkSettings {
+                base_url: email.postmark.base_url.to_owned(),
+                sender,
+                authorization_token: email.postmark.auth_token.to_owned()
+                    .ok_or(eyre!("email.postmark.auth_token must be set"))?,
+                timeout: prod::EMAIL_PROVIDER_TIMEOUT,
+                max_retries: prod::EMAIL_PROVIDER_MAX_RETRIES,
+                retry_base_delay: prod::EMAIL_PROVIDER_RETRY_BASE_DELAY,
+            };
+
+            Arc::new(
+                services::PostmarkEmailClient::new(postmark_settings).wrap_err("failed to configure Postmark email client")?)
+        }
+    };
+
+    Ok(email_client)
+}
+
+fn configure_breached_password_checker(settings: &Settings) -> Result<BreachedPasswordCheckerType> {
+    match &settings.auth.breached_passwords_dir {
+        Some(dir) => Ok(Arc::new(services::HibpBreachedPasswordChecker::new(dir.to_owned())?)),
+        None => {
+            tracing::warn!("auth.breached_passwords_dir is unset, new passwords are not checked for breaches");
+            Ok(Arc::new(services::HashsetBreachedPasswordChecker::default()))
+        }
+    }
+}
+
+fn configure_sms_client(settings: &Settings) -> Result<SmsClientType> {
+    let sms = &settings.sms;
+
+    let sms_client: SmsClientType = match sms.client {
+        SmsProvider::Mock => Arc::new(services::MockSmsClient),
+        SmsProvider::Twilio => {
+            let sender = sms.sender.to_owned().ok_or(eyre!("sms.sender must be set"))?;
+            let twilio_settings = services::TwilioSettings {
+                base_url: sms.twilio.base_url.to_owned(),
+                account_sid: sms.twilio.account_sid.to_owned().ok_or(eyre!("sms.twilio.account_sid must be set"))?,
+                auth_token: sms.twilio.auth_token.to_owned().ok_or(eyre!("sms.twilio.auth_token must be set"))?,
+                sender: PhoneNumber::parse(Secret::new(sender))?,
+                timeout: prod::SMS_PROVIDER_TIMEOUT,
+            };
+
+            Arc::new(
+                services::TwilioSmsClient::new(twilio_settings).wrap_err("failed to configure Twilio SMS client")?)
+        }
+    };
+
+    Ok(sms_client)
 }
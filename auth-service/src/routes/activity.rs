@@ -0,0 +1,68 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuditEntry, AuditEvent, AuthAPIError, Email, RequestContext},
+    utils::auth::authenticated_email
+};
+
+/// How many entries `GET /activity` returns.
+const RECENT_ACTIVITY_LIMIT: u64 = 50;
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ActivityEntry {
+    pub event: AuditEvent,
+    #[serde(rename = "occurredAt")]
+    pub occurred_at: DateTime<Utc>,
+    pub ip: Option<String>,
+    #[serde(rename = "userAgent")]
+    pub user_agent: Option<String>,
+    /// Which admin it was is not shown to the user.
+    #[serde(rename = "byAdmin")]
+    pub by_admin: bool,
+}
+
+impl From<AuditEntry> for ActivityEntry {
+    fn from(entry: AuditEntry) -> Self {
+        ActivityEntry {
+            event: entry.event,
+            occurred_at: entry.occurred_at,
+            ip: entry.context.ip,
+            user_agent: entry.context.user_agent,
+            by_admin: entry.actor.is_some(),
+        }
+    }
+}
+
+/// The logged in user's latest audit log entries, newest first.
+#[tracing::instrument(name = "Recent activity", skip_all)]
+pub async fn recent_activity(
+    State(state): State<AppState>,
+    jar: CookieJar
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticated_email(&jar, &state).await?;
+
+    let entries = state.audit_log
+        .recent_entries(&email, RECENT_ACTIVITY_LIMIT)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok((StatusCode::OK, Json(entries.into_iter().map(ActivityEntry::from).collect::<Vec<_>>())))
+}
+
+/// Records something the user did, or tried to do, to their own account.
+/// Like admin actions, a failure to record fails the request.
+pub(crate) async fn record_event(
+    state: &AppState,
+    event: AuditEvent,
+    email: &Email,
+    context: &RequestContext
+) -> Result<(), AuthAPIError> {
+    state.audit_log
+        .record(AuditEntry::new(event, None, Some(email.clone())).with_context(context.clone()))
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))
+}
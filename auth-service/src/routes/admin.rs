@@ -0,0 +1,313 @@
+use axum::{extract::{Path, Query, State}, http::StatusCode, response::IntoResponse, Json};
+use chrono::{DateTime, Utc};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AccountStatus, AuditEntry, AuditEvent, AuthAPIError, Email, Locale, RequestContext, TwoFAChannel, User, UserStoreError},
+    utils::auth::{AdminActor, UsersAdmin, UsersRead}
+};
+
+use super::send_password_reset_email;
+
+const DEFAULT_PER_PAGE: u64 = 20;
+const MAX_PER_PAGE: u64 = 100;
+
+#[derive(Deserialize)]
+pub struct ListUsersQuery {
+    /// Part of an email address, matched case insensitively.
+    pub search: Option<String>,
+    /// Starts at 1.
+    pub page: Option<u64>,
+    #[serde(rename = "perPage")]
+    pub per_page: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct StatusChangeRequest {
+    /// Kept with the status and shown to other admins.
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct UserSummary {
+    pub email: String,
+    pub status: AccountStatus,
+    #[serde(rename = "requires2FA")]
+    pub requires_2fa: bool,
+    #[serde(rename = "passwordResetRequired")]
+    pub password_reset_required: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ListUsersResponse {
+    pub users: Vec<UserSummary>,
+    pub page: u64,
+    #[serde(rename = "perPage")]
+    pub per_page: u64,
+    pub total: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct UserDetailsResponse {
+    pub email: String,
+    pub status: AccountStatus,
+    #[serde(rename = "statusReason")]
+    pub status_reason: Option<String>,
+    #[serde(rename = "statusChangedAt")]
+    pub status_changed_at: Option<DateTime<Utc>>,
+    #[serde(rename = "requires2FA")]
+    pub requires_2fa: bool,
+    pub locale: Option<Locale>,
+    #[serde(rename = "twoFAChannel")]
+    pub two_fa_channel: TwoFAChannel,
+    #[serde(rename = "hasPhoneNumber")]
+    pub has_phone_number: bool,
+    #[serde(rename = "passwordResetRequired")]
+    pub password_reset_required: bool,
+    pub roles: Vec<String>,
+}
+
+impl From<User> for UserSummary {
+    fn from(user: User) -> Self {
+        UserSummary {
+            email: user.email.as_ref().expose_secret().to_owned(),
+            status: user.status,
+            requires_2fa: user.requires2fa,
+            password_reset_required: user.password_reset_required,
+        }
+    }
+}
+
+/// Lists users by email, a page at a time.
+#[tracing::instrument(name = "Admin list users", skip_all)]
+pub async fn admin_list_users(
+    State(state): State<AppState>,
+    _admin: AdminActor<UsersRead>,
+    Query(query): Query<ListUsersQuery>
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query.per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE);
+    let search = query.search.as_deref().map(str::trim).filter(|search| !search.is_empty());
+
+    let user_page = state.user_store
+        .list_users(search, (page - 1).saturating_mul(per_page), per_page)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok((StatusCode::OK, Json(ListUsersResponse {
+        users: user_page.users.into_iter().map(UserSummary::from).collect(),
+        page,
+        per_page,
+        total: user_page.total,
+    })))
+}
+
+#[tracing::instrument(name = "Admin get user", skip_all)]
+pub async fn admin_get_user(
+    State(state): State<AppState>,
+    _admin: AdminActor<UsersRead>,
+    Path(email): Path<String>
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = find_user(&state, email).await?;
+    let access = state.user_store
+        .get_access(&user.email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok((StatusCode::OK, Json(UserDetailsResponse {
+        email: user.email.as_ref().expose_secret().to_owned(),
+        status: user.status,
+        status_reason: user.status_reason,
+        status_changed_at: user.status_changed_at,
+        requires_2fa: user.requires2fa,
+        locale: user.locale,
+        two_fa_channel: user.two_fa_channel,
+        has_phone_number: user.phone_number.is_some(),
+        password_reset_required: user.password_reset_required,
+        roles: access.roles,
+    })))
+}
+
+/// Makes the user choose a new password before they can log in again. Their
+/// sessions are revoked and a reset link is emailed to them.
+#[tracing::instrument(name = "Admin force password reset", skip_all)]
+pub async fn admin_force_password_reset(
+    State(state): State<AppState>,
+    admin: AdminActor<UsersAdmin>,
+    context: RequestContext,
+    Path(email): Path<String>
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = find_user(&state, email).await?;
+
+    state.user_store
+        .require_password_reset(&user.email)
+        .await
+        .map_err(user_store_error)?;
+    revoke_sessions(&state, &user.email).await?;
+    send_password_reset_email(&user, &state).await?;
+
+    record(&state, AuditEvent::PasswordResetForced, admin.actor, user.email, context).await?;
+    Ok(StatusCode::OK)
+}
+
+/// Blocks the user from logging in. Their sessions are kept, but are
+/// refused while the token status check is on.
+#[tracing::instrument(name = "Admin suspend user", skip_all)]
+pub async fn admin_suspend_user(
+    State(state): State<AppState>,
+    admin: AdminActor<UsersAdmin>,
+    context: RequestContext,
+    Path(email): Path<String>,
+    request: Option<Json<StatusChangeRequest>>
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = parse_email(email)?;
+
+    set_status(&state, &email, AccountStatus::Suspended, request).await?;
+
+    record(&state, AuditEvent::AccountSuspended, admin.actor, email, context).await?;
+    Ok(StatusCode::OK)
+}
+
+/// Blocks the user from logging in and signs them out everywhere.
+#[tracing::instrument(name = "Admin disable user", skip_all)]
+pub async fn admin_disable_user(
+    State(state): State<AppState>,
+    admin: AdminActor<UsersAdmin>,
+    context: RequestContext,
+    Path(email): Path<String>,
+    request: Option<Json<StatusChangeRequest>>
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = parse_email(email)?;
+
+    set_status(&state, &email, AccountStatus::Disabled, request).await?;
+    revoke_sessions(&state, &email).await?;
+
+    record(&state, AuditEvent::AccountDisabled, admin.actor, email, context).await?;
+    Ok(StatusCode::OK)
+}
+
+/// Lets a suspended or disabled user log in again.
+#[tracing::instrument(name = "Admin enable user", skip_all)]
+pub async fn admin_enable_user(
+    State(state): State<AppState>,
+    admin: AdminActor<UsersAdmin>,
+    context: RequestContext,
+    Path(email): Path<String>,
+    request: Option<Json<StatusChangeRequest>>
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = parse_email(email)?;
+
+    set_status(&state, &email, AccountStatus::Active, request).await?;
+
+    record(&state, AuditEvent::AccountEnabled, admin.actor, email, context).await?;
+    Ok(StatusCode::OK)
+}
+
+/// Removes every second factor the user set up: their phone number,
+/// passkeys and trusted devices. 2FA codes go to their email again.
+#[tracing::instrument(name = "Admin reset 2FA", skip_all)]
+pub async fn admin_reset_two_fa(
+    State(state): State<AppState>,
+    admin: AdminActor<UsersAdmin>,
+    context: RequestContext,
+    Path(email): Path<String>
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = parse_email(email)?;
+
+    state.user_store
+        .reset_two_fa(&email)
+        .await
+        .map_err(user_store_error)?;
+    state.passkey_store
+        .remove_passkeys(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    state.trusted_device_store
+        .revoke_all_devices(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    record(&state, AuditEvent::TwoFAReset, admin.actor, email, context).await?;
+    Ok(StatusCode::OK)
+}
+
+#[tracing::instrument(name = "Admin revoke sessions", skip_all)]
+pub async fn admin_revoke_sessions(
+    State(state): State<AppState>,
+    admin: AdminActor<UsersAdmin>,
+    context: RequestContext,
+    Path(email): Path<String>
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = find_user(&state, email).await?;
+
+    revoke_sessions(&state, &user.email).await?;
+
+    record(&state, AuditEvent::SessionsRevoked, admin.actor, user.email, context).await?;
+    Ok(StatusCode::OK)
+}
+
+/// Addresses that are not valid emails cannot belong to a user.
+fn parse_email(email: String) -> Result<Email, AuthAPIError> {
+    Email::parse(Secret::new(email)).map_err(|_| AuthAPIError::NotFound)
+}
+
+async fn find_user(state: &AppState, email: String) -> Result<User, AuthAPIError> {
+    state.user_store
+        .get_user(parse_email(email)?)
+        .await
+        .map_err(user_store_error)
+}
+
+fn user_store_error(e: UserStoreError) -> AuthAPIError {
+    match e {
+        UserStoreError::UserNotFound => AuthAPIError::NotFound,
+        e => AuthAPIError::UnexpectedError(e.into()),
+    }
+}
+
+/// The body is optional, so a bare POST changes the status without a reason.
+async fn set_status(
+    state: &AppState,
+    email: &Email,
+    status: AccountStatus,
+    request: Option<Json<StatusChangeRequest>>
+) -> Result<(), AuthAPIError> {
+    let reason = request
+        .and_then(|Json(request)| request.reason)
+        .map(|reason| reason.trim().to_owned())
+        .filter(|reason| !reason.is_empty());
+
+    state.user_store
+        .set_status(email, status, reason)
+        .await
+        .map_err(user_store_error)?;
+
+    if let Some(cache) = &state.account_status_cache {
+        cache.invalidate(email).await;
+    }
+    Ok(())
+}
+
+async fn revoke_sessions(state: &AppState, email: &Email) -> Result<(), AuthAPIError> {
+    state.banned_token_store
+        .revoke_sessions(email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))
+}
+
+/// Recorded once the action has succeeded. A failure to record is reported
+/// so actions never go unaudited without anyone noticing.
+async fn record(
+    state: &AppState,
+    event: AuditEvent,
+    actor: String,
+    subject: Email,
+    context: RequestContext
+) -> Result<(), AuthAPIError> {
+    state.audit_log
+        .record(AuditEntry::new(event, Some(actor), Some(subject)).with_context(context))
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))
+}
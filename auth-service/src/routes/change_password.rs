@@ -0,0 +1,64 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use secrecy::Secret;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuditEvent, AuthAPIError, Password, PasswordViolation, RequestContext, UserStoreError},
+    utils::auth::authenticated_email
+};
+
+use super::{password_violations, record_event};
+
+#[derive(Deserialize)]
+pub struct ChangePasswordRequest {
+    #[serde(rename = "currentPassword")]
+    pub current_password: Secret<String>,
+    #[serde(rename = "newPassword")]
+    pub new_password: Secret<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ChangePasswordResponse {
+    pub message: String,
+}
+
+/// Replaces the logged in user's password. The new one goes through the same
+/// checks as at signup and may not be one of their recent passwords.
+#[tracing::instrument(name = "Change password", skip_all)]
+pub async fn change_password(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    context: RequestContext,
+    Json(request): Json<ChangePasswordRequest>
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticated_email(&jar, &state).await?;
+
+    let current_password = Password::parse(request.current_password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let new_password = Password::parse(request.new_password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    match state.user_store.validate_user(email.clone(), current_password).await {
+        Ok(()) => {},
+        Err(UserStoreError::InvalidCredentials | UserStoreError::UserNotFound) => return Err(AuthAPIError::IncorrectCredentials),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    let violations = password_violations(&state, &new_password, &email).await?;
+    if !violations.is_empty() {
+        return Err(AuthAPIError::WeakPassword(violations));
+    }
+
+    match state.user_store.update_password(&email, new_password).await {
+        Ok(()) => {
+            record_event(&state, AuditEvent::PasswordChanged, &email, &context).await?;
+            Ok((StatusCode::OK, Json(ChangePasswordResponse {
+                message: "Password changed successfully!".to_string()
+            })))
+        },
+        Err(UserStoreError::PasswordReused) => Err(AuthAPIError::WeakPassword(vec![
+            PasswordViolation::Reused { last: state.settings.auth.password_policy.history_size }
+        ])),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
@@ -0,0 +1,61 @@
+use std::collections::BTreeMap;
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use serde::{Deserialize, Serialize};
+
+use crate::app_state::AppState;
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct LivenessResponse {
+    pub status: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ReadinessResponse {
+    pub status: String,
+    pub dependencies: BTreeMap<String, DependencyStatus>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct DependencyStatus {
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The process is up and serving HTTP. Dependencies are not checked so a
+/// database outage doesn't get the service restarted.
+pub async fn health_live() -> impl IntoResponse {
+    Json(LivenessResponse { status: "live".to_owned() })
+}
+
+#[tracing::instrument(name = "Readiness", skip_all)]
+pub async fn health_ready(State(state): State<AppState>) -> impl IntoResponse {
+    if state.health.is_shutting_down() {
+        return (StatusCode::SERVICE_UNAVAILABLE, Json(ReadinessResponse {
+            status: "shutting_down".to_owned(),
+            dependencies: BTreeMap::new(),
+        }));
+    }
+
+    let outcomes = state.health.check_dependencies().await;
+    let ready = outcomes.values().all(Result::is_ok);
+
+    let dependencies = outcomes
+        .into_iter()
+        .map(|(name, outcome)| {
+            let status = match outcome {
+                Ok(()) => DependencyStatus { status: "up".to_owned(), error: None },
+                Err(e) => DependencyStatus { status: "down".to_owned(), error: Some(e.to_string()) },
+            };
+            (name.to_owned(), status)
+        })
+        .collect();
+
+    let (status_code, status) = match ready {
+        true => (StatusCode::OK, "ready"),
+        false => (StatusCode::SERVICE_UNAVAILABLE, "not_ready"),
+    };
+
+    (status_code, Json(ReadinessResponse { status: status.to_owned(), dependencies }))
+}
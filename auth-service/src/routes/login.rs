@@ -1,13 +1,24 @@
-use axum::{extract::State, http::{response, StatusCode}, response::IntoResponse, Json};
+use axum::{extract::State, http::{header::ACCEPT_LANGUAGE, HeaderMap, StatusCode}, response::IntoResponse, Json};
 use serde::{Deserialize, Serialize};
-use axum_extra::extract::CookieJar;
+use axum_extra::extract::{cookie::Cookie, CookieJar};
+use chrono::{Duration, Utc};
 
 use crate::{
     app_state::AppState,
-    domain::{AuthAPIError, Email, Password, LoginAttemptId, TwoFACode},
-    utils::auth::generate_auth_cookie
+    domain::{
+        AccountStatus, AuditEvent, AuthAPIError, Email, EmailTemplate, Locale, OutboxEmail, PasskeyLogin, Password, LoginAttemptId, RequestContext,
+        TwoFACode, TwoFAChannel, User
+    },
+    utils::{auth::{generate_auth_cookie, validate_trusted_device_token}, constants::TRUSTED_DEVICE_COOKIE_NAME}
 };
+use minijinja::context;
 use secrecy::{ExposeSecret, Secret};
+use webauthn_rs::prelude::RequestChallengeResponse;
+
+use super::{record_event, start_passkey_authentication};
+
+/// Matches the expiry of codes in the 2FA code store.
+const TWO_FA_CODE_EXPIRES_IN_MINUTES: u64 = 10;
 
 #[derive(Deserialize)]
 pub struct LoginRequest {
@@ -20,6 +31,7 @@ pub struct LoginRequest {
 pub enum LoginResponse {
     RegularAuth,
     TwoFactorAuth(TwoFactorAuthResponse),
+    WebAuthn(WebAuthnAuthResponse),
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -29,10 +41,20 @@ pub struct TwoFactorAuthResponse {
     pub login_attempt_id: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebAuthnAuthResponse {
+    pub message: String,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: String,
+    pub challenge: RequestChallengeResponse,
+}
+
 #[tracing::instrument(name = "Login", skip_all)]
 pub async fn login(
     State(state): State<AppState>,
     jar: CookieJar,
+    headers: HeaderMap,
+    context: RequestContext,
     Json(request): Json<LoginRequest>) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
     let email = match Email::parse(request.email.clone()) {
         Ok(email) => email,
@@ -44,9 +66,13 @@ pub async fn login(
         Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials))
     };
 
-    let user_store = state.user_store.read().await;
+    let user_store = &state.user_store;
 
     if user_store.validate_user(email.clone(), password.clone()).await.is_err() {
+        metrics::counter!("logins_total", "outcome" => "failure").increment(1);
+        if let Err(e) = record_event(&state, AuditEvent::LoginFailed, &email, &context).await {
+            return (jar, Err(e));
+        }
         return (jar, Err(AuthAPIError::IncorrectCredentials))
     }
 
@@ -55,33 +81,69 @@ pub async fn login(
         Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials))
     };
 
+    // Checked before any 2FA challenge goes out, and again when the token is minted.
+    if let Err(e) = check_account(&user) {
+        return (jar, Err(e));
+    }
+
     match user.requires2fa {
-        true => handle_2fa(&user.email, &state, jar).await,
-        false => handle_no_2fa(&user.email, jar).await
+        true => handle_2fa(&user, preferred_locale(&user, &headers), &state, jar, &context).await,
+        false => handle_no_2fa(&user.email, &state, jar, &context).await
     }
 }
 
 #[tracing::instrument(name = "Handle 2FA", skip_all)]
 async fn handle_2fa(
-    email: &Email,
+    user: &User,
+    locale: Locale,
     state: &AppState,
-    jar: CookieJar
+    jar: CookieJar,
+    context: &RequestContext
 ) -> (
     CookieJar,
     Result<(StatusCode, Json<LoginResponse>), AuthAPIError>
 ) {
+    let email = &user.email;
+
+    if is_trusted_device(email, state, &jar).await {
+        return handle_no_2fa(email, state, jar, context).await;
+    }
+
+    let passkeys = match state.passkey_store.get_passkeys(email).await {
+        Ok(passkeys) => passkeys,
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into())))
+    };
+
+    // Users with a registered passkey answer a WebAuthn assertion
+    // instead of an emailed code.
+    if !passkeys.is_empty() {
+        return match start_passkey_authentication(email, &passkeys, PasskeyLogin::SecondFactor, state).await {
+            Ok(response) => {
+                metrics::counter!("two_fa_challenges_issued_total", "channel" => "passkey").increment(1);
+                if let Err(e) = record_event(state, AuditEvent::TwoFAIssued, email, context).await {
+                    return (jar, Err(e));
+                }
+                (jar, Ok((StatusCode::PARTIAL_CONTENT, Json(LoginResponse::WebAuthn(response)))))
+            }
+            Err(e) => (jar, Err(e))
+        };
+    }
+
     let login_attempt_id = LoginAttemptId::default();
     let two_fa_code = TwoFACode::default();
 
-    let mut store = state.two_fa_code_store.write().await;
-    if let Err(e) = store.add_code(email.clone(), login_attempt_id.clone(), two_fa_code.clone()).await {
+    if let Err(e) = state.two_fa_code_store
+        .add_code(email.clone(), login_attempt_id.clone(), two_fa_code.clone())
+        .await {
         return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
     }
 
-    let email_client = state.email_client.write().await;
+    if let Err(e) = send_two_fa_code(user, locale, &two_fa_code, state).await {
+        return (jar, Err(e));
+    }
 
-    if let Err(e) = email_client.send_email(email, login_attempt_id.as_ref().expose_secret(), two_fa_code.as_ref().expose_secret()).await {
-        return (jar, Err(AuthAPIError::UnexpectedError(e)));
+    if let Err(e) = record_event(state, AuditEvent::TwoFAIssued, email, context).await {
+        return (jar, Err(e));
     }
 
     let two_factor = TwoFactorAuthResponse {
@@ -93,17 +155,63 @@ async fn handle_2fa(
     (jar, Ok((StatusCode::PARTIAL_CONTENT, response)))
 }
 
+#[tracing::instrument(name = "Send 2FA code", skip_all)]
+async fn send_two_fa_code(
+    user: &User,
+    locale: Locale,
+    two_fa_code: &TwoFACode,
+    state: &AppState
+) -> Result<(), AuthAPIError> {
+    let code = two_fa_code.as_ref().expose_secret();
+
+    if let (TwoFAChannel::Sms, Some(phone_number)) = (user.two_fa_channel, &user.phone_number) {
+        state.sms_client
+            .send_sms(phone_number, &two_fa_sms(locale, code))
+            .await
+            .map_err(AuthAPIError::UnexpectedError)?;
+
+        metrics::counter!("two_fa_challenges_issued_total", "channel" => "sms").increment(1);
+        return Ok(());
+    }
+
+    let rendered_email = state.email_templates
+        .render(EmailTemplate::TwoFACode, locale, context! {
+            code => code,
+            expires_in_minutes => TWO_FA_CODE_EXPIRES_IN_MINUTES,
+        })
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    // The outbox worker delivers the email so a slow provider cannot hold up the login.
+    state.email_outbox
+        .enqueue(OutboxEmail::new(user.email.clone(), rendered_email)
+            .with_deadline(Utc::now() + Duration::minutes(TWO_FA_CODE_EXPIRES_IN_MINUTES as i64)))
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    metrics::counter!("two_fa_challenges_issued_total", "channel" => "email").increment(1);
+    Ok(())
+}
+
+fn two_fa_sms(locale: Locale, code: &str) -> String {
+    match locale {
+        Locale::En => format!("Your login code is {}. It expires in {} minutes.", code, TWO_FA_CODE_EXPIRES_IN_MINUTES),
+        Locale::Es => format!("Tu código de inicio de sesión es {}. Caduca en {} minutos.", code, TWO_FA_CODE_EXPIRES_IN_MINUTES),
+    }
+}
+
 #[tracing::instrument(name = "Handle for no 2fa", skip_all)]
 async fn handle_no_2fa(
     email: &Email,
-    jar: CookieJar
+    state: &AppState,
+    jar: CookieJar,
+    context: &RequestContext
 ) -> (
     CookieJar,
     Result<(StatusCode, Json<LoginResponse>), AuthAPIError>
 ) {
-    let auth_cookie = match generate_auth_cookie(&email) {
+    let auth_cookie = match auth_cookie(email, LoginProof::Password, state, context).await {
         Ok(auth_cookie) => auth_cookie,
-        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e)))
+        Err(e) => return (jar, Err(e))
     };
 
     let updated_jar = jar.add(auth_cookie);
@@ -112,4 +220,101 @@ async fn handle_no_2fa(
     (updated_jar, Ok((StatusCode::OK, response)))
 }
 
+#[tracing::instrument(name = "Check trusted device", skip_all)]
+async fn is_trusted_device(email: &Email, state: &AppState, jar: &CookieJar) -> bool {
+    let Some(cookie) = jar.get(TRUSTED_DEVICE_COOKIE_NAME) else {
+        return false;
+    };
+
+    let claims = match validate_trusted_device_token(&Secret::new(cookie.value().to_owned()), &state.settings.auth.jwt_secret) {
+        Ok(claims) => claims,
+        Err(_) => return false
+    };
+
+    if claims.sub != *email.as_ref().expose_secret() {
+        return false;
+    }
+
+    // A failed lookup falls back to the regular 2FA challenge.
+    match state.trusted_device_store.is_trusted(email, claims.jti).await {
+        Ok(trusted) => trusted,
+        Err(e) => {
+            tracing::warn!(error = %e, "failed to look up trusted device");
+            false
+        }
+    }
+}
+
+/// Refuses suspended and disabled accounts and accounts an admin has asked
+/// to reset their password, counting the refused login under its reason.
+pub(crate) fn check_account(user: &User) -> Result<(), AuthAPIError> {
+    let (outcome, error) = match user.status {
+        AccountStatus::Active if user.password_reset_required => ("password_reset_required", AuthAPIError::PasswordResetRequired),
+        AccountStatus::Active => return Ok(()),
+        AccountStatus::Suspended => ("suspended", AuthAPIError::AccountSuspended),
+        AccountStatus::Disabled => ("disabled", AuthAPIError::AccountDisabled),
+    };
+
+    metrics::counter!("logins_total", "outcome" => outcome).increment(1);
+    Err(error)
+}
+
+/// What the user proved to get an auth token.
+pub(crate) enum LoginProof {
+    /// A password alone, when no 2FA is needed or the device is trusted.
+    Password,
+    /// A password and then a 2FA code or passkey.
+    SecondFactor,
+    /// A passkey without a password.
+    Passkey,
+}
+
+/// Mints the auth cookie with the user's current roles, permissions and
+/// session epoch. Every way of logging in ends here, so the account is
+/// checked once more first and, once the token exists, the login is
+/// recorded along with how the user proved who they are.
+pub(crate) async fn auth_cookie(
+    email: &Email,
+    proof: LoginProof,
+    state: &AppState,
+    context: &RequestContext
+) -> Result<Cookie<'static>, AuthAPIError> {
+    let user = state.user_store
+        .get_user(email.clone())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    check_account(&user)?;
+
+    let access = state.user_store
+        .get_access(email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let epoch = state.banned_token_store
+        .session_epoch(email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
 
+    let cookie = generate_auth_cookie(email, &access, epoch, &state.settings.auth.jwt_secret)
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    match proof {
+        LoginProof::Password => {},
+        LoginProof::SecondFactor => record_event(state, AuditEvent::TwoFAVerified, email, context).await?,
+        LoginProof::Passkey => record_event(state, AuditEvent::PasskeyVerified, email, context).await?,
+    }
+    record_event(state, AuditEvent::LoginSucceeded, email, context).await?;
+    metrics::counter!("logins_total", "outcome" => "success").increment(1);
+    Ok(cookie)
+}
+
+/// Emails and texts go out in the user's saved locale, then the browser's
+/// preferred language, then English.
+pub(crate) fn preferred_locale(user: &User, headers: &HeaderMap) -> Locale {
+    user.locale
+        .or_else(|| headers
+            .get(ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(Locale::from_accept_language))
+        .unwrap_or_default()
+}
@@ -3,13 +3,19 @@ use axum_extra::extract::CookieJar;
 use secrecy::Secret;
 
 use crate::{
-    app_state::AppState, domain::AuthAPIError, utils::{auth::validate_token,
+    app_state::AppState, domain::{AuditEvent, AuthAPIError, Email, RequestContext}, utils::{auth::validate_token,
         constants::JWT_COOKIE_NAME
     }
 };
 
+use super::record_event;
+
 #[tracing::instrument(name = "Logout", skip_all)]
-pub async fn logout(State(state): State<AppState>,jar: CookieJar) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+pub async fn logout(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    context: RequestContext
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
     let cookie = match  jar.get(JWT_COOKIE_NAME) {
         Some(cookie) => cookie,
         _ => return (jar, Err(AuthAPIError::MissingToken))
@@ -18,17 +24,25 @@ pub async fn logout(State(state): State<AppState>,jar: CookieJar) -> (CookieJar,
     let token = Secret::new(cookie.value().to_owned());
 
     
+    // Suspended users can still sign out, so their status is not checked.
     let banned_tk_store =  state.banned_token_store.clone();
-    if validate_token(&token, banned_tk_store).await.is_err() {
-        return (jar, Err(AuthAPIError::InvalidToken))
-    }
+    let email = match validate_token(&token, banned_tk_store, None, &state.settings.auth.jwt_secret).await
+        .ok()
+        .and_then(|claims| Email::parse(Secret::new(claims.sub)).ok()) {
+        Some(email) => email,
+        None => return (jar, Err(AuthAPIError::InvalidToken))
+    };
     
 
     // drop(banned_tk_store);
-    let mut banned_tk_store = state.banned_token_store.write().await;
+    let banned_tk_store = &state.banned_token_store;
 
     match banned_tk_store.store_banned_token(token).await {
         Ok(()) => {
+            metrics::counter!("tokens_revoked_total").increment(1);
+            if let Err(e) = record_event(&state, AuditEvent::Logout, &email, &context).await {
+                return (jar, Err(e));
+            }
             let jar = jar.remove(JWT_COOKIE_NAME);
             (jar, Ok(StatusCode::OK))
         }
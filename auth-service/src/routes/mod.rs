@@ -1,12 +1,30 @@
+mod activity;
+mod admin;
+mod change_password;
+mod health;
 mod login;
 mod logout;
+mod metrics;
+mod phone;
+mod reset_password;
 mod signup;
+mod trusted_devices;
 mod verify_2fa;
 mod verify_token;
+mod webauthn;
 
 // re-export items from sub-modules
+pub use activity::*;
+pub use admin::*;
+pub use change_password::*;
+pub use health::*;
 pub use login::*;
 pub use logout::*;
+pub use metrics::*;
+pub use phone::*;
+pub use reset_password::*;
 pub use signup::*;
+pub use trusted_devices::*;
 pub use verify_2fa::*;
-pub use verify_token::*;
\ No newline at end of file
+pub use verify_token::*;
+pub use webauthn::*;
\ No newline at end of file
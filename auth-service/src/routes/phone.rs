@@ -0,0 +1,138 @@
+use axum::{extract::State, http::{HeaderMap, StatusCode}, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Locale, PhoneNumber, PhoneVerificationStoreError, TwoFACode, TwoFAChannel},
+    utils::auth::authenticated_email
+};
+
+use super::preferred_locale;
+
+#[derive(Deserialize)]
+pub struct AddPhoneNumberRequest {
+    #[serde(rename = "phoneNumber")]
+    pub phone_number: Secret<String>,
+}
+
+#[derive(Deserialize)]
+pub struct VerifyPhoneNumberRequest {
+    pub code: Secret<String>,
+}
+
+#[derive(Deserialize)]
+pub struct TwoFAChannelRequest {
+    pub channel: TwoFAChannel,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct PhoneResponse {
+    pub message: String,
+}
+
+/// Texts a verification code to the number. The number is only saved once
+/// the code is sent back to `/phone/verify`.
+#[tracing::instrument(name = "Add phone number", skip_all)]
+pub async fn add_phone_number(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    headers: HeaderMap,
+    Json(request): Json<AddPhoneNumberRequest>
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticated_email(&jar, &state).await?;
+
+    let phone_number = PhoneNumber::parse(request.phone_number)
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let user = state.user_store
+        .get_user(email.clone())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let code = TwoFACode::default();
+
+    state.phone_verification_store
+        .add_code(email, phone_number.clone(), code.clone())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let message = verification_sms(preferred_locale(&user, &headers), code.as_ref().expose_secret());
+
+    state.sms_client
+        .send_sms(&phone_number, &message)
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    Ok((StatusCode::OK, Json(PhoneResponse {
+        message: "Verification code sent".to_owned()
+    })))
+}
+
+#[tracing::instrument(name = "Verify phone number", skip_all)]
+pub async fn verify_phone_number(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<VerifyPhoneNumberRequest>
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticated_email(&jar, &state).await?;
+
+    let code = TwoFACode::parse(request.code).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let (phone_number, expected_code) = match state.phone_verification_store.take_code(&email).await {
+        Ok(pending) => pending,
+        Err(PhoneVerificationStoreError::CodeNotFound) => return Err(AuthAPIError::IncorrectCredentials),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    if code != expected_code {
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+
+    state.user_store
+        .set_phone_number(&email, phone_number)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok((StatusCode::OK, Json(PhoneResponse {
+        message: "Phone number verified".to_owned()
+    })))
+}
+
+#[tracing::instrument(name = "Set 2FA channel", skip_all)]
+pub async fn set_two_fa_channel(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<TwoFAChannelRequest>
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticated_email(&jar, &state).await?;
+
+    let user_store = &state.user_store;
+
+    if request.channel == TwoFAChannel::Sms {
+        let user = user_store
+            .get_user(email.clone())
+            .await
+            .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+        // Codes can only be texted to a verified number.
+        if user.phone_number.is_none() {
+            return Err(AuthAPIError::InvalidCredentials);
+        }
+    }
+
+    user_store
+        .set_two_fa_channel(&email, request.channel)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok(StatusCode::OK)
+}
+
+fn verification_sms(locale: Locale, code: &str) -> String {
+    match locale {
+        Locale::En => format!("Your phone verification code is {}", code),
+        Locale::Es => format!("Tu código de verificación de teléfono es {}", code),
+    }
+}
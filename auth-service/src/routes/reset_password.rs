@@ -0,0 +1,118 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use minijinja::context;
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuditEvent, AuthAPIError, EmailTemplate, OutboxEmail, Password, PasswordResetToken, PasswordResetTokenStoreError,
+        PasswordViolation, RequestContext, TakenPasswordResetToken, User, UserStoreError
+    }
+};
+
+use super::{password_violations, record_event};
+
+/// Matches the expiry of tokens in the password reset token store.
+const PASSWORD_RESET_EXPIRES_IN_MINUTES: u64 = 60;
+/// Rejected new passwords a reset token survives. The user needs a new link
+/// after that.
+const MAX_PASSWORD_RESET_ATTEMPTS: u32 = 5;
+
+#[derive(Deserialize)]
+pub struct ResetPasswordRequest {
+    pub token: Secret<String>,
+    #[serde(rename = "newPassword")]
+    pub new_password: Secret<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ResetPasswordResponse {
+    pub message: String,
+}
+
+/// Sets a new password with an emailed reset token. The new password goes
+/// through the same checks as a password change, and every session the user
+/// had is revoked.
+#[tracing::instrument(name = "Reset password", skip_all)]
+pub async fn reset_password(
+    State(state): State<AppState>,
+    context: RequestContext,
+    Json(request): Json<ResetPasswordRequest>
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let token = PasswordResetToken::parse(request.token).map_err(|_| AuthAPIError::InvalidToken)?;
+    let new_password = Password::parse(request.new_password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let taken = match state.password_reset_token_store.take_token(&token).await {
+        Ok(taken) => taken,
+        Err(PasswordResetTokenStoreError::TokenNotFound) => return Err(AuthAPIError::InvalidToken),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+    let email = taken.email.clone();
+
+    let mut violations = password_violations(&state, &new_password, &email).await?;
+    if violations.is_empty() {
+        match state.user_store.update_password(&email, new_password).await {
+            Ok(()) => {},
+            Err(UserStoreError::PasswordReused) => violations.push(
+                PasswordViolation::Reused { last: state.settings.auth.password_policy.history_size }
+            ),
+            Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+        }
+    }
+
+    // A rejected password puts the token back, with the expiry it had, so
+    // the user can pick another one without asking for a new link.
+    if !violations.is_empty() {
+        let rejected_attempts = taken.rejected_attempts + 1;
+        if rejected_attempts < MAX_PASSWORD_RESET_ATTEMPTS {
+            state.password_reset_token_store
+                .restore_token(token, TakenPasswordResetToken { rejected_attempts, ..taken })
+                .await
+                .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+        }
+        return Err(AuthAPIError::WeakPassword(violations));
+    }
+
+    state.banned_token_store
+        .revoke_sessions(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    record_event(&state, AuditEvent::PasswordReset, &email, &context).await?;
+
+    Ok((StatusCode::OK, Json(ResetPasswordResponse {
+        message: "Password reset successfully!".to_string()
+    })))
+}
+
+/// Stores a new reset token for the user and emails them a link to
+/// `auth.password_reset_url` carrying it.
+#[tracing::instrument(name = "Send password reset email", skip_all)]
+pub(crate) async fn send_password_reset_email(user: &User, state: &AppState) -> Result<(), AuthAPIError> {
+    let token = PasswordResetToken::default();
+
+    let mut reset_link = Url::parse(&state.settings.auth.password_reset_url)
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    reset_link.query_pairs_mut().append_pair("token", token.as_ref().expose_secret());
+
+    state.password_reset_token_store
+        .add_token(token, user.email.clone())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let rendered_email = state.email_templates
+        .render(EmailTemplate::PasswordReset, user.locale.unwrap_or_default(), context! {
+            reset_link => reset_link.as_str(),
+            expires_in_minutes => PASSWORD_RESET_EXPIRES_IN_MINUTES,
+        })
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    state.email_outbox
+        .enqueue(OutboxEmail::new(user.email.clone(), rendered_email))
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok(())
+}
@@ -3,13 +3,17 @@ use axum::{http::StatusCode, extract::State,
 use serde::{Deserialize, Serialize};
 use secrecy::Secret;
 
-use crate::{app_state::AppState, domain::{AuthAPIError, Email, User, UserStoreError, Password}};
+use crate::{app_state::AppState, domain::{AuditEvent, AuthAPIError, Email, Locale, PasswordViolation, RequestContext, User, Password, UserStoreError}};
+
+use super::record_event;
+
 #[derive(Deserialize)]
 pub struct SignupRequest {
     pub email:Secret<String>,
     pub password: Secret<String>,
     #[serde(rename = "requires2FA")]
     pub requires_2fa: bool,
+    pub locale: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
@@ -18,28 +22,57 @@ pub struct SignupResponse {
 }
 
 #[tracing::instrument(name = "Signup", skip_all)]
-pub async fn signup(State(state): State<AppState>, Json(request): Json<SignupRequest>) -> Result<impl IntoResponse, AuthAPIError> {
+pub async fn signup(
+    State(state): State<AppState>,
+    context: RequestContext,
+    Json(request): Json<SignupRequest>
+) -> Result<impl IntoResponse, AuthAPIError> {
     let email = Email::parse(request.email.clone()).map_err(|_| AuthAPIError::InvalidCredentials)?;
     let password = Password::parse(request.password.clone()).map_err(|_| AuthAPIError::InvalidCredentials)?;
 
-    let user = User::new(email.clone(), password, request.requires_2fa);
-
-    let mut user_store = state.user_store.write().await;
+    let locale = match request.locale.as_deref() {
+        Some(tag) => Some(Locale::parse(tag).ok_or(AuthAPIError::InvalidCredentials)?),
+        None => None
+    };
 
-    if user_store.get_user(email).await.is_ok() {
-        return Err(AuthAPIError::UserAlreadyExists);
+    let violations = password_violations(&state, &password, &email).await?;
+    if !violations.is_empty() {
+        return Err(AuthAPIError::WeakPassword(violations));
     }
 
-    match user_store.add_user(user).await {
+    let user = User::new(email.clone(), password, request.requires_2fa).with_locale(locale);
+
+    // The store rejects duplicates atomically, so concurrent signups for the
+    // same email cannot both succeed.
+    match state.user_store.add_user(user).await {
         Ok(()) => {
+            metrics::counter!("signups_total").increment(1);
+            record_event(&state, AuditEvent::Signup, &email, &context).await?;
+
             let response = Json(SignupResponse {
                 message: "User created successfully!".to_string()
             });
             
             Ok((StatusCode::CREATED, response))
         },
+        Err(UserStoreError::UserAlreadyExists) => Err(AuthAPIError::UserAlreadyExists),
         Err(e) => {
             Err(AuthAPIError::UnexpectedError(e.into()))
         },
     }
 }
+
+/// Every policy rule a new password breaks, plus `Breached` when it shows up
+/// in the breached password dataset.
+pub(crate) async fn password_violations(state: &AppState, password: &Password, email: &Email) -> Result<Vec<PasswordViolation>, AuthAPIError> {
+    let mut violations = state.password_policy.check(password.as_ref(), email).err().unwrap_or_default();
+
+    let breach_count = state.breached_password_checker.breach_count(password.as_ref())
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+    if breach_count > 0 {
+        violations.push(PasswordViolation::Breached);
+    }
+
+    Ok(violations)
+}
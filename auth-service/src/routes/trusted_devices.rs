@@ -0,0 +1,39 @@
+use axum::{extract::{Path, State}, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use uuid::Uuid;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, TrustedDeviceStoreError},
+    utils::auth::authenticated_email
+};
+
+#[tracing::instrument(name = "List trusted devices", skip_all)]
+pub async fn list_trusted_devices(
+    State(state): State<AppState>,
+    jar: CookieJar
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticated_email(&jar, &state).await?;
+
+    let devices = state.trusted_device_store
+        .get_devices(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok((StatusCode::OK, Json(devices)))
+}
+
+#[tracing::instrument(name = "Revoke trusted device", skip_all)]
+pub async fn revoke_trusted_device(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Path(device_id): Path<Uuid>
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticated_email(&jar, &state).await?;
+
+    match state.trusted_device_store.revoke_device(&email, device_id).await {
+        Ok(()) => Ok(StatusCode::OK),
+        Err(TrustedDeviceStoreError::TrustedDeviceNotFound) => Err(AuthAPIError::NotFound),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
@@ -1,11 +1,16 @@
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum::{extract::State, http::{header::USER_AGENT, HeaderMap, StatusCode}, response::IntoResponse, Json};
 use axum_extra::extract::CookieJar;
+use chrono::Duration;
 use secrecy::Secret;
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 
-use crate::{app_state::AppState, domain::{AuthAPIError, Email, LoginAttemptId, TwoFACode}, utils::auth::generate_auth_cookie};
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, LoginAttemptId, RequestContext, TrustedDevice, TwoFACode},
+    utils::auth::{generate_trusted_device_cookie, TRUSTED_DEVICE_TTL_DAYS}
+};
 
-use super::LoginResponse;
+use super::{auth_cookie, LoginProof, LoginResponse};
 
 #[derive(Deserialize)]
 pub struct Verify2FARequest {
@@ -13,12 +18,16 @@ pub struct Verify2FARequest {
     #[serde(rename = "loginAttemptId")]
     pub login_attempt_id: Secret<String>,
     #[serde(rename = "2FACode")]
-    pub two_fa_code: Secret<String>
+    pub two_fa_code: Secret<String>,
+    #[serde(rename = "rememberDevice", default)]
+    pub remember_device: bool
 }
 #[tracing::instrument(name = "Verify 2FA", skip_all)]
 pub async fn verify_2fa(
     State(state): State<AppState>,
+    headers: HeaderMap,
     jar: CookieJar,
+    context: RequestContext,
     Json(request): Json<Verify2FARequest>
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
     let email = match Email::parse(request.email.clone()) {
@@ -34,7 +43,7 @@ pub async fn verify_2fa(
         Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials))
     };
 
-    let mut two_fa_code_store = state.two_fa_code_store.write().await;
+    let two_fa_code_store = &state.two_fa_code_store;
 
     let result = match two_fa_code_store.get_code(&email).await {
         Ok(result) => result,
@@ -49,12 +58,34 @@ pub async fn verify_2fa(
         return (jar, Err(AuthAPIError::UnexpectedError(e.into())))
     }
 
-    let auth_cookie = match generate_auth_cookie(&email) {
+    let auth_cookie = match auth_cookie(&email, LoginProof::SecondFactor, &state, &context).await {
         Ok(auth_cookie) => auth_cookie,
-        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e)))
+        Err(e) => return (jar, Err(e))
     };
 
-    let updated_jar = jar.add(auth_cookie);
+    let mut updated_jar = jar.add(auth_cookie);
+
+    if request.remember_device {
+        let user_agent = headers
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned);
+
+        let device = TrustedDevice::new(user_agent, Duration::days(TRUSTED_DEVICE_TTL_DAYS));
+
+        let trusted_device_cookie = match generate_trusted_device_cookie(&email, &device, &state.settings.auth.jwt_secret) {
+            Ok(cookie) => cookie,
+            Err(e) => return (updated_jar, Err(AuthAPIError::UnexpectedError(e)))
+        };
+
+        if let Err(e) = state.trusted_device_store.add_device(email, device).await {
+            return (updated_jar, Err(AuthAPIError::UnexpectedError(e.into())))
+        }
+
+        updated_jar = updated_jar.add(trusted_device_cookie);
+    }
+
+    metrics::counter!("two_fa_challenges_passed_total", "method" => "code").increment(1);
 
     let response = Json(LoginResponse::RegularAuth);
 
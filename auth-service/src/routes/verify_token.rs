@@ -1,6 +1,6 @@
 use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 use secrecy::Secret;
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 
 use crate::{app_state::AppState, domain::AuthAPIError, utils::auth::validate_token};
 
@@ -15,7 +15,7 @@ pub async fn verify_token(State(state): State<AppState>, Json(request): Json<Tok
     let valid_token = &request.token;
     let banned_tk_store = state.banned_token_store.clone();
 
-    if validate_token(&valid_token, banned_tk_store).await.is_err() {
+    if validate_token(valid_token, banned_tk_store, state.account_status_cache.as_deref(), &state.settings.auth.jwt_secret).await.is_err() {
         return Err(AuthAPIError::InvalidToken)
     }
 
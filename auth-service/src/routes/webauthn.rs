@@ -0,0 +1,287 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use color_eyre::eyre::Context;
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use rand::{Rng, RngCore};
+use uuid::Uuid;
+use webauthn_rs::{
+    fake::{FakeCredentialIDDistribution, FakePasskeyDistribution, WebauthnFakeCredentialGenerator},
+    prelude::{CreationChallengeResponse, CredentialID, Passkey, PublicKeyCredential, RegisterPublicKeyCredential},
+    DEFAULT_AUTHENTICATOR_TIMEOUT
+};
+use webauthn_rs_proto::{AllowCredentials, PublicKeyCredentialRequestOptions, RequestChallengeResponse, UserVerificationPolicy};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, LoginAttemptId, PasskeyLogin, PasskeyStoreError, RequestContext},
+    utils::auth::authenticated_email
+};
+
+use super::{auth_cookie, LoginProof, LoginResponse, WebAuthnAuthResponse};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebAuthnRegisterStartResponse {
+    pub challenge: CreationChallengeResponse,
+}
+
+#[derive(Deserialize)]
+pub struct WebAuthnRegisterFinishRequest {
+    pub credential: RegisterPublicKeyCredential,
+}
+
+#[derive(Deserialize)]
+pub struct WebAuthnLoginStartRequest {
+    pub email: Secret<String>,
+}
+
+#[derive(Deserialize)]
+pub struct WebAuthnLoginFinishRequest {
+    pub email: Secret<String>,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: Secret<String>,
+    pub credential: PublicKeyCredential,
+}
+
+#[tracing::instrument(name = "Start WebAuthn registration", skip_all)]
+pub async fn webauthn_register_start(
+    State(state): State<AppState>,
+    jar: CookieJar
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticated_email(&jar, &state).await?;
+
+    let existing_credentials = state.passkey_store
+        .get_passkeys(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?
+        .iter()
+        .map(|passkey| passkey.cred_id().clone())
+        .collect::<Vec<_>>();
+
+    let user_name = email.as_ref().expose_secret();
+    let (challenge, registration) = state.webauthn
+        .start_passkey_registration(user_unique_id(&email), user_name, user_name, Some(existing_credentials))
+        .wrap_err("failed to start passkey registration")
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    state.webauthn_challenge_store
+        .add_registration(email, registration)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok((StatusCode::OK, Json(WebAuthnRegisterStartResponse { challenge })))
+}
+
+#[tracing::instrument(name = "Finish WebAuthn registration", skip_all)]
+pub async fn webauthn_register_finish(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<WebAuthnRegisterFinishRequest>
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticated_email(&jar, &state).await?;
+
+    let registration = state.webauthn_challenge_store
+        .take_registration(&email)
+        .await
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    let passkey = state.webauthn
+        .finish_passkey_registration(&request.credential, &registration)
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    match state.passkey_store.add_passkey(email, passkey).await {
+        Ok(()) => Ok(StatusCode::CREATED),
+        Err(PasskeyStoreError::PasskeyAlreadyExists) => Err(AuthAPIError::IncorrectCredentials),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+/// Answers the same way whether or not the email has passkeys, so it cannot
+/// be used to find out which accounts exist.
+#[tracing::instrument(name = "Start WebAuthn login", skip_all)]
+pub async fn webauthn_login_start(
+    State(state): State<AppState>,
+    Json(request): Json<WebAuthnLoginStartRequest>
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let passkeys = state.passkey_store
+        .get_passkeys(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    if passkeys.is_empty() {
+        return Ok((StatusCode::OK, Json(decoy_passkey_authentication(&email, &state)?)));
+    }
+
+    // Nothing is recorded until the assertion is verified. Anyone can start
+    // a passwordless login for any email.
+    let response = start_passkey_authentication(&email, &passkeys, PasskeyLogin::Passwordless, &state).await?;
+
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[tracing::instrument(name = "Finish WebAuthn login", skip_all)]
+pub async fn webauthn_login_finish(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    context: RequestContext,
+    Json(request): Json<WebAuthnLoginFinishRequest>
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let email = match Email::parse(request.email) {
+        Ok(email) => email,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials))
+    };
+    let login_attempt_id = match LoginAttemptId::parse(request.login_attempt_id) {
+        Ok(login_attempt_id) => login_attempt_id,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials))
+    };
+
+    let kind = match finish_passkey_authentication(&email, &login_attempt_id, &request.credential, &state).await {
+        Ok(kind) => kind,
+        Err(e) => return (jar, Err(e))
+    };
+
+    let proof = match kind {
+        PasskeyLogin::SecondFactor => LoginProof::SecondFactor,
+        PasskeyLogin::Passwordless => LoginProof::Passkey,
+    };
+
+    let auth_cookie = match auth_cookie(&email, proof, &state, &context).await {
+        Ok(auth_cookie) => auth_cookie,
+        Err(e) => return (jar, Err(e))
+    };
+
+    let updated_jar = jar.add(auth_cookie);
+
+    if kind == PasskeyLogin::SecondFactor {
+        metrics::counter!("two_fa_challenges_passed_total", "method" => "passkey").increment(1);
+    }
+
+    (updated_jar, Ok((StatusCode::OK, Json(LoginResponse::RegularAuth))))
+}
+
+/// Creates an assertion challenge for the given passkeys and keeps the
+/// matching server state until the client answers it.
+pub(crate) async fn start_passkey_authentication(
+    email: &Email,
+    passkeys: &[Passkey],
+    kind: PasskeyLogin,
+    state: &AppState
+) -> Result<WebAuthnAuthResponse, AuthAPIError> {
+    let login_attempt_id = LoginAttemptId::default();
+
+    let (challenge, authentication) = state.webauthn
+        .start_passkey_authentication(passkeys)
+        .wrap_err("failed to start passkey authentication")
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    state.webauthn_challenge_store
+        .add_authentication(email.clone(), login_attempt_id.clone(), kind, authentication)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok(WebAuthnAuthResponse {
+        message: "WebAuthn assertion required".to_string(),
+        login_attempt_id: login_attempt_id.as_ref().expose_secret().to_owned(),
+        challenge
+    })
+}
+
+/// Checks the assertion against the pending challenge and returns whether
+/// it was a second factor or a passwordless login.
+async fn finish_passkey_authentication(
+    email: &Email,
+    login_attempt_id: &LoginAttemptId,
+    credential: &PublicKeyCredential,
+    state: &AppState
+) -> Result<PasskeyLogin, AuthAPIError> {
+    let (expected_email, kind, authentication) = state.webauthn_challenge_store
+        .take_authentication(login_attempt_id)
+        .await
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    if *email != expected_email {
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+
+    let result = state.webauthn
+        .finish_passkey_authentication(credential, &authentication)
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    if !result.needs_update() {
+        return Ok(kind);
+    }
+
+    let passkey_store = &state.passkey_store;
+
+    let passkeys = passkey_store
+        .get_passkeys(email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    for mut passkey in passkeys {
+        if passkey.update_credential(&result) == Some(true) {
+            passkey_store
+                .update_passkey(email, passkey)
+                .await
+                .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+        }
+    }
+
+    Ok(kind)
+}
+
+/// A challenge for credentials that do not exist. Nothing is stored, so
+/// answering it fails like a wrong assertion. The fake credential ids are
+/// derived from the email and stay the same between requests.
+fn decoy_passkey_authentication(email: &Email, state: &AppState) -> Result<WebAuthnAuthResponse, AuthAPIError> {
+    let credential_ids = WebauthnFakeCredentialGenerator::<DecoyPasskeyDistribution>::new(
+            state.settings.auth.jwt_secret.expose_secret().as_bytes())
+        .and_then(|generator| generator.generate(email.as_ref().expose_secret().as_bytes()))
+        .wrap_err("failed to generate decoy passkey credentials")
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    let challenge = RequestChallengeResponse {
+        public_key: PublicKeyCredentialRequestOptions {
+            challenge: rand::thread_rng().gen::<[u8; 32]>().to_vec().into(),
+            timeout: Some(DEFAULT_AUTHENTICATOR_TIMEOUT.as_millis() as u32),
+            rp_id: state.settings.webauthn.rp_id.clone(),
+            allow_credentials: credential_ids
+                .into_iter()
+                .map(|id| AllowCredentials { type_: "public-key".to_owned(), id: id.into(), transports: None })
+                .collect(),
+            user_verification: UserVerificationPolicy::Required,
+            hints: None,
+            extensions: None,
+        },
+        mediation: None,
+    };
+
+    Ok(WebAuthnAuthResponse {
+        message: "WebAuthn assertion required".to_string(),
+        login_attempt_id: LoginAttemptId::default().as_ref().expose_secret().to_owned(),
+        challenge
+    })
+}
+
+/// `FakePasskeyDistribution` without the accounts that have no passkey,
+/// since a passkey login only starts for accounts that have one.
+struct DecoyPasskeyDistribution;
+
+impl FakeCredentialIDDistribution for DecoyPasskeyDistribution {
+    fn generate<R: RngCore>(seeded_rng: &mut R) -> Vec<CredentialID> {
+        loop {
+            let credential_ids = FakePasskeyDistribution::generate(seeded_rng);
+            if !credential_ids.is_empty() {
+                return credential_ids;
+            }
+        }
+    }
+}
+
+// WebAuthn needs a stable, opaque user handle. Derive it from the email so
+// no extra column is needed on the users table.
+fn user_unique_id(email: &Email) -> Uuid {
+    Uuid::new_v5(&Uuid::NAMESPACE_OID, email.as_ref().expose_secret().as_bytes())
+}
@@ -0,0 +1,143 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::RwLock;
+
+use crate::{
+    app_state::UserStoreType,
+    domain::{AccountStatus, Email, UserStoreError},
+};
+
+/// Account statuses looked up while validating tokens, kept for a short
+/// while so busy users do not cost a database query per request. A status
+/// changed on another instance takes up to the TTL to apply here.
+pub struct AccountStatusCache {
+    user_store: UserStoreType,
+    ttl: Duration,
+    entries: RwLock<Entries>,
+}
+
+struct Entries {
+    statuses: HashMap<Email, (AccountStatus, Instant)>,
+    swept_at: Instant,
+}
+
+impl AccountStatusCache {
+    pub fn new(user_store: UserStoreType, ttl: Duration) -> Self {
+        Self {
+            user_store,
+            ttl,
+            entries: RwLock::new(Entries {
+                statuses: HashMap::new(),
+                swept_at: Instant::now(),
+            }),
+        }
+    }
+
+    #[tracing::instrument(name = "Get cached account status", skip_all)]
+    pub async fn status(&self, email: &Email) -> Result<AccountStatus, UserStoreError> {
+        if let Some((status, fetched_at)) = self.entries.read().await.statuses.get(email) {
+            if fetched_at.elapsed() < self.ttl {
+                return Ok(*status);
+            }
+        }
+
+        let status = self.user_store.get_status(email).await?;
+
+        // A miss replaces the user's own entry. Other expired entries are
+        // swept at most once per TTL, so the map only holds recently active
+        // users without a full scan on every miss.
+        let mut entries = self.entries.write().await;
+        entries.statuses.insert(email.clone(), (status, Instant::now()));
+        if entries.swept_at.elapsed() >= self.ttl {
+            entries.statuses.retain(|_, (_, fetched_at)| fetched_at.elapsed() < self.ttl);
+            entries.swept_at = Instant::now();
+        }
+
+        Ok(status)
+    }
+
+    /// Forgets a status this instance has just changed, so the change
+    /// applies here straight away.
+    pub async fn invalidate(&self, email: &Email) {
+        self.entries.write().await.statuses.remove(email);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use secrecy::Secret;
+
+    use crate::{
+        domain::{Password, User, UserStore},
+        services::HashmapUserStore,
+    };
+
+    use super::*;
+
+    async fn store_with_user(email: &Email) -> Arc<HashmapUserStore> {
+        let store = Arc::new(HashmapUserStore::default());
+        let password = Password::parse(Secret::new("password".to_owned())).unwrap();
+        store.add_user(User::new(email.clone(), password, false)).await.unwrap();
+        store
+    }
+
+    #[tokio::test]
+    async fn status_is_cached_until_invalidated() {
+        let email = Email::parse(Secret::new("user.test@mail.com".to_owned())).unwrap();
+        let store = store_with_user(&email).await;
+        let cache = AccountStatusCache::new(store.clone(), Duration::from_secs(60));
+
+        assert_eq!(cache.status(&email).await, Ok(AccountStatus::Active));
+
+        store.set_status(&email, AccountStatus::Suspended, None).await.unwrap();
+        assert_eq!(cache.status(&email).await, Ok(AccountStatus::Active));
+
+        cache.invalidate(&email).await;
+        assert_eq!(cache.status(&email).await, Ok(AccountStatus::Suspended));
+    }
+
+    #[tokio::test]
+    async fn status_is_looked_up_again_after_the_ttl() {
+        let email = Email::parse(Secret::new("user.test@mail.com".to_owned())).unwrap();
+        let store = store_with_user(&email).await;
+        let cache = AccountStatusCache::new(store.clone(), Duration::ZERO);
+
+        assert_eq!(cache.status(&email).await, Ok(AccountStatus::Active));
+
+        store.set_status(&email, AccountStatus::Disabled, None).await.unwrap();
+        assert_eq!(cache.status(&email).await, Ok(AccountStatus::Disabled));
+    }
+
+    #[tokio::test]
+    async fn expired_entries_are_swept_once_per_ttl() {
+        let first = Email::parse(Secret::new("first.user@mail.com".to_owned())).unwrap();
+        let second = Email::parse(Secret::new("second.user@mail.com".to_owned())).unwrap();
+        let store = store_with_user(&first).await;
+        let password = Password::parse(Secret::new("password".to_owned())).unwrap();
+        store.add_user(User::new(second.clone(), password, false)).await.unwrap();
+        let cache = AccountStatusCache::new(store, Duration::from_millis(50));
+
+        cache.status(&first).await.unwrap();
+        cache.status(&second).await.unwrap();
+        assert_eq!(cache.entries.read().await.statuses.len(), 2);
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        cache.status(&second).await.unwrap();
+
+        let entries = cache.entries.read().await;
+        assert_eq!(entries.statuses.keys().collect::<Vec<_>>(), vec![&second]);
+    }
+
+    #[tokio::test]
+    async fn unknown_users_are_reported() {
+        let email = Email::parse(Secret::new("user.test@mail.com".to_owned())).unwrap();
+        let cache = AccountStatusCache::new(Arc::new(HashmapUserStore::default()), Duration::from_secs(60));
+
+        assert_eq!(cache.status(&email).await, Err(UserStoreError::UserNotFound));
+    }
+}
@@ -0,0 +1,167 @@
+use chrono::{DateTime, Utc};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use crate::domain::{EmailOutbox, EmailOutboxError, EmailOutboxStats, OutboxEmail};
+
+struct Entry {
+    email: OutboxEmail,
+    next_attempt_at: DateTime<Utc>,
+    last_error: Option<String>,
+    dead: bool,
+}
+
+#[derive(Default)]
+pub struct HashmapEmailOutbox {
+    entries: RwLock<Vec<Entry>>
+}
+
+impl HashmapEmailOutbox {
+    pub async fn last_error(&self, id: Uuid) -> Option<String> {
+        self.entries
+            .read()
+            .await
+            .iter()
+            .find(|entry| entry.email.id == id)
+            .and_then(|entry| entry.last_error.clone())
+    }
+}
+
+fn entry_mut(entries: &mut [Entry], id: Uuid) -> Result<&mut Entry, EmailOutboxError> {
+    entries
+        .iter_mut()
+        .find(|entry| entry.email.id == id)
+        .ok_or(EmailOutboxError::EmailNotFound)
+}
+
+#[async_trait::async_trait]
+impl EmailOutbox for HashmapEmailOutbox {
+    async fn enqueue(&self, email: OutboxEmail) -> Result<(), EmailOutboxError> {
+        self.entries.write().await.push(Entry {
+            email,
+            next_attempt_at: Utc::now(),
+            last_error: None,
+            dead: false,
+        });
+        Ok(())
+    }
+
+    async fn claim_due(&self, limit: u32, lease_until: DateTime<Utc>) -> Result<Vec<OutboxEmail>, EmailOutboxError> {
+        let now = Utc::now();
+
+        let claimed = self.entries
+            .write()
+            .await
+            .iter_mut()
+            .filter(|entry| !entry.dead && entry.next_attempt_at <= now)
+            .take(limit as usize)
+            .map(|entry| {
+                entry.email.attempts += 1;
+                entry.next_attempt_at = lease_until;
+                entry.email.clone()
+            })
+            .collect();
+
+        Ok(claimed)
+    }
+
+    async fn mark_sent(&self, id: Uuid) -> Result<(), EmailOutboxError> {
+        let mut entries = self.entries.write().await;
+        let count = entries.len();
+        entries.retain(|entry| entry.email.id != id);
+
+        if entries.len() == count {
+            return Err(EmailOutboxError::EmailNotFound)
+        }
+        Ok(())
+    }
+
+    async fn retry_later(&self, id: Uuid, error: &str, retry_at: DateTime<Utc>) -> Result<(), EmailOutboxError> {
+        let mut entries = self.entries.write().await;
+        let entry = entry_mut(&mut entries, id)?;
+        entry.next_attempt_at = retry_at;
+        entry.last_error = Some(error.to_owned());
+        Ok(())
+    }
+
+    async fn dead_letter(&self, id: Uuid, error: &str) -> Result<(), EmailOutboxError> {
+        let mut entries = self.entries.write().await;
+        let entry = entry_mut(&mut entries, id)?;
+        entry.dead = true;
+        entry.email.email.html_body.clear();
+        entry.email.email.text_body.clear();
+        entry.last_error = Some(error.to_owned());
+        Ok(())
+    }
+
+    async fn stats(&self) -> Result<EmailOutboxStats, EmailOutboxError> {
+        let entries = self.entries.read().await;
+        let dead = entries.iter().filter(|entry| entry.dead).count() as u64;
+
+        Ok(EmailOutboxStats {
+            pending: entries.len() as u64 - dead,
+            dead,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Email, RenderedEmail};
+    use chrono::Duration;
+    use secrecy::Secret;
+
+    fn outbox_email() -> OutboxEmail {
+        OutboxEmail::new(
+            Email::parse(Secret::new("user.test@mail.com".to_string())).unwrap(),
+            RenderedEmail {
+                subject: "Your login code".to_owned(),
+                html_body: "<p>123456</p>".to_owned(),
+                text_body: "123456".to_owned(),
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn test_claim_due_hides_claimed_emails() {
+        let outbox = HashmapEmailOutbox::default();
+        let email = outbox_email();
+        outbox.enqueue(email.clone()).await.unwrap();
+
+        let claimed = outbox.claim_due(10, Utc::now() + Duration::minutes(5)).await.unwrap();
+
+        assert_eq!(claimed.len(), 1);
+        assert_eq!(claimed[0].id, email.id);
+        assert_eq!(claimed[0].attempts, 1);
+        assert!(outbox.claim_due(10, Utc::now()).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_mark_sent_removes_email() {
+        let outbox = HashmapEmailOutbox::default();
+        let email = outbox_email();
+        outbox.enqueue(email.clone()).await.unwrap();
+
+        outbox.mark_sent(email.id).await.unwrap();
+
+        assert_eq!(outbox.stats().await.unwrap(), EmailOutboxStats::default());
+        assert_eq!(outbox.mark_sent(email.id).await, Err(EmailOutboxError::EmailNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_dead_letter_is_never_claimed() {
+        let outbox = HashmapEmailOutbox::default();
+        let email = outbox_email();
+        outbox.enqueue(email.clone()).await.unwrap();
+
+        outbox.dead_letter(email.id, "mailbox unavailable").await.unwrap();
+
+        assert!(outbox.claim_due(10, Utc::now()).await.unwrap().is_empty());
+        assert_eq!(outbox.stats().await.unwrap(), EmailOutboxStats { pending: 0, dead: 1 });
+        assert_eq!(outbox.last_error(email.id).await.as_deref(), Some("mailbox unavailable"));
+
+        let entries = outbox.entries.read().await;
+        assert!(entries[0].email.email.html_body.is_empty() && entries[0].email.email.text_body.is_empty());
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+
+use webauthn_rs::prelude::Passkey;
+
+use crate::domain::{Email, PasskeyStore, PasskeyStoreError};
+
+#[derive(Default)]
+pub struct HashmapPasskeyStore {
+    passkeys: RwLock<HashMap<Email, Vec<Passkey>>>
+}
+
+#[async_trait::async_trait]
+impl PasskeyStore for HashmapPasskeyStore {
+    async fn add_passkey(&self, email: Email, passkey: Passkey) -> Result<(), PasskeyStoreError> {
+        let mut passkeys = self.passkeys.write().await;
+        let already_registered = passkeys
+            .values()
+            .flatten()
+            .any(|existing| existing.cred_id() == passkey.cred_id());
+
+        if already_registered {
+            return Err(PasskeyStoreError::PasskeyAlreadyExists)
+        }
+
+        passkeys.entry(email).or_default().push(passkey);
+        Ok(())
+    }
+
+    async fn get_passkeys(&self, email: &Email) -> Result<Vec<Passkey>, PasskeyStoreError> {
+        Ok(self.passkeys.read().await.get(email).cloned().unwrap_or_default())
+    }
+
+    async fn update_passkey(&self, email: &Email, passkey: Passkey) -> Result<(), PasskeyStoreError> {
+        let mut passkeys = self.passkeys.write().await;
+        let existing = passkeys
+            .get_mut(email)
+            .and_then(|passkeys| passkeys.iter_mut().find(|existing| existing.cred_id() == passkey.cred_id()))
+            .ok_or(PasskeyStoreError::PasskeyNotFound)?;
+
+        *existing = passkey;
+        Ok(())
+    }
+
+    async fn remove_passkeys(&self, email: &Email) -> Result<(), PasskeyStoreError> {
+        self.passkeys.write().await.remove(email);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secrecy::Secret;
+    use url::Url;
+    use uuid::Uuid;
+    use webauthn_authenticator_rs::{softpasskey::SoftPasskey, WebauthnAuthenticator};
+    use webauthn_rs::{Webauthn, WebauthnBuilder};
+
+    const RP_ORIGIN: &str = "http://localhost:3000";
+
+    fn register_passkey(webauthn: &Webauthn) -> Passkey {
+        let (challenge, state) = webauthn
+            .start_passkey_registration(Uuid::new_v4(), "user.test@mail.com", "user.test@mail.com", None)
+            .unwrap();
+
+        let mut authenticator = WebauthnAuthenticator::new(SoftPasskey::new(true));
+        let credential = authenticator
+            .do_registration(Url::parse(RP_ORIGIN).unwrap(), challenge)
+            .unwrap();
+
+        webauthn.finish_passkey_registration(&credential, &state).unwrap()
+    }
+
+    fn webauthn() -> Webauthn {
+        WebauthnBuilder::new("localhost", &Url::parse(RP_ORIGIN).unwrap())
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_add_and_get_passkeys() {
+        let store = HashmapPasskeyStore::default();
+        let email = Email::parse(Secret::new("user.test@mail.com".to_string())).unwrap();
+        let passkey = register_passkey(&webauthn());
+
+        store.add_passkey(email.clone(), passkey.clone()).await.unwrap();
+
+        let result = store.get_passkeys(&email).await.unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].cred_id(), passkey.cred_id());
+    }
+
+    #[tokio::test]
+    async fn test_add_duplicate_passkey() {
+        let store = HashmapPasskeyStore::default();
+        let email = Email::parse(Secret::new("user.test@mail.com".to_string())).unwrap();
+        let other_email = Email::parse(Secret::new("other.test@mail.com".to_string())).unwrap();
+        let passkey = register_passkey(&webauthn());
+
+        store.add_passkey(email, passkey.clone()).await.unwrap();
+
+        let result = store.add_passkey(other_email, passkey).await;
+        assert_eq!(result, Err(PasskeyStoreError::PasskeyAlreadyExists));
+    }
+
+    #[tokio::test]
+    async fn test_update_missing_passkey() {
+        let store = HashmapPasskeyStore::default();
+        let email = Email::parse(Secret::new("user.test@mail.com".to_string())).unwrap();
+        let passkey = register_passkey(&webauthn());
+
+        let result = store.update_passkey(&email, passkey).await;
+        assert_eq!(result, Err(PasskeyStoreError::PasskeyNotFound));
+    }
+}
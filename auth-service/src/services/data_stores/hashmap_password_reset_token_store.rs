@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, Utc};
+use secrecy::ExposeSecret;
+use tokio::sync::RwLock;
+
+use crate::domain::{
+    Email, PasswordResetToken, PasswordResetTokenStore, PasswordResetTokenStoreError, TakenPasswordResetToken
+};
+
+#[derive(Default)]
+pub struct HashmapPasswordResetTokenStore {
+    tokens: RwLock<HashMap<String, TakenPasswordResetToken>>
+}
+
+#[async_trait::async_trait]
+impl PasswordResetTokenStore for HashmapPasswordResetTokenStore {
+    async fn add_token(&self,
+        token: PasswordResetToken,
+        email: Email
+    ) -> Result<(), PasswordResetTokenStoreError> {
+        let entry = TakenPasswordResetToken {
+            email,
+            expires_at: Utc::now() + Duration::hours(1),
+            rejected_attempts: 0,
+        };
+        self.tokens.write().await.insert(token.as_ref().expose_secret().to_owned(), entry);
+        Ok(())
+    }
+
+    async fn take_token(&self,
+        token: &PasswordResetToken
+    ) -> Result<TakenPasswordResetToken, PasswordResetTokenStoreError> {
+        self.tokens
+            .write()
+            .await
+            .remove(token.as_ref().expose_secret())
+            .filter(|entry| entry.expires_at > Utc::now())
+            .ok_or(PasswordResetTokenStoreError::TokenNotFound)
+    }
+
+    async fn restore_token(&self,
+        token: PasswordResetToken,
+        taken: TakenPasswordResetToken
+    ) -> Result<(), PasswordResetTokenStoreError> {
+        if taken.expires_at > Utc::now() {
+            self.tokens.write().await.insert(token.as_ref().expose_secret().to_owned(), taken);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secrecy::Secret;
+
+    #[tokio::test]
+    async fn test_take_token_only_once() {
+        let store = HashmapPasswordResetTokenStore::default();
+        let email = Email::parse(Secret::new("user.test@mail.com".to_string())).unwrap();
+        let token = PasswordResetToken::default();
+
+        store.add_token(token.clone(), email.clone()).await.unwrap();
+
+        assert_eq!(store.take_token(&token).await.unwrap().email, email);
+        assert_eq!(store.take_token(&token).await, Err(PasswordResetTokenStoreError::TokenNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_restore_token_keeps_its_expiry() {
+        let store = HashmapPasswordResetTokenStore::default();
+        let email = Email::parse(Secret::new("user.test@mail.com".to_string())).unwrap();
+        let token = PasswordResetToken::default();
+
+        store.add_token(token.clone(), email).await.unwrap();
+        let taken = store.take_token(&token).await.unwrap();
+        let restored = TakenPasswordResetToken { rejected_attempts: 1, ..taken.clone() };
+        store.restore_token(token.clone(), restored.clone()).await.unwrap();
+
+        assert_eq!(store.take_token(&token).await.unwrap(), restored);
+
+        let expired = TakenPasswordResetToken { expires_at: Utc::now() - Duration::seconds(1), ..taken };
+        store.restore_token(token.clone(), expired).await.unwrap();
+        assert_eq!(store.take_token(&token).await, Err(PasswordResetTokenStoreError::TokenNotFound));
+    }
+}
@@ -0,0 +1,51 @@
+use std::collections::HashMap;
+
+use tokio::sync::RwLock;
+
+use crate::domain::{Email, PhoneNumber, PhoneVerificationStore, PhoneVerificationStoreError, TwoFACode};
+
+#[derive(Default)]
+pub struct HashmapPhoneVerificationStore {
+    codes: RwLock<HashMap<Email, (PhoneNumber, TwoFACode)>>
+}
+
+#[async_trait::async_trait]
+impl PhoneVerificationStore for HashmapPhoneVerificationStore {
+    async fn add_code(&self,
+        email: Email,
+        phone_number: PhoneNumber,
+        code: TwoFACode
+    ) -> Result<(), PhoneVerificationStoreError> {
+        self.codes.write().await.insert(email, (phone_number, code));
+        Ok(())
+    }
+
+    async fn take_code(&self,
+        email: &Email
+    ) -> Result<(PhoneNumber, TwoFACode), PhoneVerificationStoreError> {
+        self.codes
+            .write()
+            .await
+            .remove(email)
+            .ok_or(PhoneVerificationStoreError::CodeNotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secrecy::Secret;
+
+    #[tokio::test]
+    async fn test_take_code_only_once() {
+        let store = HashmapPhoneVerificationStore::default();
+        let email = Email::parse(Secret::new("user.test@mail.com".to_string())).unwrap();
+        let phone_number = PhoneNumber::parse(Secret::new("+14155550100".to_string())).unwrap();
+        let code = TwoFACode::default();
+
+        store.add_code(email.clone(), phone_number.clone(), code.clone()).await.unwrap();
+
+        assert_eq!(store.take_code(&email).await, Ok((phone_number, code)));
+        assert_eq!(store.take_code(&email).await, Err(PhoneVerificationStoreError::CodeNotFound));
+    }
+}
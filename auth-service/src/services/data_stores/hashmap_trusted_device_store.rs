@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+
+use uuid::Uuid;
+
+use crate::domain::{Email, TrustedDevice, TrustedDeviceStore, TrustedDeviceStoreError};
+
+#[derive(Default)]
+pub struct HashmapTrustedDeviceStore {
+    devices: RwLock<HashMap<Email, Vec<TrustedDevice>>>
+}
+
+#[async_trait::async_trait]
+impl TrustedDeviceStore for HashmapTrustedDeviceStore {
+    async fn add_device(&self, email: Email, device: TrustedDevice) -> Result<(), TrustedDeviceStoreError> {
+        self.devices.write().await.entry(email).or_default().push(device);
+        Ok(())
+    }
+
+    async fn get_devices(&self, email: &Email) -> Result<Vec<TrustedDevice>, TrustedDeviceStoreError> {
+        let devices = self.devices
+            .read()
+            .await
+            .get(email)
+            .map(|devices| devices.iter().filter(|device| !device.is_expired()).cloned().collect())
+            .unwrap_or_default();
+
+        Ok(devices)
+    }
+
+    async fn is_trusted(&self, email: &Email, device_id: Uuid) -> Result<bool, TrustedDeviceStoreError> {
+        let trusted = self.devices
+            .read()
+            .await
+            .get(email)
+            .map(|devices| devices.iter().any(|device| device.id == device_id && !device.is_expired()))
+            .unwrap_or(false);
+
+        Ok(trusted)
+    }
+
+    async fn revoke_device(&self, email: &Email, device_id: Uuid) -> Result<(), TrustedDeviceStoreError> {
+        let mut devices = self.devices.write().await;
+        let devices = devices
+            .get_mut(email)
+            .ok_or(TrustedDeviceStoreError::TrustedDeviceNotFound)?;
+
+        let count = devices.len();
+        devices.retain(|device| device.id != device_id);
+
+        if devices.len() == count {
+            return Err(TrustedDeviceStoreError::TrustedDeviceNotFound)
+        }
+        Ok(())
+    }
+
+    async fn revoke_all_devices(&self, email: &Email) -> Result<(), TrustedDeviceStoreError> {
+        self.devices.write().await.remove(email);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use secrecy::Secret;
+
+    #[tokio::test]
+    async fn test_add_device_is_trusted() {
+        let store = HashmapTrustedDeviceStore::default();
+        let email = Email::parse(Secret::new("user.test@mail.com".to_string())).unwrap();
+        let device = TrustedDevice::new(None, Duration::days(30));
+
+        store.add_device(email.clone(), device.clone()).await.unwrap();
+
+        assert!(store.is_trusted(&email, device.id).await.unwrap());
+        assert_eq!(store.get_devices(&email).await.unwrap(), vec![device]);
+    }
+
+    #[tokio::test]
+    async fn test_expired_device_is_not_trusted() {
+        let store = HashmapTrustedDeviceStore::default();
+        let email = Email::parse(Secret::new("user.test@mail.com".to_string())).unwrap();
+        let device = TrustedDevice::new(None, Duration::seconds(-1));
+
+        store.add_device(email.clone(), device.clone()).await.unwrap();
+
+        assert!(!store.is_trusted(&email, device.id).await.unwrap());
+        assert!(store.get_devices(&email).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_revoke_device() {
+        let store = HashmapTrustedDeviceStore::default();
+        let email = Email::parse(Secret::new("user.test@mail.com".to_string())).unwrap();
+        let device = TrustedDevice::new(None, Duration::days(30));
+
+        store.add_device(email.clone(), device.clone()).await.unwrap();
+        store.revoke_device(&email, device.id).await.unwrap();
+
+        assert!(!store.is_trusted(&email, device.id).await.unwrap());
+        assert_eq!(
+            store.revoke_device(&email, device.id).await,
+            Err(TrustedDeviceStoreError::TrustedDeviceNotFound)
+        );
+    }
+}
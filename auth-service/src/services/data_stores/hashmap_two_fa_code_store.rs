@@ -1,5 +1,7 @@
 use std::collections::HashMap;
 
+use tokio::sync::RwLock;
+
 use crate::domain::{
     data_stores::{TwoFACodeStore, TwoFACodeStoreError},
     loginattemptid::LoginAttemptId,
@@ -9,22 +11,22 @@ use crate::domain::{
 
 #[derive(Default)]
 pub struct HashmapTwoFACodeStore {
-    codes: HashMap<Email, (LoginAttemptId, TwoFACode)>
+    codes: RwLock<HashMap<Email, (LoginAttemptId, TwoFACode)>>
 }
 
 #[async_trait::async_trait]
 impl TwoFACodeStore for HashmapTwoFACodeStore{
-    async fn add_code(&mut self, 
+    async fn add_code(&self,
         email: Email,
         login_attempt_id: LoginAttemptId,
         code: TwoFACode
     ) -> Result<(), TwoFACodeStoreError> {
-        self.codes.insert(email, (login_attempt_id, code));
+        self.codes.write().await.insert(email, (login_attempt_id, code));
         Ok(())
     }
 
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
-        if self.codes.remove(email).is_some() {
+    async fn remove_code(&self, email: &Email) -> Result<(), TwoFACodeStoreError> {
+        if self.codes.write().await.remove(email).is_some() {
             Ok(())
         } else {
             return Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
@@ -35,7 +37,7 @@ impl TwoFACodeStore for HashmapTwoFACodeStore{
         &self,
         email: &Email,
     ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
-        if let Some(data) = self.codes.get(email) {
+        if let Some(data) = self.codes.read().await.get(email) {
             Ok((data.0.clone(), data.1.clone()))
         } else {
             return Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
@@ -51,7 +53,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_add_code() {
-        let mut store = HashmapTwoFACodeStore::default();
+        let store = HashmapTwoFACodeStore::default();
         let email = Email::parse(Secret::new("user.test@mail.com".to_string())).unwrap();
         let login_attempt = LoginAttemptId::parse(LoginAttemptId::default().as_ref().to_owned()).unwrap();
         let code = TwoFACode::parse(TwoFACode::default().as_ref().to_owned()).unwrap();
@@ -63,7 +65,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_remove_code() {
-        let mut store = HashmapTwoFACodeStore::default();
+        let store = HashmapTwoFACodeStore::default();
         let email = Email::parse(Secret::new("user.test@mail.com".to_string())).unwrap();
         let login_attempt = LoginAttemptId::parse(LoginAttemptId::default().as_ref().to_owned()).unwrap();
         let code = TwoFACode::parse(TwoFACode::default().as_ref().to_owned()).unwrap();
@@ -77,7 +79,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_remove_code_not_found() {
-        let mut store = HashmapTwoFACodeStore::default();
+        let store = HashmapTwoFACodeStore::default();
         let email = Email::parse(Secret::new("user.test@mail.com".to_string())).unwrap();
 
         let result = store.remove_code(&email).await;
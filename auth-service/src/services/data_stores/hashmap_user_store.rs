@@ -1,28 +1,55 @@
-use std::collections::HashMap;
-use crate::domain::{Email, Password, User, UserStore, UserStoreError};
+use std::collections::{BTreeSet, HashMap};
+use chrono::Utc;
+use secrecy::ExposeSecret;
+use tokio::sync::RwLock;
+use crate::domain::{
+    Access, AccountStatus, Email, Password, PhoneNumber, TwoFAChannel, User, UserPage, UserStore, UserStoreError,
+};
 
 
 
 #[derive(Default, Debug)]
 pub struct HashmapUserStore {
-    users: HashMap<Email, User>
+    users: RwLock<HashMap<Email, User>>,
+    /// Previous passwords, oldest first.
+    password_history: RwLock<HashMap<Email, Vec<Password>>>,
+    password_history_size: usize,
+    /// Permissions granted by each known role.
+    roles: HashMap<String, Vec<String>>,
+    user_roles: RwLock<HashMap<Email, BTreeSet<String>>>,
+}
+
+impl HashmapUserStore {
+    /// Defines a role that can then be assigned to users.
+    pub fn with_role(mut self, role: &str, permissions: &[&str]) -> Self {
+        self.roles.insert(role.to_owned(), permissions.iter().map(|permission| permission.to_string()).collect());
+        self
+    }
+
+    /// Rejects password changes that reuse any of the user's last `size`
+    /// passwords, counting the current one. 0 allows any password.
+    pub fn with_password_history(mut self, size: usize) -> Self {
+        self.password_history_size = size;
+        self
+    }
 }
 
 #[async_trait::async_trait]
 impl UserStore for HashmapUserStore {
-    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError> {
+    async fn add_user(&self, user: User) -> Result<(), UserStoreError> {
         let current_user_email = user.email.clone();
+        let mut users = self.users.write().await;
 
-        if self.users.contains_key(&current_user_email) {
+        if users.contains_key(&current_user_email) {
             return Err(UserStoreError::UserAlreadyExists)
         }
 
-        self.users.insert(current_user_email, user);
+        users.insert(current_user_email, user);
         Ok(())
     }
 
     async fn get_user(&self, email: Email) -> Result<User, UserStoreError> {
-        if let Some(user) = self.users.get(&email) {
+        if let Some(user) = self.users.read().await.get(&email) {
             return Ok(user.clone())
         } else {
             Err(UserStoreError::UserNotFound)
@@ -30,7 +57,7 @@ impl UserStore for HashmapUserStore {
     }
 
     async fn validate_user(&self, email: Email, password: Password) -> Result<(), UserStoreError> {
-        if let Some(user) = self.users.get(&email) {
+        if let Some(user) = self.users.read().await.get(&email) {
             if user.password == password {
                 return Ok(())
             }
@@ -39,6 +66,116 @@ impl UserStore for HashmapUserStore {
             return Err(UserStoreError::UserNotFound)
         }
     }
+
+    async fn update_password(&self, email: &Email, password: Password) -> Result<(), UserStoreError> {
+        let mut users = self.users.write().await;
+        let mut password_history = self.password_history.write().await;
+        let user = users.get_mut(email).ok_or(UserStoreError::UserNotFound)?;
+        let history = password_history.entry(email.clone()).or_default();
+        let kept_history = self.password_history_size.saturating_sub(1);
+
+        if self.password_history_size > 0
+            && (user.password == password || history.iter().rev().take(kept_history).any(|old| *old == password)) {
+            return Err(UserStoreError::PasswordReused);
+        }
+
+        history.push(std::mem::replace(&mut user.password, password));
+        history.drain(..history.len().saturating_sub(kept_history));
+        user.password_reset_required = false;
+        Ok(())
+    }
+
+    async fn set_phone_number(&self, email: &Email, phone_number: PhoneNumber) -> Result<(), UserStoreError> {
+        let mut users = self.users.write().await;
+        let user = users.get_mut(email).ok_or(UserStoreError::UserNotFound)?;
+        user.phone_number = Some(phone_number);
+        Ok(())
+    }
+
+    async fn set_two_fa_channel(&self, email: &Email, channel: TwoFAChannel) -> Result<(), UserStoreError> {
+        let mut users = self.users.write().await;
+        let user = users.get_mut(email).ok_or(UserStoreError::UserNotFound)?;
+        user.two_fa_channel = channel;
+        Ok(())
+    }
+
+    async fn get_access(&self, email: &Email) -> Result<Access, UserStoreError> {
+        let user_roles = self.user_roles.read().await;
+        let roles = user_roles.get(email).cloned().unwrap_or_default();
+        let permissions: BTreeSet<String> = roles.iter()
+            .flat_map(|role| self.roles.get(role).into_iter().flatten().cloned())
+            .collect();
+
+        Ok(Access { roles: roles.into_iter().collect(), permissions: permissions.into_iter().collect() })
+    }
+
+    async fn assign_role(&self, email: &Email, role: &str) -> Result<(), UserStoreError> {
+        if !self.users.read().await.contains_key(email) {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        if !self.roles.contains_key(role) {
+            return Err(UserStoreError::RoleNotFound);
+        }
+
+        self.user_roles.write().await.entry(email.clone()).or_default().insert(role.to_owned());
+        Ok(())
+    }
+
+    async fn remove_role(&self, email: &Email, role: &str) -> Result<(), UserStoreError> {
+        if let Some(roles) = self.user_roles.write().await.get_mut(email) {
+            roles.remove(role);
+        }
+        Ok(())
+    }
+
+    async fn list_users(&self, search: Option<&str>, offset: u64, limit: u64) -> Result<UserPage, UserStoreError> {
+        let search = search.map(str::to_lowercase);
+        let mut users: Vec<User> = self.users.read().await.values()
+            .filter(|user| search.as_deref().is_none_or(|search| {
+                user.email.as_ref().expose_secret().to_lowercase().contains(search)
+            }))
+            .cloned()
+            .collect();
+        users.sort_by(|a, b| a.email.as_ref().expose_secret().cmp(b.email.as_ref().expose_secret()));
+        let total = users.len() as u64;
+
+        Ok(UserPage {
+            users: users.into_iter().skip(offset as usize).take(limit as usize).collect(),
+            total,
+        })
+    }
+
+    async fn set_status(&self, email: &Email, status: AccountStatus, reason: Option<String>) -> Result<(), UserStoreError> {
+        let mut users = self.users.write().await;
+        let user = users.get_mut(email).ok_or(UserStoreError::UserNotFound)?;
+        user.status = status;
+        user.status_reason = reason;
+        user.status_changed_at = Some(Utc::now());
+        Ok(())
+    }
+
+    async fn get_status(&self, email: &Email) -> Result<AccountStatus, UserStoreError> {
+        self.users.read().await
+            .get(email)
+            .map(|user| user.status)
+            .ok_or(UserStoreError::UserNotFound)
+    }
+
+    async fn require_password_reset(&self, email: &Email) -> Result<(), UserStoreError> {
+        let mut users = self.users.write().await;
+        let user = users.get_mut(email).ok_or(UserStoreError::UserNotFound)?;
+        user.password_reset_required = true;
+        Ok(())
+    }
+
+    async fn reset_two_fa(&self, email: &Email) -> Result<(), UserStoreError> {
+        let mut users = self.users.write().await;
+        let user = users.get_mut(email).ok_or(UserStoreError::UserNotFound)?;
+        user.phone_number = None;
+        user.two_fa_channel = TwoFAChannel::Email;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -48,7 +185,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_add_user() {
-        let mut store = HashmapUserStore::default();
+        let store = HashmapUserStore::default();
         let email = Email::parse(Secret::new("user.test@mail.com".to_string())).unwrap();
         let password = Password::parse(Secret::new("password".to_string())).unwrap();
         let user = User::new(
@@ -63,7 +200,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_get_user() {
-        let mut store = HashmapUserStore::default();
+        let store = HashmapUserStore::default();
         let email = Email::parse(Secret::new("user.test@mail.com".to_string())).unwrap();
         let password = Password::parse(Secret::new("password".to_string())).unwrap();
         let user = User::new(
@@ -82,7 +219,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_validate_user() {
-        let mut store = HashmapUserStore::default();
+        let store = HashmapUserStore::default();
         let email = Email::parse(Secret::new("user.test@mail.com".to_string())).unwrap();
         let password = Password::parse(Secret::new("password".to_string())).unwrap();
         let user = User::new(
@@ -96,4 +233,100 @@ mod tests {
         let result = store.validate_user(user.email, user.password).await;
         assert_eq!(result, Ok(()));
     }
+
+    #[tokio::test]
+    async fn test_update_password_rejects_recent_passwords() {
+        let store = HashmapUserStore::default().with_password_history(3);
+        let email = Email::parse(Secret::new("user.test@mail.com".to_string())).unwrap();
+        let password = |n: u8| Password::parse(Secret::new(format!("password{}", n))).unwrap();
+        store.add_user(User::new(email.clone(), password(1), false)).await.unwrap();
+
+        store.update_password(&email, password(2)).await.unwrap();
+        store.update_password(&email, password(3)).await.unwrap();
+
+        for reused in [1, 2, 3] {
+            assert_eq!(store.update_password(&email, password(reused)).await, Err(UserStoreError::PasswordReused));
+        }
+
+        store.update_password(&email, password(4)).await.unwrap();
+        assert_eq!(store.update_password(&email, password(1)).await, Ok(()));
+        assert_eq!(store.validate_user(email, password(1)).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_assigned_roles_grant_their_permissions() {
+        let store = HashmapUserStore::default()
+            .with_role("admin", &["users:read", "users:admin"])
+            .with_role("support", &["users:read"]);
+        let email = Email::parse(Secret::new("user.test@mail.com".to_string())).unwrap();
+        let password = Password::parse(Secret::new("password".to_string())).unwrap();
+        store.add_user(User::new(email.clone(), password, false)).await.unwrap();
+
+        store.assign_role(&email, "support").await.unwrap();
+        store.assign_role(&email, "admin").await.unwrap();
+        assert_eq!(store.assign_role(&email, "owner").await, Err(UserStoreError::RoleNotFound));
+
+        let access = store.get_access(&email).await.unwrap();
+        assert_eq!(access.roles, vec!["admin", "support"]);
+        assert_eq!(access.permissions, vec!["users:admin", "users:read"]);
+        assert!(access.has_permission("users:admin"));
+
+        store.remove_role(&email, "admin").await.unwrap();
+        assert!(!store.get_access(&email).await.unwrap().has_permission("users:admin"));
+    }
+
+    #[tokio::test]
+    async fn test_set_phone_number_and_two_fa_channel() {
+        let store = HashmapUserStore::default();
+        let email = Email::parse(Secret::new("user.test@mail.com".to_string())).unwrap();
+        let password = Password::parse(Secret::new("password".to_string())).unwrap();
+        let phone_number = PhoneNumber::parse(Secret::new("+14155550100".to_string())).unwrap();
+        store.add_user(User::new(email.clone(), password, true)).await.unwrap();
+
+        store.set_phone_number(&email, phone_number.clone()).await.unwrap();
+        store.set_two_fa_channel(&email, TwoFAChannel::Sms).await.unwrap();
+
+        let user = store.get_user(email).await.unwrap();
+        assert_eq!(user.phone_number, Some(phone_number));
+        assert_eq!(user.two_fa_channel, TwoFAChannel::Sms);
+    }
+
+    #[tokio::test]
+    async fn test_list_users_filters_and_paginates() {
+        let store = HashmapUserStore::default();
+        let password = Password::parse(Secret::new("password".to_string())).unwrap();
+        for address in ["carol@mail.com", "alice@mail.com", "bob@other.com"] {
+            let email = Email::parse(Secret::new(address.to_string())).unwrap();
+            store.add_user(User::new(email, password.clone(), false)).await.unwrap();
+        }
+
+        let page = store.list_users(None, 1, 1).await.unwrap();
+        assert_eq!(page.total, 3);
+        assert_eq!(page.users[0].email.as_ref().expose_secret(), "bob@other.com");
+
+        let page = store.list_users(Some("MAIL.com"), 0, 10).await.unwrap();
+        assert_eq!(page.total, 2);
+        assert_eq!(page.users.iter().map(|user| user.email.as_ref().expose_secret().as_str()).collect::<Vec<_>>(),
+            vec!["alice@mail.com", "carol@mail.com"]);
+    }
+
+    #[tokio::test]
+    async fn test_password_change_clears_required_reset() {
+        let store = HashmapUserStore::default();
+        let email = Email::parse(Secret::new("user.test@mail.com".to_string())).unwrap();
+        let password = |n: u8| Password::parse(Secret::new(format!("password{}", n))).unwrap();
+        store.add_user(User::new(email.clone(), password(1), false)).await.unwrap();
+
+        store.require_password_reset(&email).await.unwrap();
+        store.set_status(&email, AccountStatus::Suspended, Some("chargeback".to_owned())).await.unwrap();
+        let user = store.get_user(email.clone()).await.unwrap();
+        assert!(user.password_reset_required);
+        assert_eq!(user.status, AccountStatus::Suspended);
+        assert_eq!(user.status_reason.as_deref(), Some("chargeback"));
+        assert!(user.status_changed_at.is_some());
+        assert_eq!(store.get_status(&email).await, Ok(AccountStatus::Suspended));
+
+        store.update_password(&email, password(2)).await.unwrap();
+        assert!(!store.get_user(email).await.unwrap().password_reset_required);
+    }
 }
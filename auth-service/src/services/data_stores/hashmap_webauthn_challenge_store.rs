@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use secrecy::ExposeSecret;
+use tokio::sync::RwLock;
+
+use webauthn_rs::prelude::{PasskeyAuthentication, PasskeyRegistration};
+
+use crate::domain::{Email, LoginAttemptId, PasskeyLogin, WebAuthnChallengeStore, WebAuthnChallengeStoreError};
+
+#[derive(Default)]
+pub struct HashmapWebAuthnChallengeStore {
+    registrations: RwLock<HashMap<Email, PasskeyRegistration>>,
+    authentications: RwLock<HashMap<String, (Email, PasskeyLogin, PasskeyAuthentication)>>
+}
+
+#[async_trait::async_trait]
+impl WebAuthnChallengeStore for HashmapWebAuthnChallengeStore {
+    async fn add_registration(&self,
+        email: Email,
+        state: PasskeyRegistration
+    ) -> Result<(), WebAuthnChallengeStoreError> {
+        self.registrations.write().await.insert(email, state);
+        Ok(())
+    }
+
+    async fn take_registration(&self,
+        email: &Email
+    ) -> Result<PasskeyRegistration, WebAuthnChallengeStoreError> {
+        self.registrations
+            .write()
+            .await
+            .remove(email)
+            .ok_or(WebAuthnChallengeStoreError::ChallengeNotFound)
+    }
+
+    async fn add_authentication(&self,
+        email: Email,
+        login_attempt_id: LoginAttemptId,
+        kind: PasskeyLogin,
+        state: PasskeyAuthentication
+    ) -> Result<(), WebAuthnChallengeStoreError> {
+        self.authentications.write().await.insert(login_attempt_id.as_ref().expose_secret().to_owned(), (email, kind, state));
+        Ok(())
+    }
+
+    async fn take_authentication(&self,
+        login_attempt_id: &LoginAttemptId
+    ) -> Result<(Email, PasskeyLogin, PasskeyAuthentication), WebAuthnChallengeStoreError> {
+        self.authentications
+            .write()
+            .await
+            .remove(login_attempt_id.as_ref().expose_secret())
+            .ok_or(WebAuthnChallengeStoreError::ChallengeNotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secrecy::Secret;
+    use url::Url;
+    use uuid::Uuid;
+    use webauthn_rs::WebauthnBuilder;
+
+    fn registration_state() -> PasskeyRegistration {
+        let origin = Url::parse("http://localhost:3000").unwrap();
+        let webauthn = WebauthnBuilder::new("localhost", &origin).unwrap().build().unwrap();
+
+        let (_, state) = webauthn
+            .start_passkey_registration(Uuid::new_v4(), "user.test@mail.com", "user.test@mail.com", None)
+            .unwrap();
+        state
+    }
+
+    #[tokio::test]
+    async fn test_take_registration() {
+        let store = HashmapWebAuthnChallengeStore::default();
+        let email = Email::parse(Secret::new("user.test@mail.com".to_string())).unwrap();
+
+        store.add_registration(email.clone(), registration_state()).await.unwrap();
+
+        assert!(store.take_registration(&email).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_take_registration_only_once() {
+        let store = HashmapWebAuthnChallengeStore::default();
+        let email = Email::parse(Secret::new("user.test@mail.com".to_string())).unwrap();
+
+        store.add_registration(email.clone(), registration_state()).await.unwrap();
+        store.take_registration(&email).await.unwrap();
+
+        let result = store.take_registration(&email).await;
+        assert_eq!(result.err(), Some(WebAuthnChallengeStoreError::ChallengeNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_take_authentication_not_found() {
+        let store = HashmapWebAuthnChallengeStore::default();
+
+        let result = store.take_authentication(&LoginAttemptId::default()).await;
+        assert_eq!(result.err(), Some(WebAuthnChallengeStoreError::ChallengeNotFound));
+    }
+}
@@ -1,23 +1,34 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use secrecy::{ExposeSecret, Secret};
+use tokio::sync::RwLock;
 
-use crate::domain::{BannedTokenStore, BannedTokenStoreError};
+use crate::domain::{BannedTokenStore, BannedTokenStoreError, Email};
 
 #[derive(Default, Debug)]
 pub struct HashsetBannedTokenStore {
-    banned_tokens: HashSet<String>
+    banned_tokens: RwLock<HashSet<String>>,
+    session_epochs: RwLock<HashMap<Email, u64>>
 }
 
 #[async_trait::async_trait]
 impl BannedTokenStore for HashsetBannedTokenStore {
-    async fn store_banned_token(&mut self, token: Secret<String>) -> Result<(), BannedTokenStoreError> {
-        self.banned_tokens.insert(token.expose_secret().to_owned());
+    async fn store_banned_token(&self, token: Secret<String>) -> Result<(), BannedTokenStoreError> {
+        self.banned_tokens.write().await.insert(token.expose_secret().to_owned());
         Ok(())
     }
 
     async fn check_banned_token(&self, token: Secret<String>) -> Result<bool, BannedTokenStoreError> {
-        Ok(self.banned_tokens.contains(token.expose_secret()))
+        Ok(self.banned_tokens.read().await.contains(token.expose_secret()))
+    }
+
+    async fn revoke_sessions(&self, email: &Email) -> Result<(), BannedTokenStoreError> {
+        *self.session_epochs.write().await.entry(email.clone()).or_default() += 1;
+        Ok(())
+    }
+
+    async fn session_epoch(&self, email: &Email) -> Result<u64, BannedTokenStoreError> {
+        Ok(self.session_epochs.read().await.get(email).copied().unwrap_or_default())
     }
 }
 
@@ -27,18 +38,18 @@ mod tests {
 
     #[tokio::test]
     async fn test_store_banned_token() {
-        let mut store = HashsetBannedTokenStore::default();
+        let store = HashsetBannedTokenStore::default();
 
         let test_token = Secret::new("thisewweeqeqweqwe321321343424324=-w".to_string());
 
-        let result = store.store_banned_token(test_token).await.unwrap();
+        let result = store.store_banned_token(test_token).await;
 
-        assert_eq!(result, ())
+        assert!(result.is_ok())
     }
 
     #[tokio::test]
     async fn test_check_banned_token_valid() {
-        let mut store = HashsetBannedTokenStore::default();
+        let store = HashsetBannedTokenStore::default();
 
         let test_token = Secret::new("thisewweeqeqweqwe321321343424324=-w".to_string());
 
@@ -46,7 +57,7 @@ mod tests {
 
         let result = store.check_banned_token(test_token.clone()).await.unwrap();
 
-        assert_eq!(result, true)
+        assert!(result)
     }
 
     #[tokio::test]
@@ -57,7 +68,18 @@ mod tests {
 
         let result = store.check_banned_token(test_token.clone()).await.unwrap();
 
-        assert_eq!(result, false)
+        assert!(!result)
     }
-}
 
+    #[tokio::test]
+    async fn test_revoke_sessions_bumps_the_epoch() {
+        let store = HashsetBannedTokenStore::default();
+        let email = Email::parse(Secret::new("user.test@mail.com".to_string())).unwrap();
+
+        assert_eq!(store.session_epoch(&email).await.unwrap(), 0);
+
+        store.revoke_sessions(&email).await.unwrap();
+
+        assert_eq!(store.session_epoch(&email).await.unwrap(), 1);
+    }
+}
@@ -0,0 +1,43 @@
+use std::collections::HashSet;
+
+use color_eyre::eyre::Result;
+use secrecy::{ExposeSecret, Secret};
+
+use crate::domain::BreachedPasswordChecker;
+
+/// Treats a fixed set of passwords as breached. Empty by default, which
+/// turns the check off.
+#[derive(Default, Debug)]
+pub struct HashsetBreachedPasswordChecker {
+    passwords: HashSet<String>,
+}
+
+impl HashsetBreachedPasswordChecker {
+    pub fn new<I, S>(passwords: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self { passwords: passwords.into_iter().map(Into::into).collect() }
+    }
+}
+
+#[async_trait::async_trait]
+impl BreachedPasswordChecker for HashsetBreachedPasswordChecker {
+    async fn breach_count(&self, password: &Secret<String>) -> Result<u64> {
+        Ok(self.passwords.contains(password.expose_secret()).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn only_listed_passwords_are_breached() {
+        let checker = HashsetBreachedPasswordChecker::new(["hunter2hunter2"]);
+
+        assert_eq!(checker.breach_count(&Secret::new("hunter2hunter2".to_owned())).await.unwrap(), 1);
+        assert_eq!(checker.breach_count(&Secret::new("Hunter2hunter2".to_owned())).await.unwrap(), 0);
+    }
+}
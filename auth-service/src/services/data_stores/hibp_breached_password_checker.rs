@@ -0,0 +1,135 @@
+use std::{io::ErrorKind, path::PathBuf};
+
+use color_eyre::eyre::{eyre, Context, Result};
+use secrecy::{ExposeSecret, Secret};
+use sha1::{Digest, Sha1};
+
+use crate::domain::BreachedPasswordChecker;
+
+/// Number of leading SHA-1 hex digits that name a range file.
+const PREFIX_LENGTH: usize = 5;
+
+/// Reads a local copy of the Have I Been Pwned password ranges, as written
+/// by the official downloader: one file per 5 hex digit SHA-1 prefix, named
+/// `21BD1.txt` or `21BD1`, with `SUFFIX:COUNT` lines. Only the range file for
+/// the password's prefix is read, so lookups stay small and nothing leaves
+/// the machine.
+pub struct HibpBreachedPasswordChecker {
+    dir: PathBuf,
+}
+
+impl HibpBreachedPasswordChecker {
+    pub fn new(dir: PathBuf) -> Result<Self> {
+        if !dir.is_dir() {
+            return Err(eyre!("breached password dataset {} is not a directory", dir.display()));
+        }
+
+        Ok(Self { dir })
+    }
+
+    async fn read_range(&self, prefix: &str) -> Result<Option<String>> {
+        for file_name in [format!("{}.txt", prefix), prefix.to_owned()] {
+            match tokio::fs::read_to_string(self.dir.join(&file_name)).await {
+                Ok(range) => return Ok(Some(range)),
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e).wrap_err_with(|| format!("failed to read range file {}", file_name)),
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+#[async_trait::async_trait]
+impl BreachedPasswordChecker for HibpBreachedPasswordChecker {
+    #[tracing::instrument(name = "Checking breached passwords", skip_all)]
+    async fn breach_count(&self, password: &Secret<String>) -> Result<u64> {
+        let hash = format!("{:X}", Sha1::digest(password.expose_secret().as_bytes()));
+        let (prefix, suffix) = hash.split_at(PREFIX_LENGTH);
+
+        // A partial dataset may leave out ranges; their passwords count as unseen.
+        let Some(range) = self.read_range(prefix).await? else {
+            return Ok(0);
+        };
+
+        for line in range.lines() {
+            let Some((line_suffix, count)) = line.trim().split_once(':') else {
+                continue;
+            };
+
+            if line_suffix.eq_ignore_ascii_case(suffix) {
+                return count.parse().wrap_err("malformed count in range file");
+            }
+        }
+
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Dataset(PathBuf);
+
+    impl Dataset {
+        fn new(files: &[(&str, &str)]) -> Self {
+            let dir = std::env::temp_dir().join(format!("hibp-{}", uuid::Uuid::new_v4()));
+            std::fs::create_dir(&dir).unwrap();
+            for (name, content) in files {
+                std::fs::write(dir.join(name), content).unwrap();
+            }
+            Self(dir)
+        }
+    }
+
+    impl Drop for Dataset {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    // SHA-1("password") is 5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8.
+    const PASSWORD_RANGE: &str = "1D2DA4053E34E76F6576ED1DA63134B5E2A:2\r\n\
+        1E4C9B93F3F0682250B6CF8331B7EE68FD8:9659365\r\n\
+        1F1F2C1E9C1C5F0B9E2A7A0D9D3C0B8A7A6:3\r\n";
+
+    fn password(password: &str) -> Secret<String> {
+        Secret::new(password.to_owned())
+    }
+
+    #[tokio::test]
+    async fn breached_password_is_counted() {
+        let dataset = Dataset::new(&[("5BAA6.txt", PASSWORD_RANGE)]);
+        let checker = HibpBreachedPasswordChecker::new(dataset.0.clone()).unwrap();
+
+        assert_eq!(checker.breach_count(&password("password")).await.unwrap(), 9659365);
+    }
+
+    #[tokio::test]
+    async fn range_files_without_extension_are_read() {
+        let dataset = Dataset::new(&[("5BAA6", PASSWORD_RANGE)]);
+        let checker = HibpBreachedPasswordChecker::new(dataset.0.clone()).unwrap();
+
+        assert_eq!(checker.breach_count(&password("password")).await.unwrap(), 9659365);
+    }
+
+    #[tokio::test]
+    async fn unseen_password_and_missing_range_count_zero() {
+        let dataset = Dataset::new(&[("5BAA6.txt", PASSWORD_RANGE)]);
+        let checker = HibpBreachedPasswordChecker::new(dataset.0.clone()).unwrap();
+
+        // Same range as "password", different suffix.
+        let same_range = (0..).map(|i| format!("unseen{}", i))
+            .find(|candidate| format!("{:X}", Sha1::digest(candidate.as_bytes())).starts_with("5BAA6"))
+            .unwrap();
+
+        assert_eq!(checker.breach_count(&password(&same_range)).await.unwrap(), 0);
+        assert_eq!(checker.breach_count(&password("correct horse battery staple")).await.unwrap(), 0);
+    }
+
+    #[test]
+    fn missing_directory_is_rejected() {
+        assert!(HibpBreachedPasswordChecker::new(std::env::temp_dir().join("does-not-exist-hibp")).is_err());
+    }
+}
@@ -1,16 +1,54 @@
 pub mod hashmap_user_store;
 pub mod hashset_banned_token;
+pub mod hashset_breached_password_checker;
 pub mod hashmap_two_fa_code_store;
+pub mod hashmap_passkey_store;
+pub mod hashmap_webauthn_challenge_store;
+pub mod hashmap_trusted_device_store;
+pub mod hashmap_email_outbox;
+pub mod hashmap_phone_verification_store;
+pub mod hashmap_password_reset_token_store;
 pub mod mock_email_client;
+pub mod mock_sms_client;
+pub mod hibp_breached_password_checker;
 pub mod postgres_user_store;
+pub mod postgres_audit_log;
+pub mod postgres_passkey_store;
+pub mod postmark_email_client;
+pub mod postgres_trusted_device_store;
+pub mod postgres_email_outbox;
 pub mod redis_banned_token_store;
+pub mod redis_phone_verification_store;
+pub mod redis_password_reset_token_store;
 pub mod redis_two_fa_code_store;
+pub mod redis_webauthn_challenge_store;
+pub mod smtp_email_client;
+pub mod twilio_sms_client;
 
 
 pub use hashmap_user_store::*;
 pub use hashset_banned_token::*;
+pub use hashset_breached_password_checker::*;
 pub use hashmap_two_fa_code_store::*;
+pub use hashmap_passkey_store::*;
+pub use hashmap_webauthn_challenge_store::*;
+pub use hashmap_trusted_device_store::*;
+pub use hashmap_email_outbox::*;
+pub use hashmap_phone_verification_store::*;
+pub use hashmap_password_reset_token_store::*;
 pub use mock_email_client::*;
+pub use mock_sms_client::*;
+pub use hibp_breached_password_checker::*;
 pub use postgres_user_store::*;
+pub use postgres_audit_log::*;
+pub use postgres_passkey_store::*;
+pub use postmark_email_client::*;
+pub use postgres_trusted_device_store::*;
+pub use postgres_email_outbox::*;
 pub use redis_banned_token_store::*;
-pub use redis_two_fa_code_store::*;
\ No newline at end of file
+pub use redis_phone_verification_store::*;
+pub use redis_password_reset_token_store::*;
+pub use redis_two_fa_code_store::*;
+pub use redis_webauthn_challenge_store::*;
+pub use smtp_email_client::*;
+pub use twilio_sms_client::*;
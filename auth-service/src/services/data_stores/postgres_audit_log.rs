@@ -0,0 +1,104 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::eyre;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+
+use crate::domain::{AuditEntry, AuditEvent, AuditLog, AuditLogError, Email, RequestContext};
+use crate::utils::metrics::StoreOperationTimer;
+
+/// The `audit_log` table rejects updates and deletes, so entries written
+/// here are permanent.
+pub struct PostgresAuditLog {
+    pool: PgPool,
+}
+
+impl PostgresAuditLog {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl AuditLog for PostgresAuditLog {
+    #[tracing::instrument(name = "Recording audit entry in PostgreSQL", skip_all)]
+    async fn record(&self, entry: AuditEntry) -> Result<(), AuditLogError> {
+        let _timer = StoreOperationTimer::new("postgres", "audit_log", "record");
+        sqlx::query!(
+            r#"
+            INSERT INTO audit_log (event, actor, subject, ip, user_agent, request_id, occurred_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+            entry.event.as_str(),
+            entry.actor,
+            entry.subject.as_ref().map(|subject| subject.as_ref().expose_secret().to_owned()),
+            entry.context.ip,
+            entry.context.user_agent,
+            entry.context.request_id,
+            entry.occurred_at
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| AuditLogError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Getting recent audit entries from PostgreSQL", skip_all)]
+    async fn recent_entries(&self, subject: &Email, limit: u64) -> Result<Vec<AuditEntry>, AuditLogError> {
+        let _timer = StoreOperationTimer::new("postgres", "audit_log", "recent_entries");
+        let limit = i64::try_from(limit).map_err(|e| AuditLogError::UnexpectedError(e.into()))?;
+
+        sqlx::query_as!(
+            AuditRow,
+            r#"
+            SELECT event, actor, subject, ip, user_agent, request_id, occurred_at
+            FROM audit_log
+            WHERE subject = $1
+            ORDER BY id DESC
+            LIMIT $2
+            "#,
+            subject.as_ref().expose_secret(),
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| AuditLogError::UnexpectedError(e.into()))?
+        .into_iter()
+        .map(AuditEntry::try_from)
+        .collect()
+    }
+}
+
+struct AuditRow {
+    event: String,
+    actor: Option<String>,
+    subject: Option<String>,
+    ip: Option<String>,
+    user_agent: Option<String>,
+    request_id: Option<String>,
+    occurred_at: DateTime<Utc>,
+}
+
+impl TryFrom<AuditRow> for AuditEntry {
+    type Error = AuditLogError;
+
+    fn try_from(row: AuditRow) -> Result<Self, Self::Error> {
+        Ok(AuditEntry {
+            event: AuditEvent::parse(&row.event)
+                .ok_or_else(|| AuditLogError::UnexpectedError(eyre!("unknown audit event")))?,
+            actor: row.actor,
+            subject: row.subject
+                .map(|subject| Email::parse(Secret::new(subject)))
+                .transpose()
+                .map_err(AuditLogError::UnexpectedError)?,
+            context: RequestContext {
+                ip: row.ip,
+                user_agent: row.user_agent,
+                request_id: row.request_id,
+            },
+            occurred_at: row.occurred_at,
+        })
+    }
+}
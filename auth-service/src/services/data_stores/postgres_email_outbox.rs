@@ -0,0 +1,184 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::eyre;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::{Email, EmailOutbox, EmailOutboxError, EmailOutboxStats, OutboxEmail, RenderedEmail};
+use crate::utils::metrics::StoreOperationTimer;
+
+pub struct PostgresEmailOutbox {
+    pool: PgPool,
+}
+
+impl PostgresEmailOutbox {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailOutbox for PostgresEmailOutbox {
+    #[tracing::instrument(name = "Adding email to PostgreSQL outbox", skip_all)]
+    async fn enqueue(&self, email: OutboxEmail) -> Result<(), EmailOutboxError> {
+        let _timer = StoreOperationTimer::new("postgres", "email_outbox", "enqueue");
+        sqlx::query!(
+            r#"
+            INSERT INTO email_outbox (id, recipient, subject, html_body, text_body, deliver_by)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+            email.id,
+            email.recipient.as_ref().expose_secret(),
+            email.email.subject,
+            email.email.html_body,
+            email.email.text_body,
+            email.deliver_by
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| EmailOutboxError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Claiming due emails from PostgreSQL outbox", skip_all)]
+    async fn claim_due(&self, limit: u32, lease_until: DateTime<Utc>) -> Result<Vec<OutboxEmail>, EmailOutboxError> {
+        let _timer = StoreOperationTimer::new("postgres", "email_outbox", "claim_due");
+        // SKIP LOCKED lets several workers drain the outbox without
+        // claiming the same email twice.
+        let rows = sqlx::query!(
+            r#"
+            UPDATE email_outbox
+            SET attempts = attempts + 1, next_attempt_at = $2
+            WHERE id IN (
+                SELECT id
+                FROM email_outbox
+                WHERE status = 'pending' AND next_attempt_at <= NOW()
+                ORDER BY next_attempt_at
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, recipient, subject, html_body AS "html_body!", text_body AS "text_body!", attempts, deliver_by
+            "#,
+            i64::from(limit),
+            lease_until
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| EmailOutboxError::UnexpectedError(e.into()))?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(OutboxEmail {
+                    id: row.id,
+                    recipient: Email::parse(Secret::new(row.recipient))
+                        .map_err(EmailOutboxError::UnexpectedError)?,
+                    email: RenderedEmail {
+                        subject: row.subject,
+                        html_body: row.html_body,
+                        text_body: row.text_body,
+                    },
+                    attempts: u32::try_from(row.attempts)
+                        .map_err(|e| EmailOutboxError::UnexpectedError(e.into()))?,
+                    deliver_by: row.deliver_by,
+                })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(name = "Removing sent email from PostgreSQL outbox", skip_all)]
+    async fn mark_sent(&self, id: Uuid) -> Result<(), EmailOutboxError> {
+        let _timer = StoreOperationTimer::new("postgres", "email_outbox", "mark_sent");
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM email_outbox
+            WHERE id = $1
+            "#,
+            id
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| EmailOutboxError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(EmailOutboxError::EmailNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Rescheduling email in PostgreSQL outbox", skip_all)]
+    async fn retry_later(&self, id: Uuid, error: &str, retry_at: DateTime<Utc>) -> Result<(), EmailOutboxError> {
+        let _timer = StoreOperationTimer::new("postgres", "email_outbox", "retry_later");
+        let result = sqlx::query!(
+            r#"
+            UPDATE email_outbox
+            SET next_attempt_at = $2, last_error = $3
+            WHERE id = $1 AND status = 'pending'
+            "#,
+            id,
+            retry_at,
+            error
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| EmailOutboxError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(EmailOutboxError::EmailNotFound);
+        }
+
+        Ok(())
+    }
+
+    /// Drops the bodies, which may hold 2FA codes or reset links, and keeps
+    /// the metadata for inspection.
+    #[tracing::instrument(name = "Dead-lettering email in PostgreSQL outbox", skip_all)]
+    async fn dead_letter(&self, id: Uuid, error: &str) -> Result<(), EmailOutboxError> {
+        let _timer = StoreOperationTimer::new("postgres", "email_outbox", "dead_letter");
+        let result = sqlx::query!(
+            r#"
+            UPDATE email_outbox
+            SET status = 'dead', last_error = $2, html_body = NULL, text_body = NULL
+            WHERE id = $1
+            "#,
+            id,
+            error
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| EmailOutboxError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(EmailOutboxError::EmailNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Counting emails in PostgreSQL outbox", skip_all)]
+    async fn stats(&self) -> Result<EmailOutboxStats, EmailOutboxError> {
+        let _timer = StoreOperationTimer::new("postgres", "email_outbox", "stats");
+        let row = sqlx::query!(
+            r#"
+            SELECT
+                COUNT(*) FILTER (WHERE status = 'pending') AS "pending!",
+                COUNT(*) FILTER (WHERE status = 'dead') AS "dead!"
+            FROM email_outbox
+            "#
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| EmailOutboxError::UnexpectedError(e.into()))?;
+
+        let count = |value: i64| u64::try_from(value)
+            .map_err(|_| EmailOutboxError::UnexpectedError(eyre!("negative email count")));
+
+        Ok(EmailOutboxStats {
+            pending: count(row.pending)?,
+            dead: count(row.dead)?,
+        })
+    }
+}
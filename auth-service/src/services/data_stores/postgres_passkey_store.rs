@@ -0,0 +1,117 @@
+use color_eyre::eyre::Context;
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+use webauthn_rs::prelude::Passkey;
+
+use crate::domain::{Email, PasskeyStore, PasskeyStoreError};
+use crate::utils::metrics::StoreOperationTimer;
+
+pub struct PostgresPasskeyStore {
+    pool: PgPool,
+}
+
+impl PostgresPasskeyStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl PasskeyStore for PostgresPasskeyStore {
+    #[tracing::instrument(name = "Adding passkey to PostgreSQL", skip_all)]
+    async fn add_passkey(&self, email: Email, passkey: Passkey) -> Result<(), PasskeyStoreError> {
+        let _timer = StoreOperationTimer::new("postgres", "passkeys", "add_passkey");
+        let serialized_passkey = serde_json::to_value(&passkey)
+            .wrap_err("failed to serialize passkey")
+            .map_err(PasskeyStoreError::UnexpectedError)?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO passkeys (credential_id, email, passkey)
+            VALUES ($1, $2, $3)
+            "#,
+            passkey.cred_id().as_ref(),
+            email.as_ref().expose_secret(),
+            serialized_passkey
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| match e.as_database_error() {
+            Some(db_error) if db_error.is_unique_violation() => PasskeyStoreError::PasskeyAlreadyExists,
+            _ => PasskeyStoreError::UnexpectedError(e.into()),
+        })?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving passkeys from PostgreSQL", skip_all)]
+    async fn get_passkeys(&self, email: &Email) -> Result<Vec<Passkey>, PasskeyStoreError> {
+        let _timer = StoreOperationTimer::new("postgres", "passkeys", "get_passkeys");
+        sqlx::query!(
+            r#"
+            SELECT passkey
+            FROM passkeys
+            WHERE email = $1
+            ORDER BY created_at
+            "#,
+            email.as_ref().expose_secret()
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| PasskeyStoreError::UnexpectedError(e.into()))?
+        .into_iter()
+        .map(|row| {
+            serde_json::from_value(row.passkey)
+                .wrap_err("failed to deserialize passkey")
+                .map_err(PasskeyStoreError::UnexpectedError)
+        })
+        .collect()
+    }
+
+    #[tracing::instrument(name = "Updating passkey in PostgreSQL", skip_all)]
+    async fn update_passkey(&self, email: &Email, passkey: Passkey) -> Result<(), PasskeyStoreError> {
+        let _timer = StoreOperationTimer::new("postgres", "passkeys", "update_passkey");
+        let serialized_passkey = serde_json::to_value(&passkey)
+            .wrap_err("failed to serialize passkey")
+            .map_err(PasskeyStoreError::UnexpectedError)?;
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE passkeys
+            SET passkey = $1
+            WHERE credential_id = $2 AND email = $3
+            "#,
+            serialized_passkey,
+            passkey.cred_id().as_ref(),
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| PasskeyStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(PasskeyStoreError::PasskeyNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Removing passkeys from PostgreSQL", skip_all)]
+    async fn remove_passkeys(&self, email: &Email) -> Result<(), PasskeyStoreError> {
+        let _timer = StoreOperationTimer::new("postgres", "passkeys", "remove_passkeys");
+        sqlx::query!(
+            r#"
+            DELETE FROM passkeys
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| PasskeyStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+}
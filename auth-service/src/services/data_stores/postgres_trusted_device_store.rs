@@ -0,0 +1,124 @@
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::{Email, TrustedDevice, TrustedDeviceStore, TrustedDeviceStoreError};
+use crate::utils::metrics::StoreOperationTimer;
+
+pub struct PostgresTrustedDeviceStore {
+    pool: PgPool,
+}
+
+impl PostgresTrustedDeviceStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl TrustedDeviceStore for PostgresTrustedDeviceStore {
+    #[tracing::instrument(name = "Adding trusted device to PostgreSQL", skip_all)]
+    async fn add_device(&self, email: Email, device: TrustedDevice) -> Result<(), TrustedDeviceStoreError> {
+        let _timer = StoreOperationTimer::new("postgres", "trusted_devices", "add_device");
+        sqlx::query!(
+            r#"
+            INSERT INTO trusted_devices (id, email, user_agent, created_at, expires_at)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            device.id,
+            email.as_ref().expose_secret(),
+            device.user_agent,
+            device.created_at,
+            device.expires_at
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| TrustedDeviceStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving trusted devices from PostgreSQL", skip_all)]
+    async fn get_devices(&self, email: &Email) -> Result<Vec<TrustedDevice>, TrustedDeviceStoreError> {
+        let _timer = StoreOperationTimer::new("postgres", "trusted_devices", "get_devices");
+        let devices = sqlx::query_as!(
+            TrustedDevice,
+            r#"
+            SELECT id, user_agent, created_at, expires_at
+            FROM trusted_devices
+            WHERE email = $1 AND revoked_at IS NULL AND expires_at > NOW()
+            ORDER BY created_at DESC
+            "#,
+            email.as_ref().expose_secret()
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| TrustedDeviceStoreError::UnexpectedError(e.into()))?;
+
+        Ok(devices)
+    }
+
+    #[tracing::instrument(name = "Checking trusted device in PostgreSQL", skip_all)]
+    async fn is_trusted(&self, email: &Email, device_id: Uuid) -> Result<bool, TrustedDeviceStoreError> {
+        let _timer = StoreOperationTimer::new("postgres", "trusted_devices", "is_trusted");
+        let trusted = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(
+                SELECT 1
+                FROM trusted_devices
+                WHERE id = $1 AND email = $2 AND revoked_at IS NULL AND expires_at > NOW()
+            ) AS "trusted!"
+            "#,
+            device_id,
+            email.as_ref().expose_secret()
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| TrustedDeviceStoreError::UnexpectedError(e.into()))?;
+
+        Ok(trusted)
+    }
+
+    #[tracing::instrument(name = "Revoking trusted device in PostgreSQL", skip_all)]
+    async fn revoke_device(&self, email: &Email, device_id: Uuid) -> Result<(), TrustedDeviceStoreError> {
+        let _timer = StoreOperationTimer::new("postgres", "trusted_devices", "revoke_device");
+        let result = sqlx::query!(
+            r#"
+            UPDATE trusted_devices
+            SET revoked_at = NOW()
+            WHERE id = $1 AND email = $2 AND revoked_at IS NULL
+            "#,
+            device_id,
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| TrustedDeviceStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(TrustedDeviceStoreError::TrustedDeviceNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Revoking all trusted devices in PostgreSQL", skip_all)]
+    async fn revoke_all_devices(&self, email: &Email) -> Result<(), TrustedDeviceStoreError> {
+        let _timer = StoreOperationTimer::new("postgres", "trusted_devices", "revoke_all_devices");
+        sqlx::query!(
+            r#"
+            UPDATE trusted_devices
+            SET revoked_at = NOW()
+            WHERE email = $1 AND revoked_at IS NULL
+            "#,
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| TrustedDeviceStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+}
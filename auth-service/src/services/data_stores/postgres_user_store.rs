@@ -1,58 +1,124 @@
-use std::error::Error;
-
-use argon2::{
-    password_hash::SaltString, Algorithm, Argon2,
-    Params, PasswordHash, PasswordHasher, PasswordVerifier,
-    Version
-};
 use secrecy::{ExposeSecret, Secret};
 
-use color_eyre::eyre::{Context, Result};
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Result};
 use sqlx::PgPool;
 
 use crate::domain::{
     data_stores::{UserStore, UserStoreError},
-    Email, Password, User,
+    Access, AccountStatus, Email, Locale, Password, PhoneNumber, TwoFAChannel, User, UserPage,
 };
+use crate::services::PasswordHasherRegistry;
+use crate::utils::metrics::StoreOperationTimer;
 
 pub struct PostgresUserStore {
     pool: PgPool,
+    hasher: PasswordHasherRegistry,
+    password_history_size: usize,
 }
 
 impl PostgresUserStore {
-    pub fn new(pool: PgPool) -> Self {
+    pub fn new(pool: PgPool, hasher: PasswordHasherRegistry) -> Self {
         Self {
-            pool
+            pool,
+            hasher,
+            password_history_size: 0
         }
-    } 
+    }
+
+    /// Rejects password changes that reuse any of the user's last `size`
+    /// passwords, counting the current one. 0 allows any password.
+    pub fn with_password_history(mut self, size: usize) -> Self {
+        self.password_history_size = size;
+        self
+    }
+
+    /// Inserts a user migrated from another system with the password hash it
+    /// had there. The hash is upgraded to Argon2id on their first login.
+    #[tracing::instrument(name = "Importing user into PostgreSQL", skip_all)]
+    pub async fn import_user(&self, email: &Email, password_hash: &Secret<String>, requires2fa: bool,
+        locale: Option<Locale>) -> Result<(), UserStoreError> {
+        let _timer = StoreOperationTimer::new("postgres", "users", "import_user");
+
+        if !self.hasher.supports(password_hash) {
+            return Err(UserStoreError::UnexpectedError(eyre!("unsupported password hash format")));
+        }
+
+        sqlx::query!(
+            r#"
+            INSERT INTO users (email, password_hash, requires_2fa, locale)
+            VALUES ($1, $2, $3, $4)
+            "#, email.as_ref().expose_secret(), password_hash.expose_secret(), requires2fa,
+            locale.map(|locale| locale.code())
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| match e.as_database_error() {
+            Some(db_error) if db_error.is_unique_violation() => UserStoreError::UserAlreadyExists,
+            _ => UserStoreError::UnexpectedError(e.into()),
+        })?;
+
+        Ok(())
+    }
+
+    /// Replaces a legacy or outdated hash with a current one. The old hash is
+    /// part of the condition so a password changed in the meantime is left
+    /// alone.
+    #[tracing::instrument(name = "Upgrading password hash in PostgreSQL", skip_all)]
+    async fn rehash_password(&self, email: &Email, old_password_hash: &Secret<String>, password: Password) -> Result<()> {
+        let password_hash = self.hasher.hash(password.as_ref().to_owned()).await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET password_hash = $3
+            WHERE email = $1 AND password_hash = $2
+            "#,
+            email.as_ref().expose_secret(),
+            old_password_hash.expose_secret(),
+            password_hash.expose_secret()
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
 }
 
 #[async_trait::async_trait]
 impl UserStore for PostgresUserStore {
     #[tracing::instrument(name= "Adding user to PostgreSQL", skip_all)]
-    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError> {
+    async fn add_user(&self, user: User) -> Result<(), UserStoreError> {
+        let _timer = StoreOperationTimer::new("postgres", "users", "add_user");
         let pass = user.password.clone();
 
-        let password_hash = compute_password_hash(pass.as_ref().to_owned()).await.map_err(UserStoreError::UnexpectedError)?;
+        let password_hash = self.hasher.hash(pass.as_ref().to_owned()).await.map_err(UserStoreError::UnexpectedError)?;
 
         sqlx::query!(
             r#"
-            INSERT INTO users (email, password_hash, requires_2fa)
-            VALUES ($1, $2, $3)
-            "#, user.email.as_ref().expose_secret(), &password_hash.expose_secret(), user.requires2fa
+            INSERT INTO users (email, password_hash, requires_2fa, locale)
+            VALUES ($1, $2, $3, $4)
+            "#, user.email.as_ref().expose_secret(), &password_hash.expose_secret(), user.requires2fa,
+            user.locale.map(|locale| locale.code())
         )
         .execute(&self.pool)
         .await
-        .map_err(|e|UserStoreError::UnexpectedError(e.into()))?;
+        .map_err(|e| match e.as_database_error() {
+            Some(db_error) if db_error.is_unique_violation() => UserStoreError::UserAlreadyExists,
+            _ => UserStoreError::UnexpectedError(e.into()),
+        })?;
 
         Ok(())
     }
 
     #[tracing::instrument(name = "Retrieving user from PostgreSQL", skip_all)]
     async fn get_user(&self, email: Email) -> Result<User, UserStoreError> {
-        sqlx::query!(
+        let _timer = StoreOperationTimer::new("postgres", "users", "get_user");
+        sqlx::query_as!(
+            UserRow,
             r#"
-            SELECT email, password_hash, requires_2fa
+            SELECT email, password_hash, requires_2fa, locale, phone_number, two_fa_channel, status, status_reason, status_changed_at,
+                password_reset_required
             FROM users
             WHERE email = $1
             "#,
@@ -61,75 +127,422 @@ impl UserStore for PostgresUserStore {
         .fetch_optional(&self.pool)
         .await
         .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
-        .map(|row| {
-            Ok(User {
-                email: Email::parse(Secret::new(row.email))
-                    .map_err(UserStoreError::UnexpectedError)?,
-                password: Password::parse(Secret::new(row.password_hash))
-                    .map_err(UserStoreError::UnexpectedError)?,
-                requires2fa: row.requires_2fa,
-            })
-        })
+        .map(User::try_from)
         .ok_or(UserStoreError::UserNotFound)?
     }
 
 
     #[tracing::instrument(name= "Validating user credentials in PostgreSQL", skip_all)]
     async fn validate_user(&self, email: Email, password: Password) -> Result<(), UserStoreError> {
-        let valid_user = self.get_user(email).await?;
+        let _timer = StoreOperationTimer::new("postgres", "users", "validate_user");
+        let valid_user = self.get_user(email.clone()).await?;
+        let password_hash = valid_user.password.as_ref().to_owned();
+
+        self.hasher.verify(password_hash.clone(), password.as_ref().to_owned())
+            .await
+            .map_err(|_| UserStoreError::InvalidCredentials)?;
+
+        // The login already succeeded, so a failed upgrade is only logged.
+        if self.hasher.needs_rehash(&password_hash) {
+            if let Err(e) = self.rehash_password(&email, &password_hash, password).await {
+                tracing::warn!(error = ?e, "failed to upgrade password hash");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks the new password against the current hash and the stored
+    /// history while holding the user's row, so two concurrent changes cannot
+    /// both skip the check. The replaced hash joins the history, which is then
+    /// pruned to the configured size.
+    #[tracing::instrument(name = "Updating password in PostgreSQL", skip_all)]
+    async fn update_password(&self, email: &Email, password: Password) -> Result<(), UserStoreError> {
+        let _timer = StoreOperationTimer::new("postgres", "users", "update_password");
+        let kept_history = self.password_history_size.saturating_sub(1) as i64;
+
+        let password_hash = self.hasher.hash(password.as_ref().to_owned()).await.map_err(UserStoreError::UnexpectedError)?;
+
+        let mut transaction = self.pool.begin().await.map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
 
-        verify_password_hash(
-            valid_user.password.as_ref().to_owned(),
-            password.as_ref().to_owned())
+        let current_hash = sqlx::query_scalar!(
+            r#"
+            SELECT password_hash
+            FROM users
+            WHERE email = $1
+            FOR UPDATE
+            "#,
+            email.as_ref().expose_secret()
+        )
+        .fetch_optional(&mut *transaction)
         .await
-        .map_err(|_| UserStoreError::InvalidCredentials)
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .ok_or(UserStoreError::UserNotFound)?;
+
+        if self.password_history_size > 0 {
+            let previous_hashes = sqlx::query_scalar!(
+                r#"
+                SELECT password_hash
+                FROM password_history
+                WHERE email = $1
+                ORDER BY id DESC
+                LIMIT $2
+                "#,
+                email.as_ref().expose_secret(),
+                kept_history
+            )
+            .fetch_all(&mut *transaction)
+            .await
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+            for recent_hash in std::iter::once(current_hash.clone()).chain(previous_hashes) {
+                if self.hasher.verify(Secret::new(recent_hash), password.as_ref().to_owned()).await.is_ok() {
+                    return Err(UserStoreError::PasswordReused);
+                }
+            }
+        }
+
+        sqlx::query!(
+            r#"
+            INSERT INTO password_history (email, password_hash)
+            VALUES ($1, $2)
+            "#,
+            email.as_ref().expose_secret(),
+            current_hash
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM password_history
+            WHERE email = $1 AND id NOT IN (
+                SELECT id
+                FROM password_history
+                WHERE email = $1
+                ORDER BY id DESC
+                LIMIT $2
+            )
+            "#,
+            email.as_ref().expose_secret(),
+            kept_history
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET password_hash = $2, password_reset_required = FALSE
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret(),
+            password_hash.expose_secret()
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        transaction.commit().await.map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
     }
-}
 
-#[tracing::instrument(name= "Verify password hash", skip_all)]
-async fn verify_password_hash(
-    expected_password_hash: Secret<String>,
-    password_candidate: Secret<String>,
-) -> Result<()> {
+    #[tracing::instrument(name = "Setting phone number in PostgreSQL", skip_all)]
+    async fn set_phone_number(&self, email: &Email, phone_number: PhoneNumber) -> Result<(), UserStoreError> {
+        let _timer = StoreOperationTimer::new("postgres", "users", "set_phone_number");
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET phone_number = $2
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret(),
+            phone_number.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
 
-    let current_span: tracing::Span = tracing::Span::current();
+        Ok(())
+    }
 
-    let result = tokio::task::spawn_blocking(move || {
+    #[tracing::instrument(name = "Setting 2FA channel in PostgreSQL", skip_all)]
+    async fn set_two_fa_channel(&self, email: &Email, channel: TwoFAChannel) -> Result<(), UserStoreError> {
+        let _timer = StoreOperationTimer::new("postgres", "users", "set_two_fa_channel");
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET two_fa_channel = $2
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret(),
+            channel.as_str()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
 
-        current_span.in_scope(|| {
-            let expected_password_hash: PasswordHash<'_> = PasswordHash::new(expected_password_hash.expose_secret())?;
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
 
-            Argon2::default()
-                .verify_password(password_candidate.expose_secret().as_bytes(), &expected_password_hash)
-                .wrap_err("failed to verify password hash")
-        })
-        
-    })
-    .await;
+        Ok(())
+    }
 
-    result?
-    
+    #[tracing::instrument(name = "Retrieving user access from PostgreSQL", skip_all)]
+    async fn get_access(&self, email: &Email) -> Result<Access, UserStoreError> {
+        let _timer = StoreOperationTimer::new("postgres", "users", "get_access");
+        let roles = sqlx::query_scalar!(
+            r#"
+            SELECT role
+            FROM user_roles
+            WHERE email = $1
+            ORDER BY role
+            "#,
+            email.as_ref().expose_secret()
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        let permissions = sqlx::query_scalar!(
+            r#"
+            SELECT DISTINCT role_permissions.permission
+            FROM user_roles
+            JOIN role_permissions ON role_permissions.role = user_roles.role
+            WHERE user_roles.email = $1
+            ORDER BY role_permissions.permission
+            "#,
+            email.as_ref().expose_secret()
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        Ok(Access { roles, permissions })
+    }
+
+    #[tracing::instrument(name = "Assigning role in PostgreSQL", skip_all)]
+    async fn assign_role(&self, email: &Email, role: &str) -> Result<(), UserStoreError> {
+        let _timer = StoreOperationTimer::new("postgres", "users", "assign_role");
+        sqlx::query!(
+            r#"
+            INSERT INTO user_roles (email, role)
+            VALUES ($1, $2)
+            ON CONFLICT DO NOTHING
+            "#,
+            email.as_ref().expose_secret(),
+            role
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| match e.as_database_error() {
+            Some(db_error) if db_error.is_foreign_key_violation() => match db_error.constraint() {
+                Some("user_roles_role_fkey") => UserStoreError::RoleNotFound,
+                _ => UserStoreError::UserNotFound,
+            },
+            _ => UserStoreError::UnexpectedError(e.into()),
+        })?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Removing role in PostgreSQL", skip_all)]
+    async fn remove_role(&self, email: &Email, role: &str) -> Result<(), UserStoreError> {
+        let _timer = StoreOperationTimer::new("postgres", "users", "remove_role");
+        sqlx::query!(
+            r#"
+            DELETE FROM user_roles
+            WHERE email = $1 AND role = $2
+            "#,
+            email.as_ref().expose_secret(),
+            role
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Listing users from PostgreSQL", skip_all)]
+    async fn list_users(&self, search: Option<&str>, offset: u64, limit: u64) -> Result<UserPage, UserStoreError> {
+        let _timer = StoreOperationTimer::new("postgres", "users", "list_users");
+        // Wildcards typed by the operator are matched literally.
+        let pattern = search.map(|search| format!("%{}%", search
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")));
+        let offset = i64::try_from(offset).map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+        let limit = i64::try_from(limit).map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        let total = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "count!"
+            FROM users
+            WHERE $1::TEXT IS NULL OR email ILIKE $1
+            "#,
+            pattern
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        let users = sqlx::query_as!(
+            UserRow,
+            r#"
+            SELECT email, password_hash, requires_2fa, locale, phone_number, two_fa_channel, status, status_reason, status_changed_at,
+                password_reset_required
+            FROM users
+            WHERE $1::TEXT IS NULL OR email ILIKE $1
+            ORDER BY email
+            LIMIT $2 OFFSET $3
+            "#,
+            pattern,
+            limit,
+            offset
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .into_iter()
+        .map(User::try_from)
+        .collect::<Result<_, _>>()?;
+
+        Ok(UserPage { users, total: total as u64 })
+    }
+
+    #[tracing::instrument(name = "Setting account status in PostgreSQL", skip_all)]
+    async fn set_status(&self, email: &Email, status: AccountStatus, reason: Option<String>) -> Result<(), UserStoreError> {
+        let _timer = StoreOperationTimer::new("postgres", "users", "set_status");
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET status = $2, status_reason = $3, status_changed_at = NOW()
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret(),
+            status.as_str(),
+            reason
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Getting account status from PostgreSQL", skip_all)]
+    async fn get_status(&self, email: &Email) -> Result<AccountStatus, UserStoreError> {
+        let _timer = StoreOperationTimer::new("postgres", "users", "get_status");
+        let status = sqlx::query_scalar!(
+            r#"
+            SELECT status
+            FROM users
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .ok_or(UserStoreError::UserNotFound)?;
+
+        AccountStatus::parse(&status)
+            .ok_or_else(|| UserStoreError::UnexpectedError(eyre!("unknown account status")))
+    }
+
+    #[tracing::instrument(name = "Requiring password reset in PostgreSQL", skip_all)]
+    async fn require_password_reset(&self, email: &Email) -> Result<(), UserStoreError> {
+        let _timer = StoreOperationTimer::new("postgres", "users", "require_password_reset");
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET password_reset_required = TRUE
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Resetting 2FA in PostgreSQL", skip_all)]
+    async fn reset_two_fa(&self, email: &Email) -> Result<(), UserStoreError> {
+        let _timer = StoreOperationTimer::new("postgres", "users", "reset_two_fa");
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET phone_number = NULL, two_fa_channel = 'email'
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
 }
 
-#[tracing::instrument(name= "Computing password hash", skip_all)]
-async fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>> {
-    let current_span: tracing::Span = tracing::Span::current();
+struct UserRow {
+    email: String,
+    password_hash: String,
+    requires_2fa: bool,
+    locale: Option<String>,
+    phone_number: Option<String>,
+    two_fa_channel: String,
+    status: String,
+    status_reason: Option<String>,
+    status_changed_at: Option<DateTime<Utc>>,
+    password_reset_required: bool,
+}
 
-    let result = tokio::task::spawn_blocking(move || {
-        current_span.in_scope(|| {
-            let salt = SaltString::generate(&mut rand::thread_rng());
+impl TryFrom<UserRow> for User {
+    type Error = UserStoreError;
 
-            let password_hash = Argon2::new(
-                Algorithm::Argon2id,
-                Version::V0x13,
-                Params::new(15000, 2, 1, None)?
-            )
-            .hash_password(password.expose_secret().as_bytes(), &salt)?
-            .to_string();
-            Ok(Secret::new(password_hash))
+    fn try_from(row: UserRow) -> Result<Self, Self::Error> {
+        Ok(User {
+            email: Email::parse(Secret::new(row.email))
+                .map_err(UserStoreError::UnexpectedError)?,
+            password: Password::parse(Secret::new(row.password_hash))
+                .map_err(UserStoreError::UnexpectedError)?,
+            requires2fa: row.requires_2fa,
+            locale: row.locale.as_deref().and_then(Locale::parse),
+            phone_number: row.phone_number
+                .map(|phone_number| PhoneNumber::parse(Secret::new(phone_number)))
+                .transpose()
+                .map_err(UserStoreError::UnexpectedError)?,
+            two_fa_channel: TwoFAChannel::parse(&row.two_fa_channel)
+                .ok_or_else(|| UserStoreError::UnexpectedError(eyre!("unknown 2FA channel")))?,
+            status: AccountStatus::parse(&row.status)
+                .ok_or_else(|| UserStoreError::UnexpectedError(eyre!("unknown account status")))?,
+            status_reason: row.status_reason,
+            status_changed_at: row.status_changed_at,
+            password_reset_required: row.password_reset_required,
         })
-    }).await;
-
-    result?
-    
+    }
 }
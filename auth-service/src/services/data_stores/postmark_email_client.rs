@@ -0,0 +1,348 @@
+use std::time::Duration;
+
+use color_eyre::eyre::{eyre, Context, Result};
+use rand::Rng;
+use reqwest::{Client, StatusCode, Url};
+use secrecy::{ExposeSecret, Secret};
+use serde::Serialize;
+
+use crate::{domain::{Email, EmailClient, RenderedEmail}, utils::redact::redact};
+
+pub struct PostmarkSettings {
+    pub base_url: String,
+    pub sender: Email,
+    pub authorization_token: Secret<String>,
+    pub timeout: Duration,
+    pub max_retries: u32,
+    /// Delay before the first retry. Each further retry doubles it and adds
+    /// up to the same amount again as jitter.
+    pub retry_base_delay: Duration,
+}
+
+/// Sends email through a Postmark style transactional email HTTP API.
+pub struct PostmarkEmailClient {
+    http_client: Client,
+    base_url: Url,
+    sender: Email,
+    authorization_token: Secret<String>,
+    max_retries: u32,
+    retry_base_delay: Duration,
+}
+
+impl PostmarkEmailClient {
+    pub fn new(settings: PostmarkSettings) -> Result<Self> {
+        let mut base_url = Url::parse(&settings.base_url).wrap_err("failed to parse email provider base url")?;
+        // Without a trailing slash `join` would replace the last segment of
+        // a base url such as `https://api.example.com/v1`.
+        if !base_url.path().ends_with('/') {
+            base_url.set_path(&format!("{}/", base_url.path()));
+        }
+
+        let http_client = Client::builder()
+            .timeout(settings.timeout)
+            .build()
+            .wrap_err("failed to build email provider http client")?;
+
+        Ok(Self {
+            http_client,
+            base_url,
+            sender: settings.sender,
+            authorization_token: settings.authorization_token,
+            max_retries: settings.max_retries,
+            retry_base_delay: settings.retry_base_delay,
+        })
+    }
+
+    fn retry_delay(&self, attempt: u32) -> Duration {
+        let delay = self.retry_base_delay.saturating_mul(2u32.saturating_pow(attempt));
+        let jitter_ms = rand::thread_rng().gen_range(0..=self.retry_base_delay.as_millis() as u64);
+
+        delay + Duration::from_millis(jitter_ms)
+    }
+
+    async fn send(&self, request_body: SendEmailRequest<'_>) -> Result<()> {
+        let url = self.base_url.join("email").wrap_err("failed to build email provider url")?;
+
+        let mut attempt = 0;
+        loop {
+            let result = self.http_client
+                .post(url.clone())
+                .header("X-Postmark-Server-Token", self.authorization_token.expose_secret())
+                .json(&request_body)
+                .send()
+                .await;
+
+            let error = match result {
+                Ok(response) if response.status().is_success() => return Ok(()),
+                Ok(response) => {
+                    let status = response.status();
+                    let body = response.text().await.unwrap_or_default();
+                    let error = eyre!("email provider responded with {}: {}", status, redact(&body, request_body.to));
+
+                    if !is_retryable(status) {
+                        return Err(error);
+                    }
+                    error
+                }
+                Err(e) => {
+                    if !(e.is_timeout() || e.is_connect()) {
+                        return Err(e).wrap_err("failed to send request to email provider");
+                    }
+                    eyre!(e).wrap_err("failed to reach email provider")
+                }
+            };
+
+            if attempt >= self.max_retries {
+                return Err(error).wrap_err(format!("giving up after {} attempts", attempt + 1));
+            }
+
+            let delay = self.retry_delay(attempt);
+            tracing::warn!(attempt, ?delay, error = %error, "retrying email delivery");
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailClient for PostmarkEmailClient {
+    #[tracing::instrument(name = "Sending email through HTTP provider", skip_all)]
+    async fn send_email(
+        &self,
+        recipient: &Email,
+        subject: &str,
+        content: &str
+    ) -> Result<()> {
+        self.send(SendEmailRequest {
+            from: self.sender.as_ref().expose_secret(),
+            to: recipient.as_ref().expose_secret(),
+            subject,
+            html_body: None,
+            text_body: content,
+            message_stream: "outbound",
+        })
+        .await
+    }
+
+    #[tracing::instrument(name = "Sending rendered email through HTTP provider", skip_all)]
+    async fn send_rendered_email(
+        &self,
+        recipient: &Email,
+        email: &RenderedEmail
+    ) -> Result<()> {
+        self.send(SendEmailRequest {
+            from: self.sender.as_ref().expose_secret(),
+            to: recipient.as_ref().expose_secret(),
+            subject: &email.subject,
+            html_body: Some(&email.html_body),
+            text_body: &email.text_body,
+            message_stream: "outbound",
+        })
+        .await
+    }
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SendEmailRequest<'a> {
+    from: &'a str,
+    to: &'a str,
+    subject: &'a str,
+    /// Left out for plain-text emails, which are not valid HTML.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    html_body: Option<&'a str>,
+    text_body: &'a str,
+    message_stream: &'a str,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fake::faker::internet::en::SafeEmail;
+    use fake::faker::lorem::en::{Paragraph, Sentence};
+    use fake::{Fake, Faker};
+    use wiremock::matchers::{any, body_partial_json, header, header_exists, method, path};
+    use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+    struct SendEmailBodyMatcher;
+
+    impl wiremock::Match for SendEmailBodyMatcher {
+        fn matches(&self, request: &Request) -> bool {
+            let result: Result<serde_json::Value, _> = serde_json::from_slice(&request.body);
+            if let Ok(body) = result {
+                body.get("From").is_some()
+                    && body.get("To").is_some()
+                    && body.get("Subject").is_some()
+                    && body.get("HtmlBody").is_none()
+                    && body.get("TextBody").is_some()
+            } else {
+                false
+            }
+        }
+    }
+
+    fn subject() -> String {
+        Sentence(1..2).fake()
+    }
+
+    fn content() -> String {
+        Paragraph(1..10).fake()
+    }
+
+    fn email() -> Email {
+        Email::parse(Secret::new(SafeEmail().fake())).unwrap()
+    }
+
+    fn email_client(base_url: String, timeout: Duration) -> PostmarkEmailClient {
+        PostmarkEmailClient::new(PostmarkSettings {
+            base_url,
+            sender: email(),
+            authorization_token: Secret::new(Faker.fake()),
+            timeout,
+            max_retries: 2,
+            retry_base_delay: Duration::from_millis(10),
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn send_email_sends_the_expected_request() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri(), Duration::from_secs(1));
+
+        Mock::given(header_exists("X-Postmark-Server-Token"))
+            .and(header("Content-Type", "application/json"))
+            .and(path("/email"))
+            .and(method("POST"))
+            .and(SendEmailBodyMatcher)
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client.send_email(&email(), &subject(), &content()).await;
+
+        assert!(outcome.is_ok());
+    }
+
+    #[tokio::test]
+    async fn send_email_keeps_the_base_url_path() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(format!("{}/v1", mock_server.uri()), Duration::from_secs(1));
+
+        Mock::given(path("/v1/email"))
+            .and(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client.send_email(&email(), &subject(), &content()).await;
+
+        assert!(outcome.is_ok());
+    }
+
+    #[tokio::test]
+    async fn send_rendered_email_sends_separate_html_and_text_bodies() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri(), Duration::from_secs(1));
+
+        Mock::given(body_partial_json(serde_json::json!({
+                "Subject": "Your login code",
+                "HtmlBody": "<p>123456</p>",
+                "TextBody": "Your code is 123456",
+            })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let rendered_email = RenderedEmail {
+            subject: "Your login code".to_owned(),
+            html_body: "<p>123456</p>".to_owned(),
+            text_body: "Your code is 123456".to_owned(),
+        };
+        let outcome = email_client.send_rendered_email(&email(), &rendered_email).await;
+
+        assert!(outcome.is_ok());
+    }
+
+    #[tokio::test]
+    async fn send_email_retries_after_server_error() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri(), Duration::from_secs(1));
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client.send_email(&email(), &subject(), &content()).await;
+
+        assert!(outcome.is_ok());
+    }
+
+    #[tokio::test]
+    async fn send_email_gives_up_after_max_retries() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri(), Duration::from_secs(1));
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500).set_body_string("provider is down"))
+            .expect(3)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client.send_email(&email(), &subject(), &content()).await;
+
+        let report = format!("{:?}", outcome.unwrap_err());
+        assert!(report.contains("provider is down"));
+    }
+
+    #[tokio::test]
+    async fn send_email_does_not_retry_client_errors() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri(), Duration::from_secs(1));
+        let email = email();
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(422).set_body_string(format!(r#"{{"ErrorCode":300,"Message":"Invalid 'To' address: {}"}}"#, email.as_ref().expose_secret())))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client.send_email(&email, &subject(), &content()).await;
+
+        let report = format!("{:?}", outcome.unwrap_err());
+        assert!(report.contains("Invalid 'To' address"));
+        assert!(!report.contains(email.as_ref().expose_secret().as_str()));
+    }
+
+    #[tokio::test]
+    async fn send_email_times_out_if_the_server_takes_too_long() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri(), Duration::from_millis(200));
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(180)))
+            .expect(3)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client.send_email(&email(), &subject(), &content()).await;
+
+        assert!(outcome.is_err());
+    }
+}
@@ -1,22 +1,19 @@
-use std::sync::Arc;
-
-use redis::{Commands, Connection};
+use redis::{aio::ConnectionManager, AsyncCommands};
 use secrecy::{ExposeSecret, Secret};
-use tokio::sync::RwLock;
 use color_eyre::eyre::Context;
 
 use crate::{
-    domain::data_stores::{
-        BannedTokenStore, BannedTokenStoreError},
-        utils::auth::TOKEN_TTL_SECONDS,
+    domain::{data_stores::{
+        BannedTokenStore, BannedTokenStoreError}, Email},
+        utils::{auth::TOKEN_TTL_SECONDS, metrics::StoreOperationTimer},
 };
 
 pub struct RedisBannedTokenStore {
-    conn: Arc<RwLock<Connection>>
+    conn: ConnectionManager
 }
 
 impl RedisBannedTokenStore {
-    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+    pub fn new(conn: ConnectionManager) -> Self {
         Self{ conn }
     }
 }
@@ -24,9 +21,10 @@ impl RedisBannedTokenStore {
 #[async_trait::async_trait]
 impl BannedTokenStore for RedisBannedTokenStore {
     #[tracing::instrument(name= "Store a banned token to Redis", skip_all)]
-    async fn store_banned_token(&mut self, token: Secret<String>) -> Result<(), BannedTokenStoreError> {
-        let redis_token_key = get_key(&token.expose_secret());
-        let mut store_conn = self.conn.write().await;
+    async fn store_banned_token(&self, token: Secret<String>) -> Result<(), BannedTokenStoreError> {
+        let _timer = StoreOperationTimer::new("redis", "banned_tokens", "store_banned_token");
+        let redis_token_key = get_key(token.expose_secret());
+        let mut store_conn = self.conn.clone();
 
         let ttl: u64 = TOKEN_TTL_SECONDS
             .try_into()
@@ -35,6 +33,7 @@ impl BannedTokenStore for RedisBannedTokenStore {
 
         let _: () = store_conn
             .set_ex(redis_token_key, true, ttl)
+            .await
             .wrap_err("failed to set banned token in Redis")
             .map_err(BannedTokenStoreError::UnexpectedError)?;
 
@@ -44,20 +43,57 @@ impl BannedTokenStore for RedisBannedTokenStore {
 
     #[tracing::instrument(name= "Check for banned token in Redis", skip_all)]
     async fn check_banned_token(&self, token: Secret<String>) -> Result<bool, BannedTokenStoreError> {
-        let redis_token_key = get_key(&token.expose_secret());
-        let mut store_conn = self.conn.write().await;
+        let _timer = StoreOperationTimer::new("redis", "banned_tokens", "check_banned_token");
+        let redis_token_key = get_key(token.expose_secret());
+        let mut store_conn = self.conn.clone();
 
         let result: bool = store_conn
             .exists(redis_token_key)
+            .await
             .wrap_err("failed to check if token exists in Redis")
             .map_err(BannedTokenStoreError::UnexpectedError)?;
 
        Ok(result)
     }
+
+    #[tracing::instrument(name= "Revoke sessions in Redis", skip_all)]
+    async fn revoke_sessions(&self, email: &Email) -> Result<(), BannedTokenStoreError> {
+        let _timer = StoreOperationTimer::new("redis", "banned_tokens", "revoke_sessions");
+        let mut store_conn = self.conn.clone();
+
+        // The epoch has no expiry: tokens minted after the revocation carry
+        // the new value and must keep matching it.
+        let _: u64 = store_conn
+            .incr(get_session_epoch_key(email), 1)
+            .await
+            .wrap_err("failed to bump session epoch in Redis")
+            .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name= "Get session epoch from Redis", skip_all)]
+    async fn session_epoch(&self, email: &Email) -> Result<u64, BannedTokenStoreError> {
+        let _timer = StoreOperationTimer::new("redis", "banned_tokens", "session_epoch");
+        let mut store_conn = self.conn.clone();
+
+        let epoch: Option<u64> = store_conn
+            .get(get_session_epoch_key(email))
+            .await
+            .wrap_err("failed to get session epoch from Redis")
+            .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        Ok(epoch.unwrap_or_default())
+    }
 }
 
 const BANNED_TOKEN_KEY_PREFIX: &str = "banned_token:";
+const SESSION_EPOCH_KEY_PREFIX: &str = "session_epoch:";
 
 fn get_key(token: &str) -> String {
-    return format!("{}{}", BANNED_TOKEN_KEY_PREFIX, token);
+    format!("{}{}", BANNED_TOKEN_KEY_PREFIX, token)
+}
+
+fn get_session_epoch_key(email: &Email) -> String {
+    format!("{}{}", SESSION_EPOCH_KEY_PREFIX, email.as_ref().expose_secret())
 }
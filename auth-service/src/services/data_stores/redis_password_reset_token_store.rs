@@ -0,0 +1,118 @@
+use chrono::{DateTime, Duration, Utc};
+use redis::{aio::ConnectionManager, AsyncCommands};
+use serde::{Deserialize, Serialize};
+
+use crate::domain::{
+    data_stores::{PasswordResetTokenStore, PasswordResetTokenStoreError, TakenPasswordResetToken},
+    Email, PasswordResetToken,
+};
+use crate::utils::metrics::StoreOperationTimer;
+
+use color_eyre::eyre::Context;
+use secrecy::{ExposeSecret, Secret};
+
+pub struct RedisPasswordResetTokenStore {
+    conn: ConnectionManager
+}
+
+impl RedisPasswordResetTokenStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self{ conn }
+    }
+
+    async fn set_token(&self,
+        token: &PasswordResetToken,
+        entry: &TakenPasswordResetToken
+    ) -> Result<(), PasswordResetTokenStoreError> {
+        let seconds_left = (entry.expires_at - Utc::now()).num_seconds();
+        if seconds_left <= 0 {
+            return Ok(());
+        }
+
+        let stored_token = StoredToken {
+            email: entry.email.as_ref().expose_secret().to_owned(),
+            expires_at: entry.expires_at,
+            rejected_attempts: entry.rejected_attempts,
+        };
+        let serialized_data = serde_json::to_string(&stored_token)
+            .wrap_err("failed to serialize password reset token")
+            .map_err(PasswordResetTokenStoreError::UnexpectedError)?;
+
+        let mut conn = self.conn.clone();
+        let _: () = conn
+            .set_ex(get_key(token), serialized_data, seconds_left as u64)
+            .await
+            .wrap_err("failed to set password reset token in Redis")
+            .map_err(PasswordResetTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl PasswordResetTokenStore for RedisPasswordResetTokenStore {
+    #[tracing::instrument(name= "Add password reset token to Redis", skip_all)]
+    async fn add_token(&self,
+        token: PasswordResetToken,
+        email: Email
+    ) -> Result<(), PasswordResetTokenStoreError> {
+        let _timer = StoreOperationTimer::new("redis", "password_reset_tokens", "add_token");
+        let entry = TakenPasswordResetToken {
+            email,
+            expires_at: Utc::now() + Duration::seconds(ONE_HOUR_IN_SECONDS),
+            rejected_attempts: 0,
+        };
+
+        self.set_token(&token, &entry).await
+    }
+
+    #[tracing::instrument(name= "Take password reset token from Redis", skip_all)]
+    async fn take_token(&self,
+        token: &PasswordResetToken
+    ) -> Result<TakenPasswordResetToken, PasswordResetTokenStoreError> {
+        let _timer = StoreOperationTimer::new("redis", "password_reset_tokens", "take_token");
+        let mut conn = self.conn.clone();
+
+        // GETDEL keeps a token from being used by two concurrent requests.
+        let data: Option<String> = conn
+            .get_del(get_key(token))
+            .await
+            .wrap_err("failed to take password reset token from Redis")
+            .map_err(PasswordResetTokenStoreError::UnexpectedError)?;
+
+        let data = data.ok_or(PasswordResetTokenStoreError::TokenNotFound)?;
+        let stored_token: StoredToken = serde_json::from_str(&data)
+            .wrap_err("failed to deserialize password reset token")
+            .map_err(PasswordResetTokenStoreError::UnexpectedError)?;
+
+        Ok(TakenPasswordResetToken {
+            email: Email::parse(Secret::new(stored_token.email)).map_err(PasswordResetTokenStoreError::UnexpectedError)?,
+            expires_at: stored_token.expires_at,
+            rejected_attempts: stored_token.rejected_attempts,
+        })
+    }
+
+    #[tracing::instrument(name= "Restore password reset token to Redis", skip_all)]
+    async fn restore_token(&self,
+        token: PasswordResetToken,
+        taken: TakenPasswordResetToken
+    ) -> Result<(), PasswordResetTokenStoreError> {
+        let _timer = StoreOperationTimer::new("redis", "password_reset_tokens", "restore_token");
+
+        self.set_token(&token, &taken).await
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredToken {
+    email: String,
+    expires_at: DateTime<Utc>,
+    rejected_attempts: u32,
+}
+
+const ONE_HOUR_IN_SECONDS: i64 = 3600;
+const PASSWORD_RESET_TOKEN_PREFIX: &str = "password_reset_token:";
+
+fn get_key(token: &PasswordResetToken) -> String {
+    format!("{}{}", PASSWORD_RESET_TOKEN_PREFIX, token.as_ref().expose_secret())
+}
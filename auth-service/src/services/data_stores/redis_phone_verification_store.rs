@@ -0,0 +1,92 @@
+use redis::{aio::ConnectionManager, AsyncCommands};
+use serde::{Deserialize, Serialize};
+
+use crate::domain::{
+    data_stores::{PhoneVerificationStore, PhoneVerificationStoreError},
+    Email, PhoneNumber, TwoFACode,
+};
+use crate::utils::metrics::StoreOperationTimer;
+
+use color_eyre::eyre::Context;
+use secrecy::{ExposeSecret, Secret};
+
+pub struct RedisPhoneVerificationStore {
+    conn: ConnectionManager
+}
+
+impl RedisPhoneVerificationStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self{ conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl PhoneVerificationStore for RedisPhoneVerificationStore {
+    #[tracing::instrument(name= "Add phone verification code to Redis", skip_all)]
+    async fn add_code(&self,
+        email: Email,
+        phone_number: PhoneNumber,
+        code: TwoFACode
+    ) -> Result<(), PhoneVerificationStoreError> {
+        let _timer = StoreOperationTimer::new("redis", "phone_verification", "add_code");
+        let pending = PendingVerification {
+            phone_number: phone_number.as_ref().expose_secret().to_owned(),
+            code: code.as_ref().expose_secret().to_owned(),
+        };
+
+        let serialized_data = serde_json::to_string(&pending)
+            .wrap_err("failed to serialize phone verification")
+            .map_err(PhoneVerificationStoreError::UnexpectedError)?;
+
+        let mut conn = self.conn.clone();
+        let _: () = conn
+            .set_ex(get_key(&email), serialized_data, TEN_MINUTES_IN_SECONDS)
+            .await
+            .wrap_err("failed to set phone verification in Redis")
+            .map_err(PhoneVerificationStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name= "Take phone verification code from Redis", skip_all)]
+    async fn take_code(&self,
+        email: &Email
+    ) -> Result<(PhoneNumber, TwoFACode), PhoneVerificationStoreError> {
+        let _timer = StoreOperationTimer::new("redis", "phone_verification", "take_code");
+        let key = get_key(email);
+        let mut conn = self.conn.clone();
+
+        // GETDEL keeps a code from being used by two concurrent requests.
+        let data: Option<String> = conn
+            .get_del(&key)
+            .await
+            .wrap_err("failed to take phone verification from Redis")
+            .map_err(PhoneVerificationStoreError::UnexpectedError)?;
+
+        let data = data.ok_or(PhoneVerificationStoreError::CodeNotFound)?;
+
+        let pending: PendingVerification = serde_json::from_str(&data)
+            .wrap_err("failed to deserialize phone verification")
+            .map_err(PhoneVerificationStoreError::UnexpectedError)?;
+
+        let phone_number = PhoneNumber::parse(Secret::new(pending.phone_number))
+            .map_err(PhoneVerificationStoreError::UnexpectedError)?;
+        let code = TwoFACode::parse(Secret::new(pending.code))
+            .map_err(PhoneVerificationStoreError::UnexpectedError)?;
+
+        Ok((phone_number, code))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct PendingVerification {
+    phone_number: String,
+    code: String
+}
+
+const TEN_MINUTES_IN_SECONDS: u64 = 600;
+const PHONE_VERIFICATION_PREFIX: &str = "phone_verification:";
+
+fn get_key(email: &Email) -> String {
+    format!("{}{}", PHONE_VERIFICATION_PREFIX, email.as_ref().expose_secret())
+}
@@ -1,23 +1,21 @@
-use std::sync::Arc;
-
-use redis::{Commands, Connection};
+use redis::{aio::ConnectionManager, AsyncCommands};
 use serde::{Deserialize, Serialize};
-use tokio::sync::RwLock;
 
 use crate::domain::{
     data_stores::{ TwoFACodeStore, TwoFACodeStoreError}, 
     Email, LoginAttemptId, TwoFACode,
 };
+use crate::utils::metrics::StoreOperationTimer;
 
 use color_eyre::eyre::Context;
 use secrecy::{ExposeSecret, Secret};
 
 pub struct RedisTwoFACodeStore {
-    conn: Arc<RwLock<Connection>>
+    conn: ConnectionManager
 }
 
 impl RedisTwoFACodeStore {
-    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+    pub fn new(conn: ConnectionManager) -> Self {
         Self{ conn }
     }
 }
@@ -25,12 +23,13 @@ impl RedisTwoFACodeStore {
 #[async_trait::async_trait]
 impl TwoFACodeStore for RedisTwoFACodeStore {
     #[tracing::instrument(name= "Add 2fa code to Redis", skip_all)]
-    async fn add_code(&mut self, 
+    async fn add_code(&self, 
         email: Email,
         login_attempt_id: LoginAttemptId,
         code: TwoFACode
     ) -> Result<(), TwoFACodeStoreError> {
-        let mut conn = self.conn.write().await;
+        let _timer = StoreOperationTimer::new("redis", "two_fa_codes", "add_code");
+        let mut conn = self.conn.clone();
         let key = get_key(&email);
 
         let two_fa_tuple = TwoFATuple(
@@ -44,6 +43,7 @@ impl TwoFACodeStore for RedisTwoFACodeStore {
 
         let _:() = conn
             .set_ex(&key, serialized_data, TEN_MINUTES_IN_SECONDS)
+            .await
             .wrap_err("failed to set 2FA code in Redis")
             .map_err(TwoFACodeStoreError::UnexpectedError)?;
 
@@ -51,12 +51,14 @@ impl TwoFACodeStore for RedisTwoFACodeStore {
     }
 
     #[tracing::instrument(name= "Remove 2fa code from Redis", skip_all)]
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
-        let mut conn = self.conn.write().await;
+    async fn remove_code(&self, email: &Email) -> Result<(), TwoFACodeStoreError> {
+        let _timer = StoreOperationTimer::new("redis", "two_fa_codes", "remove_code");
+        let mut conn = self.conn.clone();
         let key = get_key(email);
 
         let _:() = conn
            .del(&key)
+           .await
            .wrap_err("failed to delete 2FA code from Redis")
            .map_err(TwoFACodeStoreError::UnexpectedError)?;
 
@@ -67,10 +69,11 @@ impl TwoFACodeStore for RedisTwoFACodeStore {
         &self,
         email: &Email,
     ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
-        let mut conn = self.conn.write().await;
+        let _timer = StoreOperationTimer::new("redis", "two_fa_codes", "get_code");
+        let mut conn = self.conn.clone();
         let key = get_key(email);
 
-        match conn.get::<_, String>(&key) {
+        match conn.get::<_, String>(&key).await {
             Ok(data) => {
                 let two_fa_tuple: TwoFATuple = serde_json::from_str(&data)
                     .wrap_err("failed to deserialize 2FA tuple")
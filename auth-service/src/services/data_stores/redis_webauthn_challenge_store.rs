@@ -0,0 +1,135 @@
+use redis::{aio::ConnectionManager, AsyncCommands};
+use serde::{Deserialize, Serialize};
+use webauthn_rs::prelude::{PasskeyAuthentication, PasskeyRegistration};
+
+use crate::domain::{
+    data_stores::{WebAuthnChallengeStore, WebAuthnChallengeStoreError},
+    Email, LoginAttemptId, PasskeyLogin,
+};
+use crate::utils::metrics::StoreOperationTimer;
+
+use color_eyre::eyre::Context;
+use secrecy::{ExposeSecret, Secret};
+
+pub struct RedisWebAuthnChallengeStore {
+    conn: ConnectionManager
+}
+
+impl RedisWebAuthnChallengeStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self{ conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl WebAuthnChallengeStore for RedisWebAuthnChallengeStore {
+    #[tracing::instrument(name= "Add WebAuthn registration state to Redis", skip_all)]
+    async fn add_registration(&self,
+        email: Email,
+        state: PasskeyRegistration
+    ) -> Result<(), WebAuthnChallengeStoreError> {
+        let _timer = StoreOperationTimer::new("redis", "webauthn_challenges", "add_registration");
+        let serialized_data = serde_json::to_string(&state)
+            .wrap_err("failed to serialize WebAuthn registration state")
+            .map_err(WebAuthnChallengeStoreError::UnexpectedError)?;
+
+        let mut conn = self.conn.clone();
+        let _: () = conn
+            .set_ex(get_key(REGISTRATION_PREFIX, email.as_ref()), serialized_data, CHALLENGE_TTL_SECONDS)
+            .await
+            .wrap_err("failed to set WebAuthn registration state in Redis")
+            .map_err(WebAuthnChallengeStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name= "Take WebAuthn registration state from Redis", skip_all)]
+    async fn take_registration(&self,
+        email: &Email
+    ) -> Result<PasskeyRegistration, WebAuthnChallengeStoreError> {
+        let _timer = StoreOperationTimer::new("redis", "webauthn_challenges", "take_registration");
+        let data = self.take(get_key(REGISTRATION_PREFIX, email.as_ref())).await?;
+
+        serde_json::from_str(&data)
+            .wrap_err("failed to deserialize WebAuthn registration state")
+            .map_err(WebAuthnChallengeStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name= "Add WebAuthn authentication state to Redis", skip_all)]
+    async fn add_authentication(&self,
+        email: Email,
+        login_attempt_id: LoginAttemptId,
+        kind: PasskeyLogin,
+        state: PasskeyAuthentication
+    ) -> Result<(), WebAuthnChallengeStoreError> {
+        let _timer = StoreOperationTimer::new("redis", "webauthn_challenges", "add_authentication");
+        let authentication = StoredAuthentication {
+            email: email.as_ref().expose_secret().to_owned(),
+            kind,
+            state
+        };
+
+        let serialized_data = serde_json::to_string(&authentication)
+            .wrap_err("failed to serialize WebAuthn authentication state")
+            .map_err(WebAuthnChallengeStoreError::UnexpectedError)?;
+
+        let mut conn = self.conn.clone();
+        let _: () = conn
+            .set_ex(get_key(AUTHENTICATION_PREFIX, login_attempt_id.as_ref()), serialized_data, CHALLENGE_TTL_SECONDS)
+            .await
+            .wrap_err("failed to set WebAuthn authentication state in Redis")
+            .map_err(WebAuthnChallengeStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name= "Take WebAuthn authentication state from Redis", skip_all)]
+    async fn take_authentication(&self,
+        login_attempt_id: &LoginAttemptId
+    ) -> Result<(Email, PasskeyLogin, PasskeyAuthentication), WebAuthnChallengeStoreError> {
+        let _timer = StoreOperationTimer::new("redis", "webauthn_challenges", "take_authentication");
+        let data = self.take(get_key(AUTHENTICATION_PREFIX, login_attempt_id.as_ref())).await?;
+
+        let authentication: StoredAuthentication = serde_json::from_str(&data)
+            .wrap_err("failed to deserialize WebAuthn authentication state")
+            .map_err(WebAuthnChallengeStoreError::UnexpectedError)?;
+
+        let email = Email::parse(Secret::new(authentication.email))
+            .map_err(WebAuthnChallengeStoreError::UnexpectedError)?;
+
+        Ok((email, authentication.kind, authentication.state))
+    }
+}
+
+impl RedisWebAuthnChallengeStore {
+    async fn take(&self, key: String) -> Result<String, WebAuthnChallengeStoreError> {
+        let mut conn = self.conn.clone();
+
+        // GETDEL keeps a challenge from being answered twice by concurrent
+        // requests.
+        let data: Option<String> = conn
+            .get_del(&key)
+            .await
+            .wrap_err("failed to take WebAuthn state from Redis")
+            .map_err(WebAuthnChallengeStoreError::UnexpectedError)?;
+
+        data.ok_or(WebAuthnChallengeStoreError::ChallengeNotFound)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredAuthentication {
+    email: String,
+    #[serde(default)]
+    kind: PasskeyLogin,
+    state: PasskeyAuthentication
+}
+
+const CHALLENGE_TTL_SECONDS: u64 = 300;
+const REGISTRATION_PREFIX: &str = "webauthn_registration:";
+const AUTHENTICATION_PREFIX: &str = "webauthn_authentication:";
+
+/// Registrations are keyed by email and authentications by login attempt id.
+fn get_key(prefix: &str, id: &Secret<String>) -> String {
+    format!("{}{}", prefix, id.expose_secret())
+}
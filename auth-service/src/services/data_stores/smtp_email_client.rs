@@ -0,0 +1,292 @@
+use std::{str::FromStr, time::Duration};
+
+use color_eyre::eyre::{eyre, Context, Result};
+use lettre::{
+    message::{header::ContentType, Mailbox, MessageBuilder, MultiPart},
+    transport::smtp::{authentication::Credentials, PoolConfig},
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use secrecy::{ExposeSecret, Secret};
+use serde::Deserialize;
+
+use crate::{domain::{Email, EmailClient, RenderedEmail}, utils::redact::redact};
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    /// Plain text connection, only meant for local SMTP sinks.
+    None,
+    /// Upgrade a plain connection with STARTTLS, usually on port 587.
+    StartTls,
+    /// Implicit TLS from the first byte, usually on port 465.
+    Tls,
+}
+
+impl FromStr for SmtpTls {
+    type Err = color_eyre::eyre::Report;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "none" => Ok(SmtpTls::None),
+            "starttls" => Ok(SmtpTls::StartTls),
+            "tls" => Ok(SmtpTls::Tls),
+            other => Err(eyre!("unknown SMTP TLS mode: {}", other)),
+        }
+    }
+}
+
+pub struct SmtpSettings {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<Secret<String>>,
+    pub tls: SmtpTls,
+    pub sender: Email,
+    pub pool_size: u32,
+    pub timeout: Duration,
+}
+
+pub struct SmtpEmailClient {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    sender: Mailbox,
+}
+
+impl SmtpEmailClient {
+    pub fn new(settings: SmtpSettings) -> Result<Self> {
+        let builder = match settings.tls {
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&settings.host),
+            SmtpTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&settings.host)
+                .wrap_err("failed to configure STARTTLS SMTP relay")?,
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&settings.host)
+                .wrap_err("failed to configure TLS SMTP relay")?,
+        };
+
+        let mut builder = builder
+            .port(settings.port)
+            .timeout(Some(settings.timeout))
+            .pool_config(PoolConfig::new().max_size(settings.pool_size));
+
+        match (settings.username, settings.password) {
+            (Some(username), Some(password)) => {
+                builder = builder.credentials(Credentials::new(username, password.expose_secret().to_owned()));
+            }
+            (None, None) => {}
+            _ => return Err(eyre!("SMTP username and password must be set together")),
+        }
+
+        let sender = settings.sender
+            .as_ref()
+            .expose_secret()
+            .parse()
+            .wrap_err("failed to parse sender address")?;
+
+        Ok(Self {
+            transport: builder.build(),
+            sender,
+        })
+    }
+
+    fn message_builder(&self, recipient: &Email, subject: &str) -> Result<MessageBuilder> {
+        let recipient: Mailbox = recipient
+            .as_ref()
+            .expose_secret()
+            .parse()
+            .wrap_err("failed to parse recipient address")?;
+
+        Ok(Message::builder()
+            .from(self.sender.clone())
+            .to(recipient)
+            .subject(subject))
+    }
+
+    async fn send_message(&self, message: Message) -> Result<()> {
+        let recipients: Vec<String> = message.envelope().to().iter().map(ToString::to_string).collect();
+
+        // Server replies often echo the recipient, so the error is flattened
+        // to text with the addresses removed.
+        if let Err(e) = self.transport.send(message).await {
+            let error = recipients.iter().fold(e.to_string(), |error, recipient| redact(&error, recipient));
+            return Err(eyre!("failed to send email over SMTP: {}", error));
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailClient for SmtpEmailClient {
+    #[tracing::instrument(name = "Sending email over SMTP", skip_all)]
+    async fn send_email(
+        &self,
+        recipient: &Email,
+        subject: &str,
+        content: &str
+    ) -> Result<()> {
+        let message = self.message_builder(recipient, subject)?
+            .header(ContentType::TEXT_PLAIN)
+            .body(content.to_owned())
+            .wrap_err("failed to build email message")?;
+
+        self.send_message(message).await
+    }
+
+    #[tracing::instrument(name = "Sending rendered email over SMTP", skip_all)]
+    async fn send_rendered_email(
+        &self,
+        recipient: &Email,
+        email: &RenderedEmail
+    ) -> Result<()> {
+        let message = self.message_builder(recipient, &email.subject)?
+            .multipart(MultiPart::alternative_plain_html(email.text_body.clone(), email.html_body.clone()))
+            .wrap_err("failed to build email message")?;
+
+        self.send_message(message).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+        sync::mpsc,
+    };
+
+    /// Minimal SMTP sink that accepts every message and hands the DATA
+    /// section back to the test.
+    async fn spawn_smtp_sink() -> (u16, mpsc::UnboundedReceiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let sender = sender.clone();
+                tokio::spawn(async move {
+                    let (reader, mut writer) = stream.into_split();
+                    let mut lines = BufReader::new(reader).lines();
+                    writer.write_all(b"220 localhost ESMTP sink\r\n").await.unwrap();
+
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        let command = line.to_uppercase();
+                        let reply: &[u8] = if command.starts_with("EHLO") || command.starts_with("HELO") {
+                            b"250 localhost\r\n"
+                        } else if command.starts_with("DATA") {
+                            writer.write_all(b"354 end with <CRLF>.<CRLF>\r\n").await.unwrap();
+                            let mut data = String::new();
+                            while let Ok(Some(line)) = lines.next_line().await {
+                                if line == "." {
+                                    break;
+                                }
+                                data.push_str(&line);
+                                data.push('\n');
+                            }
+                            sender.send(data).unwrap();
+                            b"250 queued\r\n"
+                        } else if command.starts_with("QUIT") {
+                            writer.write_all(b"221 bye\r\n").await.unwrap();
+                            break;
+                        } else {
+                            b"250 OK\r\n"
+                        };
+                        writer.write_all(reply).await.unwrap();
+                    }
+                });
+            }
+        });
+
+        (port, receiver)
+    }
+
+    fn settings(port: u16) -> SmtpSettings {
+        SmtpSettings {
+            host: "127.0.0.1".to_owned(),
+            port,
+            username: None,
+            password: None,
+            tls: SmtpTls::None,
+            sender: Email::parse(Secret::new("no-reply@example.com".to_owned())).unwrap(),
+            pool_size: 2,
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_email_delivers_to_smtp_sink() {
+        let (port, mut receiver) = spawn_smtp_sink().await;
+        let client = SmtpEmailClient::new(settings(port)).unwrap();
+        let recipient = Email::parse(Secret::new("user@example.com".to_owned())).unwrap();
+
+        client.send_email(&recipient, "Your code", "123456").await.unwrap();
+
+        let data = receiver.recv().await.unwrap();
+        assert!(data.contains("To: user@example.com"));
+        assert!(data.contains("From: no-reply@example.com"));
+        assert!(data.contains("Subject: Your code"));
+        assert!(data.contains("123456"));
+    }
+
+    #[tokio::test]
+    async fn test_send_email_reuses_pooled_connection() {
+        let (port, mut receiver) = spawn_smtp_sink().await;
+        let client = SmtpEmailClient::new(settings(port)).unwrap();
+        let recipient = Email::parse(Secret::new("user@example.com".to_owned())).unwrap();
+
+        for _ in 0..3 {
+            client.send_email(&recipient, "Your code", "123456").await.unwrap();
+        }
+
+        for _ in 0..3 {
+            assert!(receiver.recv().await.is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_email_fails_without_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+
+        let client = SmtpEmailClient::new(settings(port)).unwrap();
+        let recipient = Email::parse(Secret::new("user@example.com".to_owned())).unwrap();
+
+        assert!(client.send_email(&recipient, "Your code", "123456").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_send_rendered_email_sends_plain_and_html_parts() {
+        let (port, mut receiver) = spawn_smtp_sink().await;
+        let client = SmtpEmailClient::new(settings(port)).unwrap();
+        let recipient = Email::parse(Secret::new("user@example.com".to_owned())).unwrap();
+        let email = RenderedEmail {
+            subject: "Your login code".to_owned(),
+            html_body: "<p>123456</p>".to_owned(),
+            text_body: "Your code is 123456".to_owned(),
+        };
+
+        client.send_rendered_email(&recipient, &email).await.unwrap();
+
+        let data = receiver.recv().await.unwrap();
+        assert!(data.contains("multipart/alternative"));
+        assert!(data.contains("text/plain"));
+        assert!(data.contains("text/html"));
+        assert!(data.contains("<p>123456</p>"));
+    }
+
+    #[test]
+    fn test_credentials_must_be_set_together() {
+        let mut settings = settings(2525);
+        settings.username = Some("user".to_owned());
+
+        assert!(SmtpEmailClient::new(settings).is_err());
+    }
+
+    #[test]
+    fn test_parse_smtp_tls() {
+        assert_eq!("STARTTLS".parse::<SmtpTls>().unwrap(), SmtpTls::StartTls);
+        assert_eq!("tls".parse::<SmtpTls>().unwrap(), SmtpTls::Tls);
+        assert_eq!("none".parse::<SmtpTls>().unwrap(), SmtpTls::None);
+        assert!("ssl".parse::<SmtpTls>().is_err());
+    }
+}
@@ -0,0 +1,163 @@
+use std::time::Duration;
+
+use color_eyre::eyre::{eyre, Context, Result};
+use reqwest::{Client, Url};
+use secrecy::{ExposeSecret, Secret};
+use serde::Serialize;
+
+use crate::{domain::{PhoneNumber, SmsClient}, utils::redact::redact};
+
+pub struct TwilioSettings {
+    pub base_url: String,
+    pub account_sid: String,
+    pub auth_token: Secret<String>,
+    pub sender: PhoneNumber,
+    pub timeout: Duration,
+}
+
+/// Sends text messages through a Twilio style messaging HTTP API.
+pub struct TwilioSmsClient {
+    http_client: Client,
+    messages_url: Url,
+    account_sid: String,
+    auth_token: Secret<String>,
+    sender: PhoneNumber,
+}
+
+impl TwilioSmsClient {
+    pub fn new(settings: TwilioSettings) -> Result<Self> {
+        let messages_url = Url::parse(&settings.base_url)
+            .and_then(|base_url| base_url.join(&format!("/2010-04-01/Accounts/{}/Messages.json", settings.account_sid)))
+            .wrap_err("failed to build SMS provider url")?;
+
+        let http_client = Client::builder()
+            .timeout(settings.timeout)
+            .build()
+            .wrap_err("failed to build SMS provider http client")?;
+
+        Ok(Self {
+            http_client,
+            messages_url,
+            account_sid: settings.account_sid,
+            auth_token: settings.auth_token,
+            sender: settings.sender,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl SmsClient for TwilioSmsClient {
+    #[tracing::instrument(name = "Sending SMS through HTTP provider", skip_all)]
+    async fn send_sms(
+        &self,
+        recipient: &PhoneNumber,
+        message: &str
+    ) -> Result<()> {
+        let request_body = SendSmsRequest {
+            from: self.sender.as_ref().expose_secret(),
+            to: recipient.as_ref().expose_secret(),
+            body: message,
+        };
+
+        let response = self.http_client
+            .post(self.messages_url.clone())
+            .basic_auth(&self.account_sid, Some(self.auth_token.expose_secret()))
+            .form(&request_body)
+            .send()
+            .await
+            .wrap_err("failed to send request to SMS provider")?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(eyre!("SMS provider responded with {}: {}", status, redact(&body, request_body.to)));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SendSmsRequest<'a> {
+    from: &'a str,
+    to: &'a str,
+    body: &'a str,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{any, body_string_contains, header_exists, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn phone_number(number: &str) -> PhoneNumber {
+        PhoneNumber::parse(Secret::new(number.to_owned())).unwrap()
+    }
+
+    fn sms_client(base_url: String, timeout: Duration) -> TwilioSmsClient {
+        TwilioSmsClient::new(TwilioSettings {
+            base_url,
+            account_sid: "AC123".to_owned(),
+            auth_token: Secret::new("token".to_owned()),
+            sender: phone_number("+15005550006"),
+            timeout,
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn send_sms_sends_the_expected_request() {
+        let mock_server = MockServer::start().await;
+        let sms_client = sms_client(mock_server.uri(), Duration::from_secs(1));
+
+        Mock::given(path("/2010-04-01/Accounts/AC123/Messages.json"))
+            .and(method("POST"))
+            .and(header_exists("Authorization"))
+            .and(body_string_contains("To=%2B14155550100"))
+            .and(body_string_contains("From=%2B15005550006"))
+            .and(body_string_contains("Body=Your+code+is+123456"))
+            .respond_with(ResponseTemplate::new(201))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = sms_client.send_sms(&phone_number("+14155550100"), "Your code is 123456").await;
+
+        assert!(outcome.is_ok());
+    }
+
+    #[tokio::test]
+    async fn send_sms_fails_if_the_provider_rejects_the_message() {
+        let mock_server = MockServer::start().await;
+        let sms_client = sms_client(mock_server.uri(), Duration::from_secs(1));
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(400).set_body_string(r#"{"code":21211,"message":"The 'To' number +14155550100 is not a valid phone number."}"#))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = sms_client.send_sms(&phone_number("+14155550100"), "Your code is 123456").await;
+
+        let report = format!("{:?}", outcome.unwrap_err());
+        assert!(report.contains("is not a valid phone number"));
+        assert!(!report.contains("+14155550100"));
+    }
+
+    #[tokio::test]
+    async fn send_sms_times_out_if_the_server_takes_too_long() {
+        let mock_server = MockServer::start().await;
+        let sms_client = sms_client(mock_server.uri(), Duration::from_millis(200));
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(201).set_delay(Duration::from_secs(180)))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = sms_client.send_sms(&phone_number("+14155550100"), "Your code is 123456").await;
+
+        assert!(outcome.is_err());
+    }
+}
@@ -0,0 +1,319 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use color_eyre::eyre::{Context, Result};
+use metrics::{counter, gauge};
+
+use crate::{
+    app_state::{EmailClientType, EmailOutboxType},
+    domain::OutboxEmail,
+};
+
+pub struct EmailOutboxSettings {
+    pub batch_size: u32,
+    /// How long to wait before polling again once the outbox is empty.
+    pub poll_interval: Duration,
+    /// Attempts before an email is moved to the dead-letter state.
+    pub max_attempts: u32,
+    /// Delay after the first failure, doubled after each further one.
+    pub retry_base_delay: Duration,
+    pub max_retry_delay: Duration,
+    /// How long a claimed email stays hidden from other workers. Must be
+    /// longer than the email client takes to give up on a delivery.
+    pub lease: Duration,
+}
+
+/// Drains the email outbox in the background so that a slow or failing
+/// email provider never blocks a request.
+pub struct EmailOutboxWorker {
+    outbox: EmailOutboxType,
+    email_client: EmailClientType,
+    settings: EmailOutboxSettings,
+}
+
+impl EmailOutboxWorker {
+    pub fn new(outbox: EmailOutboxType, email_client: EmailClientType, settings: EmailOutboxSettings) -> Self {
+        Self {
+            outbox,
+            email_client,
+            settings,
+        }
+    }
+
+    pub async fn run(self) {
+        loop {
+            match self.process_batch().await {
+                Ok(0) => tokio::time::sleep(self.settings.poll_interval).await,
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::error!(error = ?e, "failed to process email outbox");
+                    tokio::time::sleep(self.settings.poll_interval).await;
+                }
+            }
+        }
+    }
+
+    /// Delivers one batch of due emails and returns how many were claimed.
+    #[tracing::instrument(name = "Processing email outbox batch", skip_all)]
+    pub async fn process_batch(&self) -> Result<usize> {
+        let lease_until = Utc::now() + chrono::Duration::from_std(self.settings.lease)
+            .wrap_err("email outbox lease is out of range")?;
+
+        let emails = self.outbox
+            .claim_due(self.settings.batch_size, lease_until)
+            .await
+            .wrap_err("failed to claim emails from outbox")?;
+
+        // An email that cannot be marked is claimed again once its lease
+        // runs out, so the rest of the batch still goes out.
+        for email in &emails {
+            if let Err(e) = self.deliver(email).await {
+                tracing::error!(error = ?e, email_id = %email.id, "failed to record outbox email delivery");
+            }
+        }
+
+        self.record_stats().await;
+
+        Ok(emails.len())
+    }
+
+    #[tracing::instrument(name = "Delivering outbox email", skip_all, fields(email_id = %email.id, attempt = email.attempts))]
+    async fn deliver(&self, email: &OutboxEmail) -> Result<()> {
+        if email.deliver_by.is_some_and(|deliver_by| deliver_by <= Utc::now()) {
+            tracing::warn!("email is past its delivery deadline, moving it to the dead-letter state");
+            counter!("email_outbox_dead_lettered_total").increment(1);
+            return self.outbox
+                .dead_letter(email.id, "delivery deadline passed")
+                .await
+                .wrap_err("failed to dead-letter email");
+        }
+
+        let result = self.email_client
+            .send_rendered_email(&email.recipient, &email.email)
+            .await;
+
+        let error = match result {
+            Ok(()) => {
+                counter!("email_outbox_sent_total").increment(1);
+                return self.outbox.mark_sent(email.id).await.wrap_err("failed to mark email as sent");
+            }
+            Err(e) => e.chain().map(|cause| cause.to_string()).collect::<Vec<_>>().join(": ")
+        };
+
+        if email.attempts >= self.settings.max_attempts {
+            tracing::error!(error, "giving up on email, moving it to the dead-letter state");
+            counter!("email_outbox_dead_lettered_total").increment(1);
+            return self.outbox.dead_letter(email.id, &error).await.wrap_err("failed to dead-letter email");
+        }
+
+        let delay = self.retry_delay(email.attempts);
+        tracing::warn!(error, ?delay, "email delivery failed, retrying later");
+        counter!("email_outbox_retries_total").increment(1);
+
+        let retry_at = Utc::now() + chrono::Duration::from_std(delay)
+            .wrap_err("email retry delay is out of range")?;
+        self.outbox.retry_later(email.id, &error, retry_at).await.wrap_err("failed to reschedule email")
+    }
+
+    fn retry_delay(&self, attempts: u32) -> Duration {
+        let exponent = attempts.saturating_sub(1);
+
+        self.settings.retry_base_delay
+            .saturating_mul(2u32.saturating_pow(exponent))
+            .min(self.settings.max_retry_delay)
+    }
+
+    async fn record_stats(&self) {
+        match self.outbox.stats().await {
+            Ok(stats) => {
+                gauge!("email_outbox_pending").set(stats.pending as f64);
+                gauge!("email_outbox_dead").set(stats.dead as f64);
+            }
+            Err(e) => tracing::warn!(error = %e, "failed to read email outbox stats"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    };
+
+    use chrono::DateTime;
+    use color_eyre::eyre::eyre;
+    use secrecy::Secret;
+    use uuid::Uuid;
+    use super::*;
+    use crate::{
+        domain::{Email, EmailClient, EmailOutbox, EmailOutboxError, EmailOutboxStats, RenderedEmail},
+        services::HashmapEmailOutbox,
+    };
+
+    /// Fails the first `failures` sends and succeeds afterwards.
+    struct FlakyEmailClient {
+        failures: u32,
+        calls: AtomicU32,
+    }
+
+    #[async_trait::async_trait]
+    impl EmailClient for FlakyEmailClient {
+        async fn send_email(&self, _recipient: &Email, _subject: &str, _content: &str) -> Result<()> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            if call < self.failures {
+                return Err(eyre!("provider unavailable"));
+            }
+            Ok(())
+        }
+    }
+
+    /// Loses track of the first email it is asked to mark as sent.
+    #[derive(Default)]
+    struct ForgetfulOutbox {
+        inner: HashmapEmailOutbox,
+        forgot: AtomicU32,
+    }
+
+    #[async_trait::async_trait]
+    impl EmailOutbox for ForgetfulOutbox {
+        async fn enqueue(&self, email: OutboxEmail) -> Result<(), EmailOutboxError> {
+            self.inner.enqueue(email).await
+        }
+
+        async fn claim_due(&self, limit: u32, lease_until: DateTime<Utc>) -> Result<Vec<OutboxEmail>, EmailOutboxError> {
+            self.inner.claim_due(limit, lease_until).await
+        }
+
+        async fn mark_sent(&self, id: Uuid) -> Result<(), EmailOutboxError> {
+            if self.forgot.fetch_add(1, Ordering::SeqCst) == 0 {
+                return Err(EmailOutboxError::EmailNotFound);
+            }
+            self.inner.mark_sent(id).await
+        }
+
+        async fn retry_later(&self, id: Uuid, error: &str, retry_at: DateTime<Utc>) -> Result<(), EmailOutboxError> {
+            self.inner.retry_later(id, error, retry_at).await
+        }
+
+        async fn dead_letter(&self, id: Uuid, error: &str) -> Result<(), EmailOutboxError> {
+            self.inner.dead_letter(id, error).await
+        }
+
+        async fn stats(&self) -> Result<EmailOutboxStats, EmailOutboxError> {
+            self.inner.stats().await
+        }
+    }
+
+    fn settings() -> EmailOutboxSettings {
+        EmailOutboxSettings {
+            batch_size: 10,
+            poll_interval: Duration::from_millis(10),
+            max_attempts: 3,
+            retry_base_delay: Duration::ZERO,
+            max_retry_delay: Duration::ZERO,
+            lease: Duration::from_secs(60),
+        }
+    }
+
+    fn outbox_email() -> OutboxEmail {
+        OutboxEmail::new(
+            Email::parse(Secret::new("user.test@mail.com".to_string())).unwrap(),
+            RenderedEmail {
+                subject: "Your login code".to_owned(),
+                html_body: "<p>123456</p>".to_owned(),
+                text_body: "123456".to_owned(),
+            },
+        )
+    }
+
+    async fn worker_with_email(failures: u32) -> (EmailOutboxWorker, Arc<HashmapEmailOutbox>, OutboxEmail) {
+        let outbox = Arc::new(HashmapEmailOutbox::default());
+        let email = outbox_email();
+        outbox.enqueue(email.clone()).await.unwrap();
+
+        let email_client = Arc::new(FlakyEmailClient { failures, calls: AtomicU32::new(0) });
+        let worker = EmailOutboxWorker::new(outbox.clone(), email_client, settings());
+
+        (worker, outbox, email)
+    }
+
+    #[tokio::test]
+    async fn test_delivered_email_leaves_outbox() {
+        let (worker, outbox, _) = worker_with_email(0).await;
+
+        assert_eq!(worker.process_batch().await.unwrap(), 1);
+
+        assert_eq!(outbox.stats().await.unwrap(), EmailOutboxStats::default());
+    }
+
+    #[tokio::test]
+    async fn test_failed_email_is_retried() {
+        let (worker, outbox, _) = worker_with_email(1).await;
+
+        assert_eq!(worker.process_batch().await.unwrap(), 1);
+        assert_eq!(outbox.stats().await.unwrap(), EmailOutboxStats { pending: 1, dead: 0 });
+
+        assert_eq!(worker.process_batch().await.unwrap(), 1);
+        assert_eq!(outbox.stats().await.unwrap(), EmailOutboxStats::default());
+    }
+
+    #[tokio::test]
+    async fn test_email_is_dead_lettered_after_max_attempts() {
+        let (worker, outbox, email) = worker_with_email(u32::MAX).await;
+
+        for _ in 0..3 {
+            assert_eq!(worker.process_batch().await.unwrap(), 1);
+        }
+
+        assert_eq!(worker.process_batch().await.unwrap(), 0);
+        assert_eq!(outbox.stats().await.unwrap(), EmailOutboxStats { pending: 0, dead: 1 });
+        assert_eq!(outbox.last_error(email.id).await.as_deref(), Some("provider unavailable"));
+    }
+
+    #[tokio::test]
+    async fn test_email_past_its_deadline_is_dead_lettered_unsent() {
+        let outbox = Arc::new(HashmapEmailOutbox::default());
+        let email = outbox_email().with_deadline(Utc::now() - chrono::Duration::seconds(1));
+        outbox.enqueue(email.clone()).await.unwrap();
+        let email_client = Arc::new(FlakyEmailClient { failures: 0, calls: AtomicU32::new(0) });
+        let worker = EmailOutboxWorker::new(outbox.clone(), email_client.clone(), settings());
+
+        assert_eq!(worker.process_batch().await.unwrap(), 1);
+
+        assert_eq!(email_client.calls.load(Ordering::SeqCst), 0);
+        assert_eq!(outbox.stats().await.unwrap(), EmailOutboxStats { pending: 0, dead: 1 });
+        assert_eq!(outbox.last_error(email.id).await.as_deref(), Some("delivery deadline passed"));
+    }
+
+    #[tokio::test]
+    async fn test_batch_continues_after_an_email_cannot_be_marked() {
+        let outbox = Arc::new(ForgetfulOutbox::default());
+        for _ in 0..3 {
+            outbox.enqueue(outbox_email()).await.unwrap();
+        }
+        let email_client = Arc::new(FlakyEmailClient { failures: 0, calls: AtomicU32::new(0) });
+        let worker = EmailOutboxWorker::new(outbox.clone(), email_client.clone(), settings());
+
+        assert_eq!(worker.process_batch().await.unwrap(), 3);
+
+        assert_eq!(email_client.calls.load(Ordering::SeqCst), 3);
+        assert_eq!(outbox.stats().await.unwrap(), EmailOutboxStats { pending: 1, dead: 0 });
+    }
+
+    #[test]
+    fn test_retry_delay_backs_off_up_to_max() {
+        let outbox = Arc::new(HashmapEmailOutbox::default());
+        let email_client = Arc::new(FlakyEmailClient { failures: 0, calls: AtomicU32::new(0) });
+        let worker = EmailOutboxWorker::new(outbox, email_client, EmailOutboxSettings {
+            retry_base_delay: Duration::from_secs(5),
+            max_retry_delay: Duration::from_secs(60),
+            ..settings()
+        });
+
+        assert_eq!(worker.retry_delay(1), Duration::from_secs(5));
+        assert_eq!(worker.retry_delay(2), Duration::from_secs(10));
+        assert_eq!(worker.retry_delay(4), Duration::from_secs(40));
+        assert_eq!(worker.retry_delay(5), Duration::from_secs(60));
+    }
+}
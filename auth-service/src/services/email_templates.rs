@@ -0,0 +1,201 @@
+use std::{io::ErrorKind, path::PathBuf};
+
+use color_eyre::eyre::{Context, Result};
+use minijinja::{context, Environment, Value};
+use serde::Serialize;
+
+use crate::domain::{EmailTemplate, Locale, RenderedEmail};
+
+macro_rules! builtin_templates {
+    ($($name:literal),* $(,)?) => {
+        &[$(($name, include_str!(concat!("../../templates/emails/", $name)))),*]
+    };
+}
+
+static BUILTIN_TEMPLATES: &[(&str, &str)] = builtin_templates!(
+    "layout.html",
+    "en/two_fa_code.txt",
+    "en/two_fa_code.html",
+    "en/password_reset.txt",
+    "en/password_reset.html",
+    "en/verification.txt",
+    "en/verification.html",
+    "en/security_alert.txt",
+    "en/security_alert.html",
+    "es/two_fa_code.txt",
+    "es/two_fa_code.html",
+    "es/password_reset.txt",
+    "es/password_reset.html",
+    "es/verification.txt",
+    "es/verification.html",
+    "es/security_alert.txt",
+    "es/security_alert.html",
+);
+
+/// Renders the transactional emails. Each template has a `.txt` file with
+/// `subject` and `body` blocks and a `.html` file for the HTML part.
+///
+/// Operators can override any built-in template by placing a file with the
+/// same relative path (e.g. `es/two_fa_code.html`) in the override directory.
+pub struct EmailTemplates {
+    env: Environment<'static>,
+}
+
+impl EmailTemplates {
+    pub fn new(override_dir: Option<PathBuf>) -> Result<Self> {
+        let mut env = Environment::new();
+
+        env.set_loader(move |name| {
+            if let Some(dir) = &override_dir {
+                match std::fs::read_to_string(dir.join(name)) {
+                    Ok(source) => return Ok(Some(source)),
+                    Err(e) if e.kind() == ErrorKind::NotFound => {}
+                    Err(e) => {
+                        return Err(minijinja::Error::new(
+                            minijinja::ErrorKind::TemplateNotFound,
+                            format!("failed to read template override {}", name),
+                        )
+                        .with_source(e))
+                    }
+                }
+            }
+
+            Ok(BUILTIN_TEMPLATES
+                .iter()
+                .find(|(builtin, _)| *builtin == name)
+                .map(|(_, source)| source.to_string()))
+        });
+
+        let templates = Self { env };
+
+        // Load everything up front so a broken override fails at startup
+        // instead of on the first login.
+        for locale in Locale::ALL {
+            for template in EmailTemplate::ALL {
+                for extension in ["txt", "html"] {
+                    templates.env
+                        .get_template(&template_path(template, locale, extension))
+                        .wrap_err("failed to load email template")?;
+                }
+            }
+        }
+
+        Ok(templates)
+    }
+
+    #[tracing::instrument(name = "Render email template", skip(self, context))]
+    pub fn render<S: Serialize>(&self, template: EmailTemplate, locale: Locale, context: S) -> Result<RenderedEmail> {
+        let context = context! { locale => locale.code(), ..Value::from_serialize(&context) };
+
+        let text_template = self.env
+            .get_template(&template_path(template, locale, "txt"))
+            .wrap_err("failed to load text email template")?;
+
+        let mut captured = text_template
+            .render_captured(&context)
+            .wrap_err("failed to evaluate text email template")?;
+
+        let (subject, text_body) = captured.with_state_mut(|state| {
+            Ok::<_, minijinja::Error>((state.render_block("subject")?, state.render_block("body")?))
+        })
+        .wrap_err("failed to render text email blocks")?;
+
+        let html_body = self.env
+            .get_template(&template_path(template, locale, "html"))
+            .wrap_err("failed to load HTML email template")?
+            .render(&context)
+            .wrap_err("failed to render HTML email body")?;
+
+        Ok(RenderedEmail {
+            subject: subject.trim().to_owned(),
+            html_body: html_body.trim().to_owned(),
+            text_body: text_body.trim().to_owned(),
+        })
+    }
+}
+
+fn template_path(template: EmailTemplate, locale: Locale, extension: &str) -> String {
+    format!("{}/{}.{}", locale.code(), template.name(), extension)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_render_two_fa_code() {
+        let templates = EmailTemplates::new(None).unwrap();
+
+        let email = templates
+            .render(EmailTemplate::TwoFACode, Locale::En, context! { code => "123456", expires_in_minutes => 10 })
+            .unwrap();
+
+        assert_eq!(email.subject, "Your login code");
+        assert!(email.text_body.contains("123456"));
+        assert!(email.html_body.contains("123456"));
+        assert!(email.html_body.contains(r#"<html lang="en">"#));
+    }
+
+    #[test]
+    fn test_render_localized() {
+        let templates = EmailTemplates::new(None).unwrap();
+
+        let email = templates
+            .render(EmailTemplate::TwoFACode, Locale::Es, context! { code => "123456", expires_in_minutes => 10 })
+            .unwrap();
+
+        assert_eq!(email.subject, "Tu código de inicio de sesión");
+        assert!(email.text_body.contains("Caduca en 10 minutos"));
+    }
+
+    #[test]
+    fn test_render_escapes_html_only() {
+        let templates = EmailTemplates::new(None).unwrap();
+
+        let email = templates
+            .render(EmailTemplate::SecurityAlert, Locale::En, context! {
+                event => "<script>alert(1)</script>",
+                occurred_at => "2024-07-01 10:00 UTC",
+            })
+            .unwrap();
+
+        assert!(email.html_body.contains("&lt;script&gt;"));
+        assert!(email.text_body.contains("<script>"));
+        assert!(!email.text_body.contains("From IP address"));
+    }
+
+    #[test]
+    fn test_render_uses_override_from_disk() {
+        let dir = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        std::fs::create_dir_all(dir.join("en")).unwrap();
+        std::fs::write(
+            dir.join("en/two_fa_code.txt"),
+            "{% block subject %}Code {{ code }}{% endblock %}{% block body %}Use {{ code }}{% endblock %}",
+        )
+        .unwrap();
+
+        let templates = EmailTemplates::new(Some(dir.clone())).unwrap();
+        let email = templates
+            .render(EmailTemplate::TwoFACode, Locale::En, context! { code => "123456", expires_in_minutes => 10 })
+            .unwrap();
+
+        assert_eq!(email.subject, "Code 123456");
+        assert_eq!(email.text_body, "Use 123456");
+        // The HTML part was not overridden and still uses the built-in template.
+        assert!(email.html_body.contains("Your login code"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_broken_override_fails_at_startup() {
+        let dir = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        std::fs::create_dir_all(dir.join("es")).unwrap();
+        std::fs::write(dir.join("es/verification.html"), "{% block content %}").unwrap();
+
+        assert!(EmailTemplates::new(Some(dir.clone())).is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
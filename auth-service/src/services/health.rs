@@ -0,0 +1,190 @@
+use std::{
+    collections::BTreeMap,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use color_eyre::eyre::{eyre, Context, Result};
+use redis::aio::ConnectionManager;
+use sqlx::{Connection as _, PgPool};
+use tokio::task::JoinSet;
+
+use crate::{app_state::HealthCheckType, domain::HealthCheck};
+
+/// Tracks whether the service can take traffic: every dependency answers
+/// within the timeout and the service is not shutting down.
+pub struct Health {
+    checks: Vec<HealthCheckType>,
+    timeout: Duration,
+    shutting_down: AtomicBool,
+}
+
+impl Health {
+    pub fn new(checks: Vec<HealthCheckType>, timeout: Duration) -> Self {
+        Self {
+            checks,
+            timeout,
+            shutting_down: AtomicBool::new(false),
+        }
+    }
+
+    /// Makes readiness fail so load balancers stop routing new requests here.
+    pub fn begin_shutdown(&self) {
+        self.shutting_down.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::SeqCst)
+    }
+
+    /// Runs every check concurrently and returns the outcome by dependency
+    /// name.
+    #[tracing::instrument(name = "Check dependencies", skip_all)]
+    pub async fn check_dependencies(&self) -> BTreeMap<&'static str, Result<()>> {
+        let mut checks = JoinSet::new();
+
+        for check in &self.checks {
+            let check = check.clone();
+            let timeout = self.timeout;
+
+            checks.spawn(async move {
+                let outcome = match tokio::time::timeout(timeout, check.check()).await {
+                    Ok(outcome) => outcome,
+                    Err(_) => Err(eyre!("timed out after {:?}", timeout)),
+                };
+                (check.name(), outcome)
+            });
+        }
+
+        let mut outcomes = BTreeMap::new();
+
+        while let Some(joined) = checks.join_next().await {
+            match joined {
+                Ok((name, outcome)) => {
+                    if let Err(e) = &outcome {
+                        tracing::warn!("{} health check failed: {:?}", name, e);
+                    }
+                    outcomes.insert(name, outcome);
+                }
+                Err(e) => tracing::error!("health check task failed: {:?}", e),
+            }
+        }
+
+        outcomes
+    }
+}
+
+pub struct PostgresHealthCheck {
+    pool: PgPool,
+}
+
+impl PostgresHealthCheck {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl HealthCheck for PostgresHealthCheck {
+    fn name(&self) -> &'static str {
+        "postgres"
+    }
+
+    async fn check(&self) -> Result<()> {
+        let mut conn = self.pool.acquire().await.wrap_err("failed to acquire a Postgres connection")?;
+        conn.ping().await.wrap_err("failed to ping Postgres")
+    }
+}
+
+pub struct RedisHealthCheck {
+    conn: ConnectionManager,
+}
+
+impl RedisHealthCheck {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl HealthCheck for RedisHealthCheck {
+    fn name(&self) -> &'static str {
+        "redis"
+    }
+
+    async fn check(&self) -> Result<()> {
+        let mut conn = self.conn.clone();
+
+        redis::cmd("PING")
+            .query_async::<_, String>(&mut conn)
+            .await
+            .wrap_err("failed to ping Redis")?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    struct StubCheck {
+        name: &'static str,
+        delay: Duration,
+        healthy: bool,
+    }
+
+    #[async_trait::async_trait]
+    impl HealthCheck for StubCheck {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        async fn check(&self) -> Result<()> {
+            tokio::time::sleep(self.delay).await;
+
+            if self.healthy {
+                Ok(())
+            } else {
+                Err(eyre!("connection refused"))
+            }
+        }
+    }
+
+    fn stub(name: &'static str, delay: Duration, healthy: bool) -> HealthCheckType {
+        Arc::new(StubCheck { name, delay, healthy })
+    }
+
+    #[tokio::test]
+    async fn check_dependencies_reports_each_dependency() {
+        let health = Health::new(
+            vec![stub("postgres", Duration::ZERO, true), stub("redis", Duration::ZERO, false)],
+            Duration::from_secs(1));
+
+        let outcomes = health.check_dependencies().await;
+
+        assert!(outcomes["postgres"].is_ok());
+        assert!(outcomes["redis"].as_ref().unwrap_err().to_string().contains("connection refused"));
+    }
+
+    #[tokio::test]
+    async fn slow_dependency_times_out() {
+        let health = Health::new(vec![stub("redis", Duration::from_secs(60), true)], Duration::from_millis(50));
+
+        let outcomes = health.check_dependencies().await;
+
+        assert!(outcomes["redis"].as_ref().unwrap_err().to_string().contains("timed out"));
+    }
+
+    #[test]
+    fn begin_shutdown_is_remembered() {
+        let health = Health::new(vec![], Duration::from_secs(1));
+        assert!(!health.is_shutting_down());
+
+        health.begin_shutdown();
+
+        assert!(health.is_shutting_down());
+    }
+}
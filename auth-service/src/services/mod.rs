@@ -1,4 +1,16 @@
+pub mod account_status_cache;
 pub mod data_stores;
+pub mod email_outbox_worker;
+pub mod email_templates;
+pub mod health;
+pub mod password_hasher;
+pub mod user_import;
 
 
-pub use data_stores::*;
\ No newline at end of file
+pub use account_status_cache::*;
+pub use data_stores::*;
+pub use email_outbox_worker::*;
+pub use email_templates::*;
+pub use health::*;
+pub use password_hasher::*;
+pub use user_import::*;
@@ -0,0 +1,274 @@
+use std::sync::Arc;
+
+use argon2::{
+    password_hash::SaltString, Algorithm, Argon2,
+    Params, PasswordHash, PasswordHasher, PasswordVerifier,
+    Version
+};
+use color_eyre::eyre::{eyre, Context, Result};
+use secrecy::{ExposeSecret, Secret};
+
+use crate::utils::settings::PasswordHashingSettings;
+
+/// Checks passwords against one family of stored hashes.
+pub trait PasswordHashVerifier: Send + Sync {
+    fn verify(&self, password: &[u8], password_hash: &str) -> Result<()>;
+}
+
+/// Hashes passwords with Argon2id using the configured cost parameters.
+/// Hashes made with other parameters still verify, since the PHC string
+/// carries its own, and can be upgraded with [`Argon2Hasher::needs_rehash`].
+#[derive(Clone)]
+pub struct Argon2Hasher {
+    params: Params,
+}
+
+impl Argon2Hasher {
+    pub fn new(settings: &PasswordHashingSettings) -> Result<Self> {
+        let params = Params::new(settings.memory_kib, settings.iterations, settings.parallelism, None)
+            .map_err(|e| eyre!("invalid Argon2 parameters: {}", e))?;
+
+        Ok(Self { params })
+    }
+
+    fn argon2(&self) -> Argon2<'static> {
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, self.params.clone())
+    }
+
+    #[tracing::instrument(name = "Computing password hash", skip_all)]
+    pub async fn hash(&self, password: Secret<String>) -> Result<Secret<String>> {
+        let current_span: tracing::Span = tracing::Span::current();
+        let argon2 = self.argon2();
+
+        let result = tokio::task::spawn_blocking(move || {
+            current_span.in_scope(|| {
+                let salt = SaltString::generate(&mut rand::thread_rng());
+
+                let password_hash = argon2
+                    .hash_password(password.expose_secret().as_bytes(), &salt)?
+                    .to_string();
+                Ok(Secret::new(password_hash))
+            })
+        }).await;
+
+        result?
+    }
+
+    /// Whether a stored hash was made with a different algorithm, version or
+    /// cost than new hashes get.
+    pub fn needs_rehash(&self, password_hash: &Secret<String>) -> bool {
+        let Ok(password_hash) = PasswordHash::new(password_hash.expose_secret()) else {
+            return true;
+        };
+
+        let Ok(params) = Params::try_from(&password_hash) else {
+            return true;
+        };
+
+        password_hash.algorithm != Algorithm::Argon2id.ident()
+            || password_hash.version != Some(Version::V0x13.into())
+            || params.m_cost() != self.params.m_cost()
+            || params.t_cost() != self.params.t_cost()
+            || params.p_cost() != self.params.p_cost()
+    }
+}
+
+impl PasswordHashVerifier for Argon2Hasher {
+    fn verify(&self, password: &[u8], password_hash: &str) -> Result<()> {
+        self.argon2()
+            .verify_password(password, &PasswordHash::new(password_hash)?)
+            .wrap_err("failed to verify password hash")
+    }
+}
+
+/// Modular crypt bcrypt hashes, e.g. `$2b$12$...`.
+pub struct BcryptVerifier;
+
+impl PasswordHashVerifier for BcryptVerifier {
+    fn verify(&self, password: &[u8], password_hash: &str) -> Result<()> {
+        if bcrypt::verify(password, password_hash).wrap_err("failed to verify bcrypt hash")? {
+            Ok(())
+        } else {
+            Err(eyre!("password does not match bcrypt hash"))
+        }
+    }
+}
+
+/// PHC PBKDF2 hashes, e.g. `$pbkdf2-sha256$i=...`.
+pub struct Pbkdf2Verifier;
+
+impl PasswordHashVerifier for Pbkdf2Verifier {
+    fn verify(&self, password: &[u8], password_hash: &str) -> Result<()> {
+        pbkdf2::Pbkdf2
+            .verify_password(password, &PasswordHash::new(password_hash)?)
+            .wrap_err("failed to verify PBKDF2 hash")
+    }
+}
+
+/// PHC scrypt hashes, e.g. `$scrypt$ln=...`.
+pub struct ScryptVerifier;
+
+impl PasswordHashVerifier for ScryptVerifier {
+    fn verify(&self, password: &[u8], password_hash: &str) -> Result<()> {
+        scrypt::Scrypt
+            .verify_password(password, &PasswordHash::new(password_hash)?)
+            .wrap_err("failed to verify scrypt hash")
+    }
+}
+
+/// Picks a verifier by the prefix of the stored hash. New hashes are always
+/// Argon2id, so anything else is replaced after the next successful login.
+#[derive(Clone)]
+pub struct PasswordHasherRegistry {
+    argon2: Argon2Hasher,
+    verifiers: Vec<(&'static str, Arc<dyn PasswordHashVerifier>)>,
+}
+
+impl PasswordHasherRegistry {
+    /// Registers Argon2 and the legacy bcrypt, PBKDF2 and scrypt formats.
+    pub fn new(argon2: Argon2Hasher) -> Self {
+        let registry = Self { argon2: argon2.clone(), verifiers: Vec::new() }
+            .register("$argon2", argon2);
+
+        ["$2a$", "$2b$", "$2x$", "$2y$"].into_iter()
+            .fold(registry, |registry, prefix| registry.register(prefix, BcryptVerifier))
+            .register("$pbkdf2", Pbkdf2Verifier)
+            .register("$scrypt$", ScryptVerifier)
+    }
+
+    pub fn register(mut self, prefix: &'static str, verifier: impl PasswordHashVerifier + 'static) -> Self {
+        self.verifiers.push((prefix, Arc::new(verifier)));
+        self
+    }
+
+    fn verifier(&self, password_hash: &str) -> Option<Arc<dyn PasswordHashVerifier>> {
+        self.verifiers.iter()
+            .find(|(prefix, _)| password_hash.starts_with(prefix))
+            .map(|(_, verifier)| verifier.clone())
+    }
+
+    /// Whether a stored hash has a format some verifier understands.
+    pub fn supports(&self, password_hash: &Secret<String>) -> bool {
+        self.verifier(password_hash.expose_secret()).is_some()
+    }
+
+    pub async fn hash(&self, password: Secret<String>) -> Result<Secret<String>> {
+        self.argon2.hash(password).await
+    }
+
+    #[tracing::instrument(name = "Verify password hash", skip_all)]
+    pub async fn verify(&self, expected_password_hash: Secret<String>, password_candidate: Secret<String>) -> Result<()> {
+        let current_span: tracing::Span = tracing::Span::current();
+        let verifier = self.verifier(expected_password_hash.expose_secret())
+            .ok_or_else(|| eyre!("unsupported password hash format"))?;
+
+        let result = tokio::task::spawn_blocking(move || {
+            current_span.in_scope(|| {
+                verifier.verify(password_candidate.expose_secret().as_bytes(), expected_password_hash.expose_secret())
+            })
+        })
+        .await;
+
+        result?
+    }
+
+    pub fn needs_rehash(&self, password_hash: &Secret<String>) -> bool {
+        self.argon2.needs_rehash(password_hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hasher(memory_kib: u32, iterations: u32) -> Argon2Hasher {
+        Argon2Hasher::new(&PasswordHashingSettings { memory_kib, iterations, parallelism: 1 }).unwrap()
+    }
+
+    fn registry() -> PasswordHasherRegistry {
+        PasswordHasherRegistry::new(hasher(1024, 1))
+    }
+
+    fn password() -> Secret<String> {
+        Secret::new("password123".to_owned())
+    }
+
+    fn wrong_password() -> Secret<String> {
+        Secret::new("password321".to_owned())
+    }
+
+    #[tokio::test]
+    async fn hash_made_with_current_params_is_kept() {
+        let registry = registry();
+        let password_hash = registry.hash(password()).await.unwrap();
+
+        assert!(registry.verify(password_hash.clone(), password()).await.is_ok());
+        assert!(!registry.needs_rehash(&password_hash));
+    }
+
+    #[tokio::test]
+    async fn hash_made_with_other_params_verifies_and_needs_rehash() {
+        let old_hash = hasher(1024, 1).hash(password()).await.unwrap();
+
+        for registry in [PasswordHasherRegistry::new(hasher(2048, 1)), PasswordHasherRegistry::new(hasher(1024, 2))] {
+            assert!(registry.verify(old_hash.clone(), password()).await.is_ok());
+            assert!(registry.needs_rehash(&old_hash));
+        }
+    }
+
+    #[tokio::test]
+    async fn hash_made_with_another_algorithm_needs_rehash() {
+        let salt = SaltString::generate(&mut rand::thread_rng());
+        let argon2i_hash = Argon2::new(Algorithm::Argon2i, Version::V0x13, Params::new(1024, 1, 1, None).unwrap())
+            .hash_password(password().expose_secret().as_bytes(), &salt)
+            .unwrap()
+            .to_string();
+
+        assert!(hasher(1024, 1).needs_rehash(&Secret::new(argon2i_hash)));
+    }
+
+    #[tokio::test]
+    async fn wrong_password_is_rejected() {
+        let registry = registry();
+        let password_hash = registry.hash(password()).await.unwrap();
+
+        assert!(registry.verify(password_hash, wrong_password()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn legacy_hashes_verify_and_need_rehash() {
+        let registry = registry();
+        let salt = SaltString::generate(&mut rand::thread_rng());
+        let legacy_hashes = [
+            bcrypt::hash(password().expose_secret(), 4).unwrap(),
+            pbkdf2::Pbkdf2.hash_password_customized(
+                password().expose_secret().as_bytes(), Some(pbkdf2::Algorithm::Pbkdf2Sha256.ident()), None,
+                pbkdf2::Params { rounds: 1000, output_length: 32 }, &salt).unwrap().to_string(),
+            scrypt::Scrypt.hash_password_customized(
+                password().expose_secret().as_bytes(), None, None,
+                scrypt::Params::new(4, 8, 1, 32).unwrap(), &salt).unwrap().to_string(),
+        ];
+
+        for legacy_hash in legacy_hashes.map(Secret::new) {
+            assert!(registry.supports(&legacy_hash), "unsupported: {}", legacy_hash.expose_secret());
+            assert!(registry.verify(legacy_hash.clone(), password()).await.is_ok());
+            assert!(registry.verify(legacy_hash.clone(), wrong_password()).await.is_err());
+            assert!(registry.needs_rehash(&legacy_hash));
+        }
+    }
+
+    #[tokio::test]
+    async fn unknown_hash_format_is_rejected() {
+        let registry = registry();
+        let password_hash = Secret::new("$1$saltsalt$abcdefghijklmnopqrstuv".to_owned());
+
+        assert!(!registry.supports(&password_hash));
+        assert!(registry.verify(password_hash, password()).await.is_err());
+    }
+
+    #[test]
+    fn invalid_params_are_rejected() {
+        let settings = PasswordHashingSettings { memory_kib: 1, iterations: 1, parallelism: 1 };
+        assert!(Argon2Hasher::new(&settings).is_err());
+    }
+}
@@ -0,0 +1,151 @@
+use std::{io::Read, path::Path};
+
+use color_eyre::eyre::{eyre, Context, Result};
+use secrecy::Secret;
+use serde::Deserialize;
+
+use crate::{
+    domain::{Email, Locale, UserStoreError},
+    services::PostgresUserStore,
+};
+
+/// One user exported from another system.
+#[derive(Debug, Deserialize)]
+pub struct ImportedUser {
+    pub email: Secret<String>,
+    /// bcrypt, PBKDF2, scrypt or Argon2 hash, in PHC or modular crypt format.
+    pub password_hash: Secret<String>,
+    /// Off when missing or empty.
+    #[serde(default)]
+    pub requires_2fa: Option<bool>,
+    #[serde(default)]
+    pub locale: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum ImportFormat {
+    /// Comma separated with a header row: email,password_hash,requires_2fa,locale
+    Csv,
+    /// One JSON object per line with the same fields.
+    Jsonl,
+}
+
+impl ImportFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "csv" => Some(Self::Csv),
+            "jsonl" | "ndjson" => Some(Self::Jsonl),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub imported: usize,
+    /// Users whose email is already registered. They are left untouched.
+    pub skipped: usize,
+    /// One entry per record that could not be imported, by record number.
+    /// Emails are left out so the report can be logged.
+    pub failed: Vec<String>,
+}
+
+/// Parses every record. A malformed record comes back as an error of its own
+/// so it does not stop the rest of the file from being imported.
+pub fn read_users(format: ImportFormat, mut reader: impl Read) -> Result<Vec<Result<ImportedUser>>> {
+    match format {
+        ImportFormat::Csv => Ok(csv::Reader::from_reader(reader)
+            .deserialize()
+            .map(|record| record.wrap_err("malformed CSV record"))
+            .collect()),
+        ImportFormat::Jsonl => {
+            let mut content = String::new();
+            reader.read_to_string(&mut content).wrap_err("failed to read JSONL")?;
+
+            Ok(content.lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| serde_json::from_str(line).wrap_err("malformed JSON record"))
+                .collect())
+        }
+    }
+}
+
+#[tracing::instrument(name = "Importing users", skip_all)]
+pub async fn import_users(user_store: &PostgresUserStore, format: ImportFormat, reader: impl Read) -> Result<ImportReport> {
+    let mut report = ImportReport::default();
+
+    for (index, user) in read_users(format, reader)?.into_iter().enumerate() {
+        let record = index + 1;
+
+        match import_user(user_store, user).await {
+            Ok(()) => report.imported += 1,
+            Err(ImportError::AlreadyExists) => report.skipped += 1,
+            Err(ImportError::Invalid(e)) => report.failed.push(format!("record {}: {:#}", record, e)),
+        }
+    }
+
+    Ok(report)
+}
+
+enum ImportError {
+    AlreadyExists,
+    Invalid(color_eyre::Report),
+}
+
+async fn import_user(user_store: &PostgresUserStore, user: Result<ImportedUser>) -> Result<(), ImportError> {
+    let user = user.map_err(ImportError::Invalid)?;
+    let email = Email::parse(user.email).map_err(ImportError::Invalid)?;
+    let locale = match user.locale.as_deref().filter(|tag| !tag.is_empty()) {
+        Some(tag) => Some(Locale::parse(tag).ok_or_else(|| ImportError::Invalid(eyre!("unsupported locale {:?}", tag)))?),
+        None => None,
+    };
+
+    match user_store.import_user(&email, &user.password_hash, user.requires_2fa.unwrap_or_default(), locale).await {
+        Ok(()) => Ok(()),
+        Err(UserStoreError::UserAlreadyExists) => Err(ImportError::AlreadyExists),
+        Err(e) => Err(ImportError::Invalid(e.into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::ExposeSecret;
+
+    use super::*;
+
+    #[test]
+    fn csv_records_are_read_with_defaults() {
+        let csv = "email,password_hash,requires_2fa,locale\n\
+            one@example.com,$2b$04$hash,true,es\n\
+            two@example.com,$2b$04$hash,,\n";
+
+        let users = read_users(ImportFormat::Csv, csv.as_bytes()).unwrap();
+
+        let users: Vec<ImportedUser> = users.into_iter().map(Result::unwrap).collect();
+        assert_eq!(users[0].email.expose_secret(), "one@example.com");
+        assert_eq!(users[0].requires_2fa, Some(true));
+        assert_eq!(users[0].locale.as_deref(), Some("es"));
+        assert_eq!(users[1].requires_2fa, None);
+        assert_eq!(users[1].locale, None);
+    }
+
+    #[test]
+    fn malformed_jsonl_records_are_reported_individually() {
+        let jsonl = "{\"email\":\"one@example.com\",\"password_hash\":\"$2b$04$hash\"}\n\
+            \n\
+            {\"email\":\"two@example.com\"}\n";
+
+        let users = read_users(ImportFormat::Jsonl, jsonl.as_bytes()).unwrap();
+
+        assert_eq!(users.len(), 2);
+        assert!(users[0].is_ok());
+        assert!(users[1].is_err());
+    }
+
+    #[test]
+    fn format_is_picked_from_the_extension() {
+        assert_eq!(ImportFormat::from_path(Path::new("users.csv")), Some(ImportFormat::Csv));
+        assert_eq!(ImportFormat::from_path(Path::new("users.jsonl")), Some(ImportFormat::Jsonl));
+        assert_eq!(ImportFormat::from_path(Path::new("users.txt")), None);
+    }
+}
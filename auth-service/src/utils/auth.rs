@@ -1,17 +1,25 @@
-use axum_extra::extract::cookie::{Cookie, SameSite};
+use std::marker::PhantomData;
+
+use axum::{extract::FromRequestParts, http::{header::AUTHORIZATION, request::Parts}};
+use axum_extra::extract::{cookie::{Cookie, SameSite}, CookieJar};
 use chrono::Utc;
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Validation};
 use serde::{Deserialize, Serialize};
 use color_eyre::eyre::{eyre, Context, ContextCompat, Result};
 use secrecy::{ExposeSecret, Secret};
+use uuid::Uuid;
 
-use crate::{app_state::BannedTokenStoreType, domain::email::Email};
+use crate::{
+    app_state::{AppState, BannedTokenStoreType},
+    domain::{email::Email, Access, AccountStatus, AuthAPIError, TrustedDevice, USERS_ADMIN_PERMISSION, USERS_READ_PERMISSION},
+    services::AccountStatusCache
+};
 
-use super::constants::{JWT_COOKIE_NAME, JWT_SECRET};
+use super::constants::{JWT_COOKIE_NAME, TRUSTED_DEVICE_COOKIE_NAME};
 
 #[tracing::instrument(name= "Generate an auth cookie", skip_all)]
-pub fn generate_auth_cookie(email: &Email) -> Result<Cookie<'static>> {
-    let token = generate_auth_token(email)?;
+pub fn generate_auth_cookie(email: &Email, access: &Access, epoch: u64, jwt_secret: &Secret<String>) -> Result<Cookie<'static>> {
+    let token = generate_auth_token(email, access, epoch, jwt_secret)?;
     Ok(create_auth_cookie(token))
 }
 
@@ -35,7 +43,7 @@ pub enum GenerateTokenError {
 pub const TOKEN_TTL_SECONDS: i64 = 600;
 
 #[tracing::instrument(name= "Generate an auth token", skip_all)]
-fn generate_auth_token(email: &Email) -> Result<Secret<String>> {
+fn generate_auth_token(email: &Email, access: &Access, epoch: u64, jwt_secret: &Secret<String>) -> Result<Secret<String>> {
     let delta = chrono::Duration::try_seconds(TOKEN_TTL_SECONDS)
             .wrap_err("failed to create 10 minute time delta")?;
 
@@ -50,16 +58,28 @@ fn generate_auth_token(email: &Email) -> Result<Secret<String>> {
 
     let sub = email.as_ref().expose_secret().to_owned();
 
-    let claims = Claims {sub, exp};
+    let claims = Claims {
+        sub,
+        exp,
+        roles: access.roles.clone(),
+        permissions: access.permissions.clone(),
+        epoch
+    };
 
-    create_token(&claims)
+    create_token(&claims, jwt_secret)
 }
 
+/// Checks the signature, expiry, ban list and session epoch of a token, and
+/// the user's account status when `account_status_cache` is given.
 #[tracing::instrument(name= "Validate an auth token", skip_all)]
-pub async fn validate_token(token: &Secret<String>, banned_token_store: BannedTokenStoreType) -> Result<Claims> {
-
-    let banned_tk_store = &banned_token_store.read().await;
-    match banned_tk_store.check_banned_token(token.to_owned()).await {
+pub async fn validate_token(
+    token: &Secret<String>,
+    banned_token_store: BannedTokenStoreType,
+    account_status_cache: Option<&AccountStatusCache>,
+    jwt_secret: &Secret<String>
+) -> Result<Claims> {
+
+    match banned_token_store.check_banned_token(token.to_owned()).await {
         Ok(value) => {
             if value {
                 return Err(eyre!("token is banned"));
@@ -68,49 +88,248 @@ pub async fn validate_token(token: &Secret<String>, banned_token_store: BannedTo
         Err(e) => return Err(e.into()),
     };
 
-    decode::<Claims>(
+    let claims = decode::<Claims>(
         token.expose_secret().as_ref(),
-        &DecodingKey::from_secret(JWT_SECRET.expose_secret().as_bytes()),
+        &DecodingKey::from_secret(jwt_secret.expose_secret().as_bytes()),
         &Validation::default(),
     )
     .map(|data| data.claims)
-    .wrap_err("failed to decode token")
+    .wrap_err("failed to decode token")?;
+
+    let email = Email::parse(Secret::new(claims.sub.clone()))?;
+    if claims.epoch != banned_token_store.session_epoch(&email).await? {
+        return Err(eyre!("token belongs to a revoked session"));
+    }
+
+    if let Some(account_status_cache) = account_status_cache {
+        if account_status_cache.status(&email).await? != AccountStatus::Active {
+            return Err(eyre!("account is not active"));
+        }
+    }
+
+    Ok(claims)
 }
 
 #[tracing::instrument(name= "Create a json web token", skip_all)]
-fn create_token(claims: &Claims) -> Result<Secret<String>> {
+fn create_token(claims: &Claims, jwt_secret: &Secret<String>) -> Result<Secret<String>> {
     encode(
         &jsonwebtoken::Header::default(),
         &claims,
-        &EncodingKey::from_secret(JWT_SECRET.expose_secret().as_bytes()),
+        &EncodingKey::from_secret(jwt_secret.expose_secret().as_bytes()),
     )
     .map(Secret::new)
     .wrap_err("failed to create token")
 }
 
+/// Roles and permissions are copied in when the token is minted, so a
+/// change to them applies from the user's next login.
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct Claims {
     pub sub: String,
+    pub exp: usize,
+    #[serde(default)]
+    pub roles: Vec<String>,
+    #[serde(default)]
+    pub permissions: Vec<String>,
+    /// The user's session epoch when the token was minted. Revoking the
+    /// user's sessions moves the epoch on and invalidates the token.
+    #[serde(default)]
+    pub epoch: u64
+}
+
+/// Reads the JWT cookie and returns the claims of the signed-in user.
+#[tracing::instrument(name = "Get authenticated claims", skip_all)]
+pub async fn authenticated_claims(jar: &CookieJar, state: &AppState) -> Result<Claims, AuthAPIError> {
+    let cookie = jar.get(JWT_COOKIE_NAME).ok_or(AuthAPIError::MissingToken)?;
+    let token = Secret::new(cookie.value().to_owned());
+
+    validate_token(
+        &token,
+        state.banned_token_store.clone(),
+        state.account_status_cache.as_deref(),
+        &state.settings.auth.jwt_secret
+    )
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)
+}
+
+/// Reads the JWT cookie and returns the email of the signed-in user.
+#[tracing::instrument(name = "Get authenticated email", skip_all)]
+pub async fn authenticated_email(jar: &CookieJar, state: &AppState) -> Result<Email, AuthAPIError> {
+    let claims = authenticated_claims(jar, state).await?;
+
+    Email::parse(Secret::new(claims.sub)).map_err(|_| AuthAPIError::InvalidToken)
+}
+
+/// A permission that can guard a route through [`RequirePermission`].
+pub trait Permission {
+    const NAME: &'static str;
+}
+
+pub struct UsersRead;
+
+impl Permission for UsersRead {
+    const NAME: &'static str = USERS_READ_PERMISSION;
+}
+
+pub struct UsersAdmin;
+
+impl Permission for UsersAdmin {
+    const NAME: &'static str = USERS_ADMIN_PERMISSION;
+}
+
+/// Extractor that only lets through signed-in users whose token grants `P`,
+/// e.g. `RequirePermission<UsersAdmin>` for `users:admin`. Requests without
+/// a valid token are rejected as for any authenticated route, and valid
+/// tokens without the permission get a 403.
+pub struct RequirePermission<P: Permission> {
+    pub claims: Claims,
+    permission: PhantomData<P>
+}
+
+#[async_trait::async_trait]
+impl<P: Permission> FromRequestParts<AppState> for RequirePermission<P> {
+    type Rejection = AuthAPIError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let jar = CookieJar::from_headers(&parts.headers);
+        let claims = authenticated_claims(&jar, state).await?;
+
+        if !claims.permissions.iter().any(|permission| permission == P::NAME) {
+            return Err(AuthAPIError::Forbidden);
+        }
+
+        Ok(Self { claims, permission: PhantomData })
+    }
+}
+
+/// Recorded as the actor of admin actions taken with the admin API key.
+pub const ADMIN_API_KEY_ACTOR: &str = "api-key";
+
+/// Extractor for the admin API. Scripts send the admin API key as an
+/// `Authorization: Bearer` header, and anyone else needs a token granting
+/// `P` as for [`RequirePermission`]. `actor` names the caller in the audit
+/// log.
+pub struct AdminActor<P: Permission> {
+    pub actor: String,
+    permission: PhantomData<P>
+}
+
+#[async_trait::async_trait]
+impl<P: Permission> FromRequestParts<AppState> for AdminActor<P> {
+    type Rejection = AuthAPIError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let Some(authorization) = parts.headers.get(AUTHORIZATION) else {
+            let RequirePermission::<P> { claims, .. } = RequirePermission::from_request_parts(parts, state).await?;
+            return Ok(Self { actor: claims.sub, permission: PhantomData });
+        };
+
+        let key = authorization.to_str().ok()
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or(AuthAPIError::InvalidToken)?;
+
+        match &state.settings.admin.api_key {
+            Some(api_key) if constant_time_eq(key.as_bytes(), api_key.expose_secret().as_bytes()) => {
+                Ok(Self { actor: ADMIN_API_KEY_ACTOR.to_owned(), permission: PhantomData })
+            }
+            _ => Err(AuthAPIError::InvalidToken)
+        }
+    }
+}
+
+/// Compares every byte so the time taken does not reveal how much of a
+/// guessed key was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |difference, (x, y)| difference | (x ^ y)) == 0
+}
+
+pub const TRUSTED_DEVICE_TTL_DAYS: i64 = 30;
+const TRUSTED_DEVICE_AUDIENCE: &str = "trusted-device";
+
+/// Claims of the long-lived cookie that lets a browser skip 2FA. The
+/// audience keeps it from being accepted as an auth token.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct TrustedDeviceClaims {
+    pub sub: String,
+    pub jti: Uuid,
+    pub aud: String,
     pub exp: usize
 }
 
+#[tracing::instrument(name = "Generate a trusted device cookie", skip_all)]
+pub fn generate_trusted_device_cookie(
+    email: &Email,
+    device: &TrustedDevice,
+    jwt_secret: &Secret<String>
+) -> Result<Cookie<'static>> {
+    let exp: usize = device.expires_at
+        .timestamp()
+        .try_into()
+        .wrap_err("failed to cast trusted device expiry to usize")?;
+
+    let claims = TrustedDeviceClaims {
+        sub: email.as_ref().expose_secret().to_owned(),
+        jti: device.id,
+        aud: TRUSTED_DEVICE_AUDIENCE.to_owned(),
+        exp
+    };
+
+    let token = encode(
+        &jsonwebtoken::Header::default(),
+        &claims,
+        &EncodingKey::from_secret(jwt_secret.expose_secret().as_bytes()),
+    )
+    .wrap_err("failed to create trusted device token")?;
+
+    let cookie = Cookie::build((TRUSTED_DEVICE_COOKIE_NAME, token))
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::days(TRUSTED_DEVICE_TTL_DAYS))
+        .build();
+
+    Ok(cookie)
+}
+
+#[tracing::instrument(name = "Validate a trusted device token", skip_all)]
+pub fn validate_trusted_device_token(token: &Secret<String>, jwt_secret: &Secret<String>) -> Result<TrustedDeviceClaims> {
+    let mut validation = Validation::default();
+    validation.set_audience(&[TRUSTED_DEVICE_AUDIENCE]);
+
+    decode::<TrustedDeviceClaims>(
+        token.expose_secret(),
+        &DecodingKey::from_secret(jwt_secret.expose_secret().as_bytes()),
+        &validation,
+    )
+    .map(|data| data.claims)
+    .wrap_err("failed to decode trusted device token")
+}
+
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
 
     use secrecy::Secret;
-    use tokio::sync::RwLock;
 
     use crate::services;
 
-    use crate::domain::data_stores::BannedTokenStore;
+    use crate::domain::data_stores::{BannedTokenStore, UserStore};
 
     use super::*;
 
+    fn jwt_secret() -> Secret<String> {
+        Secret::new("secret".to_owned())
+    }
+
+    fn no_access() -> Access {
+        Access::default()
+    }
+
     #[tokio::test]
     async fn test_generate_auth_cookie() {
         let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
-        let cookie = generate_auth_cookie(&email).unwrap();
+        let cookie = generate_auth_cookie(&email, &no_access(), 0, &jwt_secret()).unwrap();
         assert_eq!(cookie.name(), JWT_COOKIE_NAME);
         assert_eq!(cookie.value().split('.').count(), 3);
         assert_eq!(cookie.path(), Some("/"));
@@ -132,16 +351,16 @@ mod tests {
     #[tokio::test]
     async fn test_generate_auth_token() {
         let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
-        let result = generate_auth_token(&email).unwrap();
+        let result = generate_auth_token(&email, &no_access(), 0, &jwt_secret()).unwrap();
         assert_eq!(result.expose_secret().split('.').count(), 3);
     }
 
     #[tokio::test]
     async fn test_validate_token_with_valid_token() {
         let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
-        let token = generate_auth_token(&email).unwrap();
-        let banned_token_store = Arc::new(RwLock::new(services::HashsetBannedTokenStore::default()));
-        let result = validate_token(&token, banned_token_store).await.unwrap();
+        let token = generate_auth_token(&email, &no_access(), 0, &jwt_secret()).unwrap();
+        let banned_token_store = Arc::new(services::HashsetBannedTokenStore::default());
+        let result = validate_token(&token, banned_token_store, None, &jwt_secret()).await.unwrap();
         assert_eq!(result.sub, "test@example.com");
 
         let exp = Utc::now()
@@ -152,27 +371,125 @@ mod tests {
         assert!(result.exp > exp as usize);
     }
 
+    #[tokio::test]
+    async fn test_token_carries_roles_and_permissions() {
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let access = Access {
+            roles: vec!["admin".to_owned()],
+            permissions: vec![USERS_ADMIN_PERMISSION.to_owned(), USERS_READ_PERMISSION.to_owned()]
+        };
+        let token = generate_auth_token(&email, &access, 0, &jwt_secret()).unwrap();
+        let banned_token_store = Arc::new(services::HashsetBannedTokenStore::default());
+
+        let claims = validate_token(&token, banned_token_store, None, &jwt_secret()).await.unwrap();
+        assert_eq!(claims.roles, access.roles);
+        assert_eq!(claims.permissions, access.permissions);
+    }
+
+    #[tokio::test]
+    async fn test_token_without_roles_still_validates() {
+        let exp = (Utc::now().timestamp() + 60) as usize;
+        let token = encode(
+            &jsonwebtoken::Header::default(),
+            &serde_json::json!({ "sub": "test@example.com", "exp": exp }),
+            &EncodingKey::from_secret(jwt_secret().expose_secret().as_bytes()),
+        ).unwrap();
+        let banned_token_store = Arc::new(services::HashsetBannedTokenStore::default());
+
+        let claims = validate_token(&Secret::new(token), banned_token_store, None, &jwt_secret()).await.unwrap();
+        assert!(claims.roles.is_empty());
+        assert!(claims.permissions.is_empty());
+    }
+
     #[tokio::test]
     async fn test_validate_token_with_invalid_token() {
         let token = Secret::new("invalid_token".to_owned());
-        let banned_token_store = Arc::new(RwLock::new(services::HashsetBannedTokenStore::default()));
-        let result = validate_token(&token, banned_token_store).await;
+        let banned_token_store = Arc::new(services::HashsetBannedTokenStore::default());
+        let result = validate_token(&token, banned_token_store, None, &jwt_secret()).await;
         assert!(result.is_err());
     }
 
     #[tokio::test]
     async fn test_validate_token_with_banned_token() {
         let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
-        let token = generate_auth_token(&email).unwrap();
-        let banned_token_store = Arc::new(RwLock::new(services::HashsetBannedTokenStore::default()));
+        let token = generate_auth_token(&email, &no_access(), 0, &jwt_secret()).unwrap();
+        let banned_token_store = Arc::new(services::HashsetBannedTokenStore::default());
 
-        {
-            let mut banned_tk_store = banned_token_store.write().await;
-            banned_tk_store.store_banned_token(token.clone()).await.unwrap();
-        }
+        banned_token_store.store_banned_token(token.clone()).await.unwrap();
+
+        let result = validate_token(&token, banned_token_store, None, &jwt_secret()).await.is_err();
+
+        assert!(result);
+    }
 
-        let result = validate_token(&token, banned_token_store).await.is_err();
+    #[tokio::test]
+    async fn test_validate_token_from_revoked_session() {
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let banned_token_store = Arc::new(services::HashsetBannedTokenStore::default());
+        let old_token = generate_auth_token(&email, &no_access(), 0, &jwt_secret()).unwrap();
+
+        banned_token_store.revoke_sessions(&email).await.unwrap();
+        let epoch = banned_token_store.session_epoch(&email).await.unwrap();
+        let new_token = generate_auth_token(&email, &no_access(), epoch, &jwt_secret()).unwrap();
+
+        assert!(validate_token(&old_token, banned_token_store.clone(), None, &jwt_secret()).await.is_err());
+        assert!(validate_token(&new_token, banned_token_store, None, &jwt_secret()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_of_suspended_user() {
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let user_store = Arc::new(services::HashmapUserStore::default());
+        let password = crate::domain::Password::parse(Secret::new("password".to_owned())).unwrap();
+        user_store.add_user(crate::domain::User::new(email.clone(), password, false)).await.unwrap();
+        let account_status_cache = services::AccountStatusCache::new(user_store.clone(), std::time::Duration::ZERO);
+        let banned_token_store = Arc::new(services::HashsetBannedTokenStore::default());
+        let token = generate_auth_token(&email, &no_access(), 0, &jwt_secret()).unwrap();
+
+        assert!(validate_token(&token, banned_token_store.clone(), Some(&account_status_cache), &jwt_secret()).await.is_ok());
+
+        user_store.set_status(&email, AccountStatus::Suspended, None).await.unwrap();
+        assert!(validate_token(&token, banned_token_store.clone(), Some(&account_status_cache), &jwt_secret()).await.is_err());
+        assert!(validate_token(&token, banned_token_store, None, &jwt_secret()).await.is_ok());
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"admin-key", b"admin-key"));
+        assert!(!constant_time_eq(b"admin-key", b"admin-kez"));
+        assert!(!constant_time_eq(b"admin-key", b"admin-key-2"));
+    }
+
+    #[tokio::test]
+    async fn test_validate_trusted_device_token() {
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let device = TrustedDevice::new(None, chrono::Duration::days(TRUSTED_DEVICE_TTL_DAYS));
+        let cookie = generate_trusted_device_cookie(&email, &device, &jwt_secret()).unwrap();
+
+        assert_eq!(cookie.name(), TRUSTED_DEVICE_COOKIE_NAME);
+        assert_eq!(cookie.http_only(), Some(true));
+
+        let claims = validate_trusted_device_token(&Secret::new(cookie.value().to_owned()), &jwt_secret()).unwrap();
+        assert_eq!(claims.sub, "test@example.com");
+        assert_eq!(claims.jti, device.id);
+    }
+
+    #[tokio::test]
+    async fn test_trusted_device_token_is_not_an_auth_token() {
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let device = TrustedDevice::new(None, chrono::Duration::days(TRUSTED_DEVICE_TTL_DAYS));
+        let cookie = generate_trusted_device_cookie(&email, &device, &jwt_secret()).unwrap();
+        let banned_token_store = Arc::new(services::HashsetBannedTokenStore::default());
+
+        let result = validate_token(&Secret::new(cookie.value().to_owned()), banned_token_store, None, &jwt_secret()).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_auth_token_is_not_a_trusted_device_token() {
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let token = generate_auth_token(&email, &no_access(), 0, &jwt_secret()).unwrap();
 
-        assert_eq!(result, true);
+        assert!(validate_trusted_device_token(&token, &jwt_secret()).is_err());
     }
 }
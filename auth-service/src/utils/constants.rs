@@ -1,56 +1,117 @@
-use dotenvy::dotenv;
-use lazy_static::lazy_static;
-use secrecy::Secret;
-use std::env as std_env;
+use std::time::Duration;
 
-lazy_static! {
-    pub static ref JWT_SECRET: Secret<String> = set_token();
-    pub static ref DATABASE_URL: Secret<String> = set_database_url();
-    pub static ref REDIS_HOST_NAME: String = set_redis_host();
-}
-
-fn set_token() -> Secret<String> {
-    dotenv().ok();
-
-    let secret = std_env::var(env::JWT_SECRET_ENV_VAR)
-        .expect("JWT_SECRET must be set");
-
-    if secret.is_empty() {
-        panic!("JWT_SECRET must not be empty");
-    }
-    Secret::new(secret)
-}
-
-fn set_database_url() -> Secret<String> {
-    dotenv().ok();
-
-    let database_url = std_env::var(env::DATABASE_URL_ENV_VAR)
-        .expect("DATABASE_URL must be set");
-
-    if database_url.is_empty() {
-        panic!("DATABASE_URL must not be empty");
-    }
-    Secret::new(database_url)
-}
-
-fn set_redis_host() -> String {
-    dotenv().ok();
-    std_env::var(env::REDIS_HOST_NAME_ENV_VAR)
-    .unwrap_or(DEFAULT_REDIS_HOSTNAME.to_owned())
-}
 pub mod env {
+    pub const CONFIG_FILE_ENV_VAR: &str = "AUTH_SERVICE_CONFIG";
+    pub const APP_ADDRESS_ENV_VAR: &str = "APP_ADDRESS";
+    pub const ALLOWED_ORIGINS_ENV_VAR: &str = "ALLOWED_ORIGINS";
+    pub const DRAIN_TIMEOUT_SECS_ENV_VAR: &str = "DRAIN_TIMEOUT_SECS";
+    pub const READINESS_GRACE_SECS_ENV_VAR: &str = "READINESS_GRACE_SECS";
+    pub const TRUST_FORWARDED_FOR_ENV_VAR: &str = "TRUST_FORWARDED_FOR";
     pub const JWT_SECRET_ENV_VAR: &str = "JWT_SECRET";
     pub const DATABASE_URL_ENV_VAR: &str = "DATABASE_URL";
+    pub const DATABASE_MAX_CONNECTIONS_ENV_VAR: &str = "DATABASE_MAX_CONNECTIONS";
     pub const REDIS_HOST_NAME_ENV_VAR: &str = "REDIS_HOST_NAME";
+    pub const WEBAUTHN_RP_ID_ENV_VAR: &str = "WEBAUTHN_RP_ID";
+    pub const WEBAUTHN_RP_ORIGIN_ENV_VAR: &str = "WEBAUTHN_RP_ORIGIN";
+    pub const EMAIL_CLIENT_ENV_VAR: &str = "EMAIL_CLIENT";
+    pub const EMAIL_SENDER_ENV_VAR: &str = "EMAIL_SENDER";
+    pub const SMTP_HOST_ENV_VAR: &str = "SMTP_HOST";
+    pub const SMTP_PORT_ENV_VAR: &str = "SMTP_PORT";
+    pub const SMTP_TLS_ENV_VAR: &str = "SMTP_TLS";
+    pub const SMTP_USERNAME_ENV_VAR: &str = "SMTP_USERNAME";
+    pub const SMTP_PASSWORD_ENV_VAR: &str = "SMTP_PASSWORD";
+    pub const POSTMARK_BASE_URL_ENV_VAR: &str = "POSTMARK_BASE_URL";
+    pub const POSTMARK_AUTH_TOKEN_ENV_VAR: &str = "POSTMARK_AUTH_TOKEN";
+    pub const EMAIL_TEMPLATES_DIR_ENV_VAR: &str = "EMAIL_TEMPLATES_DIR";
+    pub const SMS_CLIENT_ENV_VAR: &str = "SMS_CLIENT";
+    pub const SMS_SENDER_ENV_VAR: &str = "SMS_SENDER";
+    pub const TWILIO_BASE_URL_ENV_VAR: &str = "TWILIO_BASE_URL";
+    pub const TWILIO_ACCOUNT_SID_ENV_VAR: &str = "TWILIO_ACCOUNT_SID";
+    pub const TWILIO_AUTH_TOKEN_ENV_VAR: &str = "TWILIO_AUTH_TOKEN";
+    pub const OTLP_ENDPOINT_ENV_VAR: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+    pub const SERVICE_NAME_ENV_VAR: &str = "OTEL_SERVICE_NAME";
+    pub const LOG_FORMAT_ENV_VAR: &str = "LOG_FORMAT";
+    pub const PASSWORD_HASH_MEMORY_KIB_ENV_VAR: &str = "PASSWORD_HASH_MEMORY_KIB";
+    pub const PASSWORD_HASH_ITERATIONS_ENV_VAR: &str = "PASSWORD_HASH_ITERATIONS";
+    pub const PASSWORD_HASH_PARALLELISM_ENV_VAR: &str = "PASSWORD_HASH_PARALLELISM";
+    pub const PASSWORD_MIN_LENGTH_ENV_VAR: &str = "PASSWORD_MIN_LENGTH";
+    pub const PASSWORD_MAX_LENGTH_ENV_VAR: &str = "PASSWORD_MAX_LENGTH";
+    pub const PASSWORD_MIN_STRENGTH_ENV_VAR: &str = "PASSWORD_MIN_STRENGTH";
+    pub const PASSWORD_HISTORY_SIZE_ENV_VAR: &str = "PASSWORD_HISTORY_SIZE";
+    pub const BREACHED_PASSWORDS_DIR_ENV_VAR: &str = "BREACHED_PASSWORDS_DIR";
+    pub const PASSWORD_RESET_URL_ENV_VAR: &str = "PASSWORD_RESET_URL";
+    pub const ADMIN_API_KEY_ENV_VAR: &str = "ADMIN_API_KEY";
+    pub const TOKEN_STATUS_CHECK_ENV_VAR: &str = "TOKEN_STATUS_CHECK";
+    pub const ACCOUNT_STATUS_CACHE_SECS_ENV_VAR: &str = "ACCOUNT_STATUS_CACHE_SECS";
 }
 
 pub const JWT_COOKIE_NAME: &str = "jwt";
+pub const TRUSTED_DEVICE_COOKIE_NAME: &str = "trusted_device";
+pub const DEFAULT_APP_ADDRESS: &str = "0.0.0.0:3000";
+pub const DEFAULT_ALLOWED_ORIGINS: &[&str] = &["http://localhost:8000"];
+pub const DEFAULT_DRAIN_TIMEOUT_SECS: u64 = 30;
+pub const DEFAULT_READINESS_GRACE_SECS: u64 = 5;
+pub const DEFAULT_DATABASE_MAX_CONNECTIONS: u32 = 10;
 pub const DEFAULT_REDIS_HOSTNAME: &str = "127.0.0.1";
+pub const DEFAULT_WEBAUTHN_RP_ID: &str = "localhost";
+pub const DEFAULT_WEBAUTHN_RP_ORIGIN: &str = "http://localhost:3000";
+pub const DEFAULT_EMAIL_CLIENT: &str = "mock";
+pub const DEFAULT_EMAIL_SENDER: &str = "no-reply@localhost.com";
+pub const DEFAULT_SMTP_HOST: &str = "127.0.0.1";
+pub const DEFAULT_SMTP_PORT: u16 = 587;
+pub const DEFAULT_SMTP_TLS: &str = "starttls";
+pub const DEFAULT_POSTMARK_BASE_URL: &str = "https://api.postmarkapp.com";
+pub const DEFAULT_SMS_CLIENT: &str = "mock";
+pub const DEFAULT_TWILIO_BASE_URL: &str = "https://api.twilio.com";
+pub const DEFAULT_SERVICE_NAME: &str = "auth-service";
+pub const DEFAULT_LOG_FORMAT: &str = "text";
+pub const DEFAULT_PASSWORD_HASH_MEMORY_KIB: u32 = 15000;
+pub const DEFAULT_PASSWORD_HASH_ITERATIONS: u32 = 2;
+pub const DEFAULT_PASSWORD_HASH_PARALLELISM: u32 = 1;
+pub const DEFAULT_PASSWORD_MAX_LENGTH: usize = 128;
+pub const DEFAULT_PASSWORD_MIN_STRENGTH: u8 = 3;
+pub const DEFAULT_PASSWORD_HISTORY_SIZE: usize = 5;
+pub const DEFAULT_PASSWORD_RESET_URL: &str = "http://localhost:8000/reset-password";
+pub const MIN_ADMIN_API_KEY_LENGTH: usize = 32;
+pub const MIN_JWT_SECRET_LENGTH: usize = 32;
+/// Fragments of example secrets, matched case-insensitively, that must not
+/// reach a deployment.
+pub const JWT_SECRET_PLACEHOLDERS: &[&str] = &["change-me", "changeme", "replace-me", "your-secret", "example"];
+pub const DEFAULT_ACCOUNT_STATUS_CACHE_SECS: u64 = 30;
+pub const REDIS_RESPONSE_TIMEOUT: Duration = Duration::from_secs(2);
+pub const REDIS_CONNECTION_TIMEOUT: Duration = Duration::from_secs(2);
+pub const REDIS_RECONNECT_ATTEMPTS: usize = 6;
 
 pub mod prod {
-    pub const APP_ADDRESS: &str = "0.0.0.0:3000";
+    use std::time::Duration;
+
+    pub const SMTP_POOL_SIZE: u32 = 10;
+    pub const SMTP_TIMEOUT: Duration = Duration::from_secs(10);
+    pub const EMAIL_PROVIDER_TIMEOUT: Duration = Duration::from_secs(10);
+    pub const EMAIL_PROVIDER_MAX_RETRIES: u32 = 3;
+    pub const EMAIL_PROVIDER_RETRY_BASE_DELAY: Duration = Duration::from_millis(250);
+    pub const SMS_PROVIDER_TIMEOUT: Duration = Duration::from_secs(10);
+    pub const EMAIL_OUTBOX_BATCH_SIZE: u32 = 20;
+    pub const EMAIL_OUTBOX_POLL_INTERVAL: Duration = Duration::from_secs(1);
+    pub const EMAIL_OUTBOX_MAX_ATTEMPTS: u32 = 8;
+    pub const EMAIL_OUTBOX_RETRY_BASE_DELAY: Duration = Duration::from_secs(5);
+    pub const EMAIL_OUTBOX_MAX_RETRY_DELAY: Duration = Duration::from_secs(15 * 60);
+    pub const EMAIL_OUTBOX_LEASE: Duration = Duration::from_secs(5 * 60);
+    pub const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+    pub const DATABASE_CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+    pub const REDIS_CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
 }
 
 pub mod test {
+    use std::time::Duration;
+
     pub const APP_ADDRESS: &str = "127.0.0.1:0";
+    pub const ALLOWED_ORIGIN: &str = "http://localhost:8000";
+    pub const WEBAUTHN_RP_ID: &str = "localhost";
+    pub const WEBAUTHN_RP_ORIGIN: &str = "http://localhost:3000";
+    pub const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+    pub const DRAIN_TIMEOUT_SECS: u64 = 1;
+    pub const DATABASE_MAX_CONNECTIONS: u32 = 5;
+    pub const READINESS_GRACE_SECS: u64 = 1;
+    pub const ADMIN_API_KEY: &str = "test-admin-api-key-0123456789abcdef";
 }
@@ -0,0 +1,76 @@
+use std::{sync::OnceLock, time::Instant};
+
+use axum::{extract::{MatchedPath, Request}, middleware::Next, response::Response};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+
+/// Upper bounds, in seconds, of the latency histogram buckets.
+const LATENCY_BUCKETS: &[f64] = &[0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+static PROMETHEUS_HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+
+/// Installs the Prometheus recorder on first use. A process can only have
+/// one recorder, so every caller shares the same handle.
+pub fn prometheus_handle() -> PrometheusHandle {
+    PROMETHEUS_HANDLE
+        .get_or_init(|| {
+            PrometheusBuilder::new()
+                .set_buckets_for_metric(Matcher::Suffix("duration_seconds".to_owned()), LATENCY_BUCKETS)
+                .expect("Failed to set latency buckets")
+                .install_recorder()
+                .expect("Failed to install Prometheus recorder")
+        })
+        .clone()
+}
+
+/// Counts requests and records their latency by method, route and status.
+/// The route is the matched pattern, e.g. `/trusted-devices/:id`, to keep
+/// the number of series bounded.
+pub async fn track_http_metrics(request: Request, next: Next) -> Response {
+    let start = Instant::now();
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_owned())
+        .unwrap_or_else(|| "unmatched".to_owned());
+
+    let response = next.run(request).await;
+
+    let labels = [
+        ("method", method),
+        ("route", route),
+        ("status", response.status().as_u16().to_string()),
+    ];
+
+    metrics::counter!("http_requests_total", &labels).increment(1);
+    metrics::histogram!("http_request_duration_seconds", &labels).record(start.elapsed().as_secs_f64());
+
+    response
+}
+
+/// Records the latency of a store operation when dropped, so every return
+/// path is measured.
+pub struct StoreOperationTimer {
+    backend: &'static str,
+    store: &'static str,
+    operation: &'static str,
+    start: Instant,
+}
+
+impl StoreOperationTimer {
+    pub fn new(backend: &'static str, store: &'static str, operation: &'static str) -> Self {
+        Self { backend, store, operation, start: Instant::now() }
+    }
+}
+
+impl Drop for StoreOperationTimer {
+    fn drop(&mut self) {
+        metrics::histogram!(
+            "store_operation_duration_seconds",
+            "backend" => self.backend,
+            "store" => self.store,
+            "operation" => self.operation
+        )
+        .record(self.start.elapsed().as_secs_f64());
+    }
+}
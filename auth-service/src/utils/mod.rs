@@ -1,3 +1,7 @@
 pub mod constants;
 pub mod auth;
+pub mod metrics;
+pub mod redact;
+pub mod request_context;
+pub mod settings;
 pub mod tracing;
\ No newline at end of file
@@ -0,0 +1,29 @@
+/// Placeholder written in place of personal data in logs and error reports.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Replaces every occurrence of `secret` in `text`, e.g. a recipient echoed
+/// back in a provider's error response.
+pub fn redact(text: &str, secret: &str) -> String {
+    if secret.is_empty() {
+        return text.to_owned();
+    }
+
+    text.replace(secret, REDACTED)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redact_replaces_every_occurrence() {
+        let text = "Invalid 'To' address: 'jane@example.com' (jane@example.com)";
+
+        assert_eq!(redact(text, "jane@example.com"), "Invalid 'To' address: '[REDACTED]' ([REDACTED])");
+    }
+
+    #[test]
+    fn redact_leaves_text_alone_for_an_empty_secret() {
+        assert_eq!(redact("provider is down", ""), "provider is down");
+    }
+}
@@ -0,0 +1,82 @@
+use std::{convert::Infallible, net::{IpAddr, SocketAddr}};
+
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header::USER_AGENT, request::Parts, HeaderMap}
+};
+
+use crate::{app_state::AppState, domain::RequestContext};
+
+use super::tracing::REQUEST_ID_HEADER;
+
+const FORWARDED_FOR_HEADER: &str = "x-forwarded-for";
+
+/// Longer user agents are cut short before they are stored.
+const MAX_USER_AGENT_LENGTH: usize = 512;
+
+/// Never rejects: anything the request does not say is left empty.
+#[async_trait::async_trait]
+impl FromRequestParts<AppState> for RequestContext {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let ip = if state.settings.application.trust_forwarded_for {
+            forwarded_ip(&parts.headers)
+        } else {
+            parts.extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(address)| address.ip().to_string())
+        };
+
+        let user_agent = header(&parts.headers, USER_AGENT.as_str())
+            .map(|user_agent| user_agent.chars().take(MAX_USER_AGENT_LENGTH).collect());
+
+        Ok(RequestContext {
+            ip,
+            user_agent,
+            request_id: header(&parts.headers, REQUEST_ID_HEADER).map(str::to_owned),
+        })
+    }
+}
+
+/// The proxy in front of the service appends the address it saw to the
+/// header, so only the last entry can be trusted.
+fn forwarded_ip(headers: &HeaderMap) -> Option<String> {
+    header(headers, FORWARDED_FOR_HEADER)?
+        .rsplit(',')
+        .next()?
+        .trim()
+        .parse::<IpAddr>()
+        .ok()
+        .map(|ip| ip.to_string())
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    fn forwarded_for(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(FORWARDED_FOR_HEADER, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn forwarded_ip_is_the_last_entry() {
+        assert_eq!(forwarded_ip(&forwarded_for("10.0.0.1")), Some("10.0.0.1".to_owned()));
+        assert_eq!(forwarded_ip(&forwarded_for("1.2.3.4, 203.0.113.7")), Some("203.0.113.7".to_owned()));
+        assert_eq!(forwarded_ip(&forwarded_for("2001:db8::1")), Some("2001:db8::1".to_owned()));
+    }
+
+    #[test]
+    fn forwarded_ip_ignores_junk() {
+        assert_eq!(forwarded_ip(&forwarded_for("203.0.113.7, not-an-ip")), None);
+        assert_eq!(forwarded_ip(&HeaderMap::new()), None);
+    }
+}
@@ -1,37 +1,135 @@
 use std::time::Duration;
-use axum::{body::Body, extract::Request,
+use axum::{body::Body, extract::{MatchedPath, Request}, http::HeaderMap,
 response::Response};
+use opentelemetry::{
+    propagation::{Extractor, TextMapPropagator},
+    trace::TracerProvider as _,
+    Context as OtelContext, KeyValue
+};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{propagation::TraceContextPropagator, runtime, trace::TracerProvider, Resource};
 use tracing::{Level, Span};
-use color_eyre::eyre::Result;
+use color_eyre::eyre::{Context, Result};
 use tracing_error::ErrorLayer;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 use tracing_subscriber::prelude::*;
-use tracing_subscriber::{fmt, EnvFilter};
+use tracing_subscriber::{fmt::{self, MakeWriter}, registry::LookupSpan, EnvFilter, Layer};
 
+use super::settings::{LogFormat, TelemetrySettings};
 
-pub fn init_tracing() -> Result<()> {
-    let fmt_layer = fmt::layer().compact(); //compact format
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Flushes buffered spans to the collector when dropped, so keep it alive
+/// until the process exits.
+pub struct TracingGuard {
+    tracer_provider: TracerProvider,
+}
+
+impl Drop for TracingGuard {
+    fn drop(&mut self) {
+        if let Err(e) = self.tracer_provider.shutdown() {
+            eprintln!("Failed to flush spans: {:?}", e);
+        }
+    }
+}
+
+/// Spans always carry an OpenTelemetry context so incoming `traceparent`
+/// headers are honoured. They are only exported when an OTLP endpoint is
+/// configured.
+pub fn init_tracing(telemetry: &TelemetrySettings) -> Result<TracingGuard> {
+    let fmt_layer = fmt_layer(telemetry.log_format, std::io::stdout);
+
+    let mut tracer_provider = TracerProvider::builder()
+        .with_resource(Resource::new([KeyValue::new("service.name", telemetry.service_name.clone())]));
+
+    if let Some(endpoint) = &telemetry.otlp_endpoint {
+        let exporter = SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint(endpoint)
+            .build()
+            .wrap_err("failed to build OTLP span exporter")?;
+
+        tracer_provider = tracer_provider.with_batch_exporter(exporter, runtime::Tokio);
+    }
+
+    let tracer_provider = tracer_provider.build();
+    let otel_layer = tracing_opentelemetry::layer()
+        .with_tracer(tracer_provider.tracer(telemetry.service_name.clone()));
 
     let filter_layer = EnvFilter::try_from_default_env().or_else(|_| EnvFilter::try_new("sqlx=debug,info"))?;
     tracing_subscriber::registry()
         .with(filter_layer)
         .with(fmt_layer)
         .with(ErrorLayer::default())
+        .with(otel_layer)
         .init();
 
-    Ok(())
+    Ok(TracingGuard { tracer_provider })
 }
 
+/// Writes events to `writer`. JSON events are flattened into one object
+/// per line, with the fields of the innermost span under `span`.
+pub fn fmt_layer<S, W>(format: LogFormat, writer: W) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    match format {
+        LogFormat::Text => fmt::layer().compact().with_writer(writer).boxed(),
+        LogFormat::Json => fmt::layer()
+            .json()
+            .flatten_event(true)
+            .with_current_span(true)
+            .with_span_list(false)
+            .with_writer(writer)
+            .boxed(),
+    }
+}
+
+/// The request id is set by `SetRequestIdLayer`, which keeps the caller's
+/// `X-Request-Id` when there is one. Only the matched route is logged, e.g.
+/// `/admin/users/:email`, because paths and query strings can hold emails.
 pub fn make_span_with_request_id(request: &Request<Body>) -> Span {
-    let request_id = uuid::Uuid::new_v4();
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str())
+        .unwrap_or("unmatched");
 
-    tracing::span!(
+    let span = tracing::span!(
         Level::INFO,
         "[REQUEST]",
         method = tracing::field::display(request.method()),
-        uri = tracing::field::display(request.uri()),
+        route = tracing::field::display(route),
         version = tracing::field::debug(request.version()),
         request_id = tracing::field::display(request_id)
-    )
+    );
+
+    span.set_parent(parent_context(request.headers()));
+    span
+}
+
+/// Reads the W3C `traceparent` and `tracestate` headers. Without them the
+/// request starts a new trace.
+fn parent_context(headers: &HeaderMap) -> OtelContext {
+    TraceContextPropagator::new().extract(&HeaderExtractor(headers))
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
 }
 
 pub fn on_request(_request: &Request<Body>, _span: &Span) {
@@ -62,3 +160,88 @@ pub fn on_response(response: &Response, latency: Duration, _span: &Span) {
         }
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use opentelemetry::trace::{TraceContextExt, TraceId};
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn request_with_headers(headers: &[(&str, &str)]) -> Request<Body> {
+        let mut builder = Request::builder().uri("/verify-token");
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        builder.body(Body::empty()).unwrap()
+    }
+
+    fn trace_id_of(request: &Request<Body>) -> TraceId {
+        let tracer = TracerProvider::builder().build().tracer("test");
+        let subscriber = tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(tracer));
+
+        tracing::subscriber::with_default(subscriber, || {
+            make_span_with_request_id(request).context().span().span_context().trace_id()
+        })
+    }
+
+    #[test]
+    fn span_joins_the_incoming_trace() {
+        let request = request_with_headers(&[
+            ("traceparent", "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"),
+            (REQUEST_ID_HEADER, "req-123"),
+        ]);
+
+        assert_eq!(trace_id_of(&request), TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap());
+    }
+
+    #[test]
+    fn span_starts_a_new_trace_without_traceparent() {
+        let request = request_with_headers(&[]);
+
+        let trace_id = trace_id_of(&request);
+
+        assert_ne!(trace_id, TraceId::INVALID);
+        assert_ne!(trace_id, TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap());
+    }
+
+    #[test]
+    fn json_format_writes_one_object_per_event() {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::registry().with(fmt_layer(LogFormat::Json, move || writer.clone()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let _span = tracing::info_span!("[REQUEST]", request_id = "req-123").entered();
+            tracing::info!(status = 200, "first");
+            tracing::warn!("second");
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let events: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["message"], "first");
+        assert_eq!(events[0]["status"], 200);
+        assert_eq!(events[0]["span"]["request_id"], "req-123");
+        assert_eq!(events[1]["level"], "WARN");
+    }
+}
@@ -0,0 +1,151 @@
+use auth_service::{
+    domain::{AuditEvent, Email, TwoFACodeStore},
+    routes::{ActivityEntry, TwoFactorAuthResponse},
+    ErrorResponse
+};
+use reqwest::header::USER_AGENT;
+use secrecy::{ExposeSecret, Secret};
+
+use crate::helpers::{get_random_email, TestApp};
+
+const PASSWORD: &str = "correct horse battery staple";
+const NEW_PASSWORD: &str = "plum kettle orbit velvet";
+
+async fn audit_row(app: &TestApp, email: &str, event: &str) -> (Option<String>, Option<String>, Option<String>) {
+    sqlx::query_as("SELECT ip, user_agent, request_id FROM audit_log WHERE subject = $1 AND event = $2")
+        .bind(email)
+        .bind(event)
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn should_record_authentication_events_and_show_them_to_the_user() {
+    let mut app = TestApp::new().await;
+    let random_email = get_random_email();
+
+    let response = app.signup(&serde_json::json!({
+        "email": random_email,
+        "password": PASSWORD,
+        "requires2FA": true
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app.http_client
+        .post(format!("{}/login", &app.address))
+        .header(USER_AGENT, "Activity Test Browser")
+        .header("x-request-id", "req-activity-1")
+        .json(&serde_json::json!({ "email": random_email, "password": "wrong password" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(audit_row(&app, &random_email, "login_failed").await, (
+        Some("127.0.0.1".to_owned()),
+        Some("Activity Test Browser".to_owned()),
+        Some("req-activity-1".to_owned())
+    ));
+
+    let response = app.login(&serde_json::json!({ "email": random_email, "password": PASSWORD })).await;
+    assert_eq!(response.status().as_u16(), 206);
+    let login_attempt_id = response.json::<TwoFactorAuthResponse>().await.unwrap().login_attempt_id;
+
+    let email = Email::parse(Secret::new(random_email.clone())).unwrap();
+    let (_, code) = app.two_fa_code_store.get_code(&email).await.unwrap();
+    let response = app.verify_2fa(&serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": code.as_ref().expose_secret()
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.change_password(&serde_json::json!({
+        "currentPassword": PASSWORD,
+        "newPassword": NEW_PASSWORD
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.get_activity().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let activity = response.json::<Vec<ActivityEntry>>().await.unwrap();
+    assert_eq!(activity.iter().map(|entry| entry.event).collect::<Vec<_>>(), vec![
+        AuditEvent::PasswordChanged,
+        AuditEvent::LoginSucceeded,
+        AuditEvent::TwoFAVerified,
+        AuditEvent::TwoFAIssued,
+        AuditEvent::LoginFailed,
+        AuditEvent::Signup,
+    ]);
+    assert!(activity.iter().all(|entry| !entry.by_admin));
+    assert_eq!(activity[4].user_agent.as_deref(), Some("Activity Test Browser"));
+
+    assert_eq!(app.logout().await.status().as_u16(), 200);
+    assert_eq!(audit_row(&app, &random_email, "logout").await.0, Some("127.0.0.1".to_owned()));
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_only_show_the_logged_in_users_activity() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_activity().await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(response.json::<ErrorResponse>().await.unwrap().error, "Missing token");
+
+    let other_email = get_random_email();
+    let random_email = get_random_email();
+    for email in [&other_email, &random_email] {
+        let response = app.signup(&serde_json::json!({
+            "email": email,
+            "password": PASSWORD,
+            "requires2FA": false
+        })).await;
+        assert_eq!(response.status().as_u16(), 201);
+    }
+    assert_eq!(app.admin_post(&format!("/users/{}/revoke-sessions", random_email)).await.status().as_u16(), 200);
+
+    let response = app.login(&serde_json::json!({ "email": random_email, "password": PASSWORD })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let activity = app.get_activity().await.json::<Vec<ActivityEntry>>().await.unwrap();
+    assert_eq!(activity.iter().map(|entry| (entry.event, entry.by_admin)).collect::<Vec<_>>(), vec![
+        (AuditEvent::LoginSucceeded, false),
+        (AuditEvent::SessionsRevoked, true),
+        (AuditEvent::Signup, false),
+    ]);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_not_allow_audit_entries_to_be_changed_or_removed() {
+    let mut app = TestApp::new().await;
+    let random_email = get_random_email();
+
+    let response = app.signup(&serde_json::json!({
+        "email": random_email,
+        "password": PASSWORD,
+        "requires2FA": false
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let update = sqlx::query("UPDATE audit_log SET event = 'login_succeeded' WHERE subject = $1")
+        .bind(&random_email)
+        .execute(&app.pg_pool)
+        .await;
+    assert!(update.unwrap_err().to_string().contains("audit_log is append-only"));
+
+    let delete = sqlx::query("DELETE FROM audit_log WHERE subject = $1")
+        .bind(&random_email)
+        .execute(&app.pg_pool)
+        .await;
+    assert!(delete.is_err());
+    assert!(sqlx::query("TRUNCATE audit_log").execute(&app.pg_pool).await.is_err());
+
+    assert_eq!(audit_row(&app, &random_email, "signup").await.0, Some("127.0.0.1".to_owned()));
+
+    app.clean_up().await;
+}
@@ -0,0 +1,307 @@
+use auth_service::{
+    domain::{
+        AccountStatus, Email, EmailOutbox, PasswordResetToken, PasswordResetTokenStore, PhoneNumber, TwoFAChannel, UserStore,
+        ADMIN_ROLE
+    },
+    routes::{ListUsersResponse, UserDetailsResponse},
+    utils::constants::JWT_COOKIE_NAME,
+    ErrorResponse
+};
+use chrono::{Duration, Utc};
+use secrecy::{ExposeSecret, Secret};
+use uuid::Uuid;
+
+use crate::helpers::{get_random_email, TestApp};
+
+const PASSWORD: &str = "correct horse battery staple";
+const NEW_PASSWORD: &str = "plum kettle orbit velvet";
+
+async fn signup(app: &TestApp, email: &str) {
+    let response = app.signup(&serde_json::json!({
+        "email": email,
+        "password": PASSWORD,
+        "requires2FA": false
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+async fn login(app: &TestApp, email: &str, password: &str) -> reqwest::Response {
+    app.login(&serde_json::json!({ "email": email, "password": password })).await
+}
+
+/// Admin actions only. The user's own logins and signup are recorded too.
+async fn audit_events(app: &TestApp, email: &str) -> Vec<(String, Option<String>)> {
+    sqlx::query_as("SELECT event, actor FROM audit_log WHERE subject = $1 AND actor IS NOT NULL ORDER BY id")
+        .bind(email)
+        .fetch_all(&app.pg_pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn should_list_and_search_users_a_page_at_a_time() {
+    let mut app = TestApp::new().await;
+    let tag = Uuid::new_v4().simple().to_string();
+    let emails: Vec<String> = ["c", "a", "b"].iter().map(|n| format!("{}-{}@example.com", tag, n)).collect();
+    for email in &emails {
+        signup(&app, email).await;
+    }
+    signup(&app, &get_random_email()).await;
+
+    let response = app.admin_get(&format!("/users?search={}&perPage=2", tag.to_uppercase())).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let first_page = response.json::<ListUsersResponse>().await.unwrap();
+    assert_eq!(first_page.total, 3);
+    assert_eq!((first_page.page, first_page.per_page), (1, 2));
+    assert_eq!(first_page.users.iter().map(|user| user.email.as_str()).collect::<Vec<_>>(),
+        vec![emails[1].as_str(), emails[2].as_str()]);
+    assert_eq!(first_page.users[0].status, AccountStatus::Active);
+
+    let second_page = app.admin_get(&format!("/users?search={}&perPage=2&page=2", tag)).await
+        .json::<ListUsersResponse>().await.unwrap();
+    assert_eq!(second_page.users.len(), 1);
+    assert_eq!(second_page.users[0].email, emails[0]);
+
+    let everyone = app.admin_get("/users").await.json::<ListUsersResponse>().await.unwrap();
+    assert_eq!(everyone.total, 4);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_a_users_details() {
+    let mut app = TestApp::new().await;
+    let random_email = get_random_email();
+    signup(&app, &random_email).await;
+    app.user_store.assign_role(&Email::parse(Secret::new(random_email.clone())).unwrap(), ADMIN_ROLE).await.unwrap();
+
+    let response = app.admin_get(&format!("/users/{}", random_email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let details = response.json::<UserDetailsResponse>().await.unwrap();
+    assert_eq!(details.email, random_email);
+    assert_eq!(details.status, AccountStatus::Active);
+    assert_eq!(details.two_fa_channel, TwoFAChannel::Email);
+    assert!(!details.has_phone_number);
+    assert!(!details.password_reset_required);
+    assert_eq!(details.roles, vec![ADMIN_ROLE]);
+
+    assert_eq!(app.admin_get(&format!("/users/{}", get_random_email())).await.status().as_u16(), 404);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_only_let_admins_and_the_api_key_in() {
+    let mut app = TestApp::new().await;
+    let random_email = get_random_email();
+    signup(&app, &random_email).await;
+    let users_url = format!("{}/admin/users", &app.address);
+
+    let response = app.http_client.get(&users_url).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.http_client.get(&users_url).bearer_auth("wrong-key").send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+
+    assert_eq!(login(&app, &random_email, PASSWORD).await.status().as_u16(), 200);
+    let response = app.http_client.get(&users_url).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(response.json::<ErrorResponse>().await.unwrap().error, "Forbidden");
+
+    app.user_store.assign_role(&Email::parse(Secret::new(random_email.clone())).unwrap(), ADMIN_ROLE).await.unwrap();
+    assert_eq!(login(&app, &random_email, PASSWORD).await.status().as_u16(), 200);
+    assert_eq!(app.http_client.get(&users_url).send().await.unwrap().status().as_u16(), 200);
+
+    let target = get_random_email();
+    signup(&app, &target).await;
+    let response = app.http_client.post(format!("{}/{}/disable", users_url, target)).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(audit_events(&app, &target).await, vec![("account_disabled".to_owned(), Some(random_email))]);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_block_login_while_an_account_is_disabled() {
+    let mut app = TestApp::new().await;
+    let random_email = get_random_email();
+    signup(&app, &random_email).await;
+
+    let response = login(&app, &random_email, PASSWORD).await;
+    let auth_cookie = response.cookies().find(|cookie| cookie.name() == JWT_COOKIE_NAME).unwrap();
+    let token = serde_json::json!({ "token": auth_cookie.value() });
+
+    assert_eq!(app.admin_post(&format!("/users/{}/disable", random_email)).await.status().as_u16(), 200);
+
+    assert_eq!(app.verify_token(&token).await.status().as_u16(), 401);
+    let response = login(&app, &random_email, PASSWORD).await;
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(response.json::<ErrorResponse>().await.unwrap().error, "Account disabled");
+
+    assert_eq!(app.admin_post(&format!("/users/{}/enable", random_email)).await.status().as_u16(), 200);
+    assert_eq!(login(&app, &random_email, PASSWORD).await.status().as_u16(), 200);
+
+    assert_eq!(audit_events(&app, &random_email).await, vec![
+        ("account_disabled".to_owned(), Some("api-key".to_owned())),
+        ("account_enabled".to_owned(), Some("api-key".to_owned())),
+    ]);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_block_login_and_tokens_while_an_account_is_suspended() {
+    let mut app = TestApp::new().await;
+    let random_email = get_random_email();
+    signup(&app, &random_email).await;
+
+    let response = login(&app, &random_email, PASSWORD).await;
+    let auth_cookie = response.cookies().find(|cookie| cookie.name() == JWT_COOKIE_NAME).unwrap();
+    let token = serde_json::json!({ "token": auth_cookie.value() });
+
+    let response = app.admin_post_json(&format!("/users/{}/suspend", random_email),
+        &serde_json::json!({ "reason": " chargeback " })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let details = app.admin_get(&format!("/users/{}", random_email)).await
+        .json::<UserDetailsResponse>().await.unwrap();
+    assert_eq!(details.status, AccountStatus::Suspended);
+    assert_eq!(details.status_reason.as_deref(), Some("chargeback"));
+    assert!(details.status_changed_at.is_some());
+
+    assert_eq!(app.verify_token(&token).await.status().as_u16(), 401);
+    let response = login(&app, &random_email, PASSWORD).await;
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(response.json::<ErrorResponse>().await.unwrap().error, "Account suspended");
+
+    // Suspending keeps the user's sessions, so their token works again.
+    assert_eq!(app.admin_post(&format!("/users/{}/enable", random_email)).await.status().as_u16(), 200);
+    assert_eq!(app.verify_token(&token).await.status().as_u16(), 200);
+
+    let details = app.admin_get(&format!("/users/{}", random_email)).await
+        .json::<UserDetailsResponse>().await.unwrap();
+    assert_eq!(details.status, AccountStatus::Active);
+    assert_eq!(details.status_reason, None);
+
+    assert_eq!(app.admin_post(&format!("/users/{}/suspend", get_random_email())).await.status().as_u16(), 404);
+    assert_eq!(audit_events(&app, &random_email).await, vec![
+        ("account_suspended".to_owned(), Some("api-key".to_owned())),
+        ("account_enabled".to_owned(), Some("api-key".to_owned())),
+    ]);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_revoke_every_session_of_a_user() {
+    let mut app = TestApp::new().await;
+    let random_email = get_random_email();
+    signup(&app, &random_email).await;
+
+    let mut tokens = Vec::new();
+    for _ in 0..2 {
+        let response = login(&app, &random_email, PASSWORD).await;
+        let auth_cookie = response.cookies().find(|cookie| cookie.name() == JWT_COOKIE_NAME).unwrap();
+        tokens.push(serde_json::json!({ "token": auth_cookie.value() }));
+    }
+
+    assert_eq!(app.admin_post(&format!("/users/{}/revoke-sessions", random_email)).await.status().as_u16(), 200);
+
+    for token in &tokens {
+        assert_eq!(app.verify_token(token).await.status().as_u16(), 401);
+    }
+
+    let response = login(&app, &random_email, PASSWORD).await;
+    let auth_cookie = response.cookies().find(|cookie| cookie.name() == JWT_COOKIE_NAME).unwrap();
+    assert_eq!(app.verify_token(&serde_json::json!({ "token": auth_cookie.value() })).await.status().as_u16(), 200);
+
+    assert_eq!(app.admin_post(&format!("/users/{}/revoke-sessions", get_random_email())).await.status().as_u16(), 404);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_make_the_user_reset_their_password_with_the_emailed_link() {
+    let mut app = TestApp::new().await;
+    let random_email = get_random_email();
+    signup(&app, &random_email).await;
+
+    assert_eq!(app.admin_post(&format!("/users/{}/force-password-reset", random_email)).await.status().as_u16(), 200);
+
+    let response = login(&app, &random_email, PASSWORD).await;
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(response.json::<ErrorResponse>().await.unwrap().error, "Password reset required");
+
+    let queued = app.email_outbox.claim_due(10, Utc::now() + Duration::minutes(5)).await.unwrap();
+    assert_eq!(queued.len(), 1);
+    let reset_url = format!("{}?token=", app.settings.auth.password_reset_url);
+    let token: String = queued[0].email.text_body
+        .split(&reset_url)
+        .nth(1)
+        .expect("No reset link in email")
+        .chars()
+        .take_while(char::is_ascii_alphanumeric)
+        .collect();
+
+    let response = app.reset_password(&serde_json::json!({ "token": token, "newPassword": "password" })).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.reset_password(&serde_json::json!({ "token": token, "newPassword": NEW_PASSWORD })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.reset_password(&serde_json::json!({ "token": token, "newPassword": NEW_PASSWORD })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    assert_eq!(login(&app, &random_email, PASSWORD).await.status().as_u16(), 401);
+    assert_eq!(login(&app, &random_email, NEW_PASSWORD).await.status().as_u16(), 200);
+    assert_eq!(audit_events(&app, &random_email).await,
+        vec![("password_reset_forced".to_owned(), Some("api-key".to_owned()))]);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_expire_a_reset_link_after_too_many_rejected_passwords() {
+    let mut app = TestApp::new().await;
+    let random_email = get_random_email();
+    signup(&app, &random_email).await;
+
+    let token = PasswordResetToken::default();
+    let email = Email::parse(Secret::new(random_email.clone())).unwrap();
+    app.password_reset_token_store.add_token(token.clone(), email).await.unwrap();
+    let token = token.as_ref().expose_secret().to_owned();
+
+    for _ in 0..5 {
+        let response = app.reset_password(&serde_json::json!({ "token": token, "newPassword": "password" })).await;
+        assert_eq!(response.status().as_u16(), 400);
+    }
+
+    let response = app.reset_password(&serde_json::json!({ "token": token, "newPassword": NEW_PASSWORD })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_reset_a_users_second_factors() {
+    let mut app = TestApp::new().await;
+    let random_email = get_random_email();
+    signup(&app, &random_email).await;
+
+    let email = Email::parse(Secret::new(random_email.clone())).unwrap();
+    let phone_number = PhoneNumber::parse(Secret::new("+14155550100".to_owned())).unwrap();
+    app.user_store.set_phone_number(&email, phone_number).await.unwrap();
+    app.user_store.set_two_fa_channel(&email, TwoFAChannel::Sms).await.unwrap();
+
+    assert_eq!(app.admin_post(&format!("/users/{}/reset-2fa", random_email)).await.status().as_u16(), 200);
+
+    let details = app.admin_get(&format!("/users/{}", random_email)).await
+        .json::<UserDetailsResponse>().await.unwrap();
+    assert!(!details.has_phone_number);
+    assert_eq!(details.two_fa_channel, TwoFAChannel::Email);
+    assert_eq!(audit_events(&app, &random_email).await, vec![("two_fa_reset".to_owned(), Some("api-key".to_owned()))]);
+
+    app.clean_up().await;
+}
@@ -0,0 +1,136 @@
+use auth_service::{domain::PasswordViolation, routes::ChangePasswordResponse, ErrorResponse};
+
+use crate::helpers::{get_random_email, TestApp};
+
+const PASSWORDS: [&str; 3] = [
+    "correct horse battery staple",
+    "plum kettle orbit velvet",
+    "quiet falcon under marble",
+];
+
+async fn signup_and_login(app: &TestApp) -> String {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": PASSWORDS[0],
+        "requires2FA": false
+    });
+    assert_eq!(app.signup(&signup_body).await.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": PASSWORDS[0]
+    });
+    assert_eq!(app.login(&login_body).await.status().as_u16(), 200);
+
+    random_email
+}
+
+async fn change_password(app: &TestApp, current_password: &str, new_password: &str) -> reqwest::Response {
+    app.change_password(&serde_json::json!({
+        "currentPassword": current_password,
+        "newPassword": new_password
+    })).await
+}
+
+#[tokio::test]
+async fn should_return_200_and_accept_the_new_password_at_login() {
+    let mut app = TestApp::new().await;
+    let email = signup_and_login(&app).await;
+
+    let response = change_password(&app, PASSWORDS[0], PASSWORDS[1]).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.json::<ChangePasswordResponse>().await.unwrap().message, "Password changed successfully!");
+
+    let old_login = app.login(&serde_json::json!({ "email": email, "password": PASSWORDS[0] })).await;
+    assert_eq!(old_login.status().as_u16(), 401);
+
+    let new_login = app.login(&serde_json::json!({ "email": email, "password": PASSWORDS[1] })).await;
+    assert_eq!(new_login.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_a_recent_password_is_reused() {
+    let mut app = TestApp::new().await;
+    signup_and_login(&app).await;
+
+    assert_eq!(change_password(&app, PASSWORDS[0], PASSWORDS[1]).await.status().as_u16(), 200);
+
+    for reused in [PASSWORDS[0], PASSWORDS[1]] {
+        let response = change_password(&app, PASSWORDS[1], reused).await;
+        assert_eq!(response.status().as_u16(), 400);
+
+        let body = response.json::<ErrorResponse>().await.unwrap();
+        assert_eq!(body.error, "Weak password");
+        assert_eq!(body.violations.iter().map(|violation| violation.violation.clone()).collect::<Vec<_>>(),
+            vec![PasswordViolation::Reused { last: app.settings.auth.password_policy.history_size }]);
+    }
+
+    assert_eq!(change_password(&app, PASSWORDS[1], PASSWORDS[2]).await.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_keep_only_the_configured_number_of_previous_passwords() {
+    let mut app = TestApp::new().await;
+    let email = signup_and_login(&app).await;
+    let history_size = app.settings.auth.password_policy.history_size as i64;
+
+    let mut current_password = PASSWORDS[0].to_owned();
+    for n in 0..history_size + 1 {
+        let new_password = format!("{} {}", PASSWORDS[2], n);
+        assert_eq!(change_password(&app, &current_password, &new_password).await.status().as_u16(), 200);
+        current_password = new_password;
+    }
+
+    let stored: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM password_history WHERE email = $1")
+        .bind(&email)
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap();
+    assert_eq!(stored, history_size - 1);
+
+    // The signup password has dropped out of the history.
+    assert_eq!(change_password(&app, &current_password, PASSWORDS[0]).await.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_the_current_password_is_wrong() {
+    let mut app = TestApp::new().await;
+    signup_and_login(&app).await;
+
+    let response = change_password(&app, PASSWORDS[2], PASSWORDS[1]).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_with_violations_if_the_new_password_is_weak() {
+    let mut app = TestApp::new().await;
+    signup_and_login(&app).await;
+
+    let response = change_password(&app, PASSWORDS[0], "password123").await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let body = response.json::<ErrorResponse>().await.unwrap();
+    assert!(body.violations.iter().any(|violation| violation.violation == PasswordViolation::Common));
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_the_token_is_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = change_password(&app, PASSWORDS[0], PASSWORDS[1]).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
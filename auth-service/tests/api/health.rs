@@ -0,0 +1,48 @@
+use auth_service::routes::{LivenessResponse, ReadinessResponse};
+
+use crate::helpers::TestApp;
+
+#[tokio::test]
+async fn live_returns_200() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_health_live().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response.json::<LivenessResponse>().await.expect("Could not deserialize response body");
+    assert_eq!(body.status, "live");
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn ready_reports_each_dependency() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_health_ready().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response.json::<ReadinessResponse>().await.expect("Could not deserialize response body");
+    assert_eq!(body.status, "ready");
+    assert_eq!(body.dependencies["postgres"].status, "up");
+    assert_eq!(body.dependencies["redis"].status, "up");
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn ready_returns_503_during_shutdown() {
+    let mut app = TestApp::new().await;
+
+    app.health.begin_shutdown();
+    let response = app.get_health_ready().await;
+
+    assert_eq!(response.status().as_u16(), 503);
+    let body = response.json::<ReadinessResponse>().await.expect("Could not deserialize response body");
+    assert_eq!(body.status, "shutting_down");
+
+    let response = app.get_health_live().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
@@ -1,43 +1,112 @@
-use auth_service::{app_state::AppState, get_postgres_pool, get_redis_client, services::{self, RedisTwoFACodeStore}, utils::constants::{test, DATABASE_URL, DEFAULT_REDIS_HOSTNAME}, Application};
+use auth_service::{app_state::{AppState, Services, Stores, UserStoreType}, get_postgres_pool, get_redis_connection, get_webauthn, services::{self, Argon2Hasher, Health, PasswordHasherRegistry, PostgresEmailOutbox, PostgresUserStore, RedisPasswordResetTokenStore, RedisPhoneVerificationStore, RedisTwoFACodeStore}, utils::{constants::{test, DEFAULT_REDIS_HOSTNAME}, metrics::prometheus_handle, settings::{Cli, DatabaseSettings, Settings}}, Application, ShutdownHandle};
 use sqlx::{postgres::{PgConnectOptions, PgPoolOptions}, Connection, Executor, PgConnection, PgPool};
+use redis::aio::ConnectionManager;
 use uuid::Uuid;
-use std::{str::FromStr, sync::Arc};
-use tokio::sync::RwLock;
+use std::{str::FromStr, sync::Arc, time::Duration};
+use tokio::task::JoinHandle;
 use reqwest::cookie::Jar;
 use secrecy::{ExposeSecret, Secret};
 
 
+/// Meets the password policy but is treated as breached by the test app.
+pub const BREACHED_PASSWORD: &str = "Tr0ub4dor&3-breached";
+
 pub struct TestApp {
     pub address: String,
     pub http_client: reqwest::Client,
     pub db_name: String,
+    database_url: Secret<String>,
     pub cookie_jar: Arc<Jar>,
-    pub two_fa_code_store: Arc<RwLock<RedisTwoFACodeStore>>,
+    pub two_fa_code_store: Arc<RedisTwoFACodeStore>,
+    pub email_outbox: Arc<PostgresEmailOutbox>,
+    pub phone_verification_store: Arc<RedisPhoneVerificationStore>,
+    pub password_reset_token_store: Arc<RedisPasswordResetTokenStore>,
+    pub health: Arc<Health>,
+    pub pg_pool: PgPool,
+    pub user_store: Arc<PostgresUserStore>,
+    pub password_hasher: Argon2Hasher,
+    pub settings: Arc<Settings>,
+    pub shutdown: ShutdownHandle,
+    pub server: JoinHandle<Result<(), std::io::Error>>,
     clean_up_called: bool
 }
 
 impl  TestApp {
     pub async fn new() -> Self {
-        let db_name = Uuid::new_v4().to_string();
-        let pg_pool = configure_postgresql(&db_name).await;
-        let redis_client = Arc::new(RwLock::new(configure_redis()));
+        Self::new_with(|_| {}, |user_store| user_store).await
+    }
 
-        let test_user_store = Arc::new(RwLock::new(services::PostgresUserStore::new(pg_pool)));
-        let test_banned_token_store = Arc::new(RwLock::new(services::RedisBannedTokenStore::new(redis_client.clone())));
-        let two_fa_code_store = Arc::new(RwLock::new(services::RedisTwoFACodeStore::new(redis_client)));
-        let email_client = Arc::new(RwLock::new(services::MockEmailClient::default()));
+    /// Like `new`, after `configure` has adjusted the test settings and with
+    /// the app's user store replaced by `wrap_user_store`'s result.
+    pub async fn new_with(
+        configure: impl FnOnce(&mut Settings),
+        wrap_user_store: impl FnOnce(UserStoreType) -> UserStoreType
+    ) -> Self {
+        let mut settings = test_settings();
+        configure(&mut settings);
+        let settings = Arc::new(settings);
+        let db_name = Uuid::new_v4().to_string();
+        let pg_pool = configure_postgresql(&settings.database, &db_name).await;
+        let redis_conn = configure_redis().await;
+        let health = Arc::new(services::Health::new(vec![
+            Arc::new(services::PostgresHealthCheck::new(pg_pool.clone())),
+            Arc::new(services::RedisHealthCheck::new(redis_conn.clone())),
+        ], test::HEALTH_CHECK_TIMEOUT));
+
+        let password_hasher = Argon2Hasher::new(&settings.auth.password_hashing)
+            .expect("Failed to configure password hashing");
+        let test_user_store = Arc::new(services::PostgresUserStore::new(pg_pool.clone(), PasswordHasherRegistry::new(password_hasher.clone()))
+            .with_password_history(settings.auth.password_policy.history_size));
+        let test_banned_token_store = Arc::new(services::RedisBannedTokenStore::new(redis_conn.clone()));
+        let two_fa_code_store = Arc::new(services::RedisTwoFACodeStore::new(redis_conn.clone()));
+        let passkey_store = Arc::new(services::PostgresPasskeyStore::new(pg_pool.clone()));
+        let trusted_device_store = Arc::new(services::PostgresTrustedDeviceStore::new(pg_pool.clone()));
+        let email_outbox = Arc::new(services::PostgresEmailOutbox::new(pg_pool.clone()));
+        let webauthn_challenge_store = Arc::new(services::RedisWebAuthnChallengeStore::new(redis_conn.clone()));
+        let phone_verification_store = Arc::new(services::RedisPhoneVerificationStore::new(redis_conn.clone()));
+        let password_reset_token_store = Arc::new(services::RedisPasswordResetTokenStore::new(redis_conn));
+        let audit_log = Arc::new(services::PostgresAuditLog::new(pg_pool.clone()));
+        let account_status_cache = Arc::new(services::AccountStatusCache::new(
+            test_user_store.clone(), Duration::from_secs(settings.auth.account_status_cache_secs)));
+        let sms_client = Arc::new(services::MockSmsClient);
+        let webauthn = Arc::new(get_webauthn(&settings.webauthn.rp_id, &settings.webauthn.rp_origin)
+            .expect("Failed to configure WebAuthn"));
+        let email_templates = Arc::new(services::EmailTemplates::new(None)
+            .expect("Failed to load email templates"));
+
+        let stores = Stores {
+            user_store: wrap_user_store(test_user_store.clone()),
+            banned_token_store: test_banned_token_store,
+            two_fa_code_store: two_fa_code_store.clone(),
+            email_outbox: email_outbox.clone(),
+            passkey_store,
+            webauthn_challenge_store,
+            trusted_device_store,
+            phone_verification_store: phone_verification_store.clone(),
+            password_reset_token_store: password_reset_token_store.clone(),
+            audit_log,
+        };
+        let app_services = Services {
+            webauthn,
+            email_templates,
+            sms_client,
+            password_policy: Arc::new(settings.auth.password_policy.policy()),
+            breached_password_checker: Arc::new(services::HashsetBreachedPasswordChecker::new([BREACHED_PASSWORD])),
+            account_status_cache: Some(account_status_cache),
+        };
 
-        let test_app_state = AppState::new(test_user_store, test_banned_token_store, two_fa_code_store.clone(), email_client);
-        let app = Application::build(test_app_state, test::APP_ADDRESS)
+        let test_app_state = AppState::new(stores, app_services, settings.clone(), health.clone(), prometheus_handle());
+        let app = Application::build(test_app_state)
             .await
             .expect("Failed to build app");
 
         let address = format!("http://{}", app.address.clone());
 
+        let shutdown = app.shutdown_handle();
+
         // Run the auth service in a seprate async task
         // to avoid blocking the main test thread.
-        #[allow(clippy::let_underscore_future)]
-        let _ = tokio::spawn(app.run());
+        let server = tokio::spawn(app.run());
 
         let cookie_jar = Arc::new(Jar::default());
         let http_client = reqwest::Client::builder()
@@ -45,31 +114,73 @@ impl  TestApp {
             .build()
             .unwrap();
 
-        let testing_app = TestApp {
+        TestApp {
             address,
             http_client,
             db_name,
+            database_url: settings.database.url.clone(),
             cookie_jar,
             two_fa_code_store,
+            email_outbox,
+            phone_verification_store,
+            password_reset_token_store,
+            health,
+            pg_pool,
+            user_store: test_user_store,
+            password_hasher,
+            settings,
+            shutdown,
+            server,
             clean_up_called: false
-        };
-
-        testing_app
+        }
     }
 
     pub async fn get_root(&self) -> reqwest::Response {
         self.http_client
-            .get(&format!("{}/", &self.address))
+            .get(format!("{}/", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
+    pub async fn get_root_with_header(&self, name: &str, value: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/", &self.address))
+            .header(name, value)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_health_live(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/health/live", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request health live")
+    }
+
+    pub async fn get_health_ready(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/health/ready", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request health ready")
+    }
+
+    pub async fn get_metrics(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/metrics", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request metrics")
+    }
+
     pub async fn signup<Body>(&self, body: &Body) -> reqwest::Response
     where
     Body: serde::Serialize {
         self.http_client
-            .post(&format!("{}/signup", &self.address))
+            .post(format!("{}/signup", &self.address))
             .json(body)
             .send()
             .await
@@ -80,7 +191,7 @@ impl  TestApp {
     where
     Body: serde::Serialize {
         self.http_client
-            .post(&format!("{}/login", &self.address))
+            .post(format!("{}/login", &self.address))
             .json(body)
             .send()
             .await
@@ -89,17 +200,28 @@ impl  TestApp {
 
     pub async fn logout(&self) -> reqwest::Response {
         self.http_client
-            .post(&format!("{}/logout", &self.address))
+            .post(format!("{}/logout", &self.address))
             .send()
             .await
             .expect("Failed to execute request logout")
     }
 
+    pub async fn change_password<Body>(&self, body: &Body) -> reqwest::Response
+    where
+    Body: serde::Serialize {
+        self.http_client
+            .post(format!("{}/change-password", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request change password")
+    }
+
     pub async fn verify_2fa<Body>(&self, body: &Body) -> reqwest::Response
     where
     Body: serde::Serialize {
         self.http_client
-            .post(&format!("{}/verify-2fa", &self.address))
+            .post(format!("{}/verify-2fa", &self.address))
             .json(body)
             .send()
             .await
@@ -110,15 +232,153 @@ impl  TestApp {
     where
     Body: serde::Serialize {
         self.http_client
-            .post(&format!("{}/verify-token", &self.address))
+            .post(format!("{}/verify-token", &self.address))
             .json(body)
             .send()
             .await
             .expect("Failed to execute request verify login")
     }
 
+    pub async fn webauthn_register_start(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/webauthn/register/start", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request webauthn register start")
+    }
+
+    pub async fn webauthn_register_finish<Body>(&self, body: &Body) -> reqwest::Response
+    where
+    Body: serde::Serialize {
+        self.http_client
+            .post(format!("{}/webauthn/register/finish", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request webauthn register finish")
+    }
+
+    pub async fn webauthn_login_start<Body>(&self, body: &Body) -> reqwest::Response
+    where
+    Body: serde::Serialize {
+        self.http_client
+            .post(format!("{}/webauthn/login/start", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request webauthn login start")
+    }
+
+    pub async fn webauthn_login_finish<Body>(&self, body: &Body) -> reqwest::Response
+    where
+    Body: serde::Serialize {
+        self.http_client
+            .post(format!("{}/webauthn/login/finish", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request webauthn login finish")
+    }
+
+    pub async fn list_trusted_devices(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/trusted-devices", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request list trusted devices")
+    }
+
+    pub async fn get_activity(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/activity", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request get activity")
+    }
+
+    pub async fn revoke_trusted_device(&self, device_id: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/trusted-devices/{}", &self.address, device_id))
+            .send()
+            .await
+            .expect("Failed to execute request revoke trusted device")
+    }
+
+    pub async fn add_phone_number<Body>(&self, body: &Body) -> reqwest::Response
+    where
+    Body: serde::Serialize {
+        self.http_client
+            .post(format!("{}/phone", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request add phone number")
+    }
+
+    pub async fn verify_phone_number<Body>(&self, body: &Body) -> reqwest::Response
+    where
+    Body: serde::Serialize {
+        self.http_client
+            .post(format!("{}/phone/verify", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request verify phone number")
+    }
+
+    pub async fn set_two_fa_channel<Body>(&self, body: &Body) -> reqwest::Response
+    where
+    Body: serde::Serialize {
+        self.http_client
+            .post(format!("{}/2fa-channel", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request set 2fa channel")
+    }
+
+    pub async fn reset_password<Body>(&self, body: &Body) -> reqwest::Response
+    where
+    Body: serde::Serialize {
+        self.http_client
+            .post(format!("{}/reset-password", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request reset password")
+    }
+
+    /// Calls an admin route with the admin API key, e.g. `admin_get("/users")`.
+    pub async fn admin_get(&self, path: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/admin{}", &self.address, path))
+            .bearer_auth(test::ADMIN_API_KEY)
+            .send()
+            .await
+            .expect("Failed to execute admin request")
+    }
+
+    pub async fn admin_post(&self, path: &str) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/admin{}", &self.address, path))
+            .bearer_auth(test::ADMIN_API_KEY)
+            .send()
+            .await
+            .expect("Failed to execute admin request")
+    }
+
+    pub async fn admin_post_json<Body: serde::Serialize>(&self, path: &str, body: &Body) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/admin{}", &self.address, path))
+            .bearer_auth(test::ADMIN_API_KEY)
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute admin request")
+    }
+
     pub async fn clean_up(&mut self) {
-        delete_database(&self.db_name).await;
+        delete_database(&self.database_url, &self.db_name).await;
         self.clean_up_called = true;
     }
 }
@@ -133,19 +393,32 @@ impl Drop for TestApp {
     }
 }
 
+/// Settings from the environment, pointed at a random local port.
+fn test_settings() -> Settings {
+    let mut settings = Settings::load(&Cli::default()).expect("Failed to load settings");
+    settings.application.address = test::APP_ADDRESS.to_owned();
+    settings.application.allowed_origins = vec![test::ALLOWED_ORIGIN.to_owned()];
+    settings.application.drain_timeout_secs = test::DRAIN_TIMEOUT_SECS;
+    settings.application.readiness_grace_secs = test::READINESS_GRACE_SECS;
+    settings.database.max_connections = test::DATABASE_MAX_CONNECTIONS;
+    settings.webauthn.rp_id = test::WEBAUTHN_RP_ID.to_owned();
+    settings.webauthn.rp_origin = test::WEBAUTHN_RP_ORIGIN.to_owned();
+    settings.admin.api_key = Some(Secret::new(test::ADMIN_API_KEY.to_owned()));
+    settings.auth.token_status_check = true;
+    settings
+}
+
 pub fn get_random_email() -> String {
     format!("{}@example.com", Uuid::new_v4())
 }
 
-async fn configure_postgresql(db_name: &str) -> PgPool {
-    let postgresql_conn_url = DATABASE_URL.to_owned();
-
-    configure_database(&postgresql_conn_url, &db_name).await;
+async fn configure_postgresql(database: &DatabaseSettings, db_name: &str) -> PgPool {
+    configure_database(&database.url, db_name).await;
 
     let postgres_conn_url_with_db = Secret::new(format!("{}/{}",
-        postgresql_conn_url.expose_secret(), db_name));
+        database.url.expose_secret(), db_name));
 
-    get_postgres_pool(&postgres_conn_url_with_db)
+    get_postgres_pool(&postgres_conn_url_with_db, database.max_connections)
         .await
         .expect("Failed to create Postgres connection pool!")
 }
@@ -176,10 +449,8 @@ async fn configure_database(db_conn_string: &Secret<String>, db_name: &str) {
 }
 
 
-async fn delete_database(db_name: &str) {
-    let postgresql_conn_url = DATABASE_URL.to_owned();
-
-    let connection_options = PgConnectOptions::from_str(&postgresql_conn_url.expose_secret())
+async fn delete_database(postgresql_conn_url: &Secret<String>, db_name: &str) {
+    let connection_options = PgConnectOptions::from_str(postgresql_conn_url.expose_secret())
         .expect("Failed to parse PostgreSQL connection string");
 
     let mut connection = PgConnection::connect_with(&connection_options)
@@ -208,11 +479,8 @@ async fn delete_database(db_name: &str) {
             .expect("Failed to drop the database.");
 }
 
-fn configure_redis() -> redis::Connection {
-    let redis_hostname = DEFAULT_REDIS_HOSTNAME.to_owned();
-
-    get_redis_client(redis_hostname)
-        .expect("Failed to get Redis client")
-        .get_connection()
+async fn configure_redis() -> ConnectionManager {
+    get_redis_connection(DEFAULT_REDIS_HOSTNAME.to_owned())
+        .await
         .expect("Failed to get Redis connection")
 }
@@ -0,0 +1,54 @@
+use auth_service::services::{import_users, ImportFormat, PasswordHasherRegistry, PostgresUserStore};
+use secrecy::Secret;
+
+use crate::helpers::{get_random_email, TestApp};
+
+#[tokio::test]
+async fn imported_bcrypt_user_can_log_in_and_is_rehashed() {
+    let mut app = TestApp::new().await;
+    let random_email = get_random_email();
+    let taken_email = get_random_email();
+
+    let signup = serde_json::json!({
+        "email": taken_email,
+        "password": "correct horse battery staple",
+        "requires2FA": false
+    });
+    assert_eq!(app.signup(&signup).await.status().as_u16(), 201);
+
+    let bcrypt_hash = bcrypt::hash("legacy-password", 4).unwrap();
+    let jsonl = [
+        serde_json::json!({ "email": random_email, "password_hash": bcrypt_hash, "locale": "es" }),
+        serde_json::json!({ "email": taken_email, "password_hash": bcrypt_hash }),
+        serde_json::json!({ "email": get_random_email(), "password_hash": "$1$saltsalt$unknownformat" }),
+        serde_json::json!({ "email": "not-an-email", "password_hash": bcrypt_hash }),
+    ].map(|user| user.to_string()).join("\n");
+
+    let user_store = PostgresUserStore::new(app.pg_pool.clone(), PasswordHasherRegistry::new(app.password_hasher.clone()));
+    let report = import_users(&user_store, ImportFormat::Jsonl, jsonl.as_bytes()).await.unwrap();
+
+    assert_eq!(report.imported, 1);
+    assert_eq!(report.skipped, 1);
+    assert_eq!(report.failed.len(), 2, "{:?}", report.failed);
+    assert!(report.failed[0].starts_with("record 3:"));
+    assert!(report.failed.iter().all(|failure| !failure.contains("not-an-email")));
+
+    let credentials = serde_json::json!({
+        "email": random_email,
+        "password": "legacy-password"
+    });
+    assert_eq!(app.login(&credentials).await.status().as_u16(), 200);
+
+    let (stored_hash,): (String,) = sqlx::query_as("SELECT password_hash FROM users WHERE email = $1")
+        .bind(&random_email)
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap();
+    assert!(stored_hash.starts_with("$argon2id$"));
+    assert!(!app.password_hasher.needs_rehash(&Secret::new(stored_hash)));
+
+    // The taken account keeps its own password.
+    assert_eq!(app.login(&signup).await.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
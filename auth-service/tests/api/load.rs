@@ -0,0 +1,186 @@
+use std::{future::Future, sync::Arc, time::{Duration, Instant}};
+
+use auth_service::{
+    app_state::UserStoreType,
+    domain::{Access, AccountStatus, Email, Password, PhoneNumber, TwoFAChannel, User, UserPage, UserStore, UserStoreError},
+    utils::settings::PasswordHashingSettings,
+};
+use reqwest::{Client, StatusCode};
+use tokio::{sync::RwLock, task::JoinSet};
+
+use crate::helpers::{get_random_email, TestApp};
+
+const USERS: usize = 32;
+const LOGINS_PER_USER: usize = 2;
+/// Round trip added to every user store call, as if the database were on
+/// another host. Waiting on it costs no CPU, so the comparison holds on a
+/// single core.
+const STORE_LATENCY: Duration = Duration::from_millis(50);
+/// How much faster the users must be served without the global lock.
+const MIN_SPEEDUP: f64 = 2.0;
+
+/// A user store a network round trip away, optionally behind one `RwLock`
+/// held for the whole call the way `AppState` used to share every store:
+/// writes exclusive, reads shared.
+struct RemoteUserStore {
+    inner: UserStoreType,
+    global_lock: Option<RwLock<()>>,
+}
+
+impl RemoteUserStore {
+    async fn read<T>(&self, call: impl Future<Output = T>) -> T {
+        let _guard = match &self.global_lock {
+            Some(lock) => Some(lock.read().await),
+            None => None,
+        };
+        tokio::time::sleep(STORE_LATENCY).await;
+        call.await
+    }
+
+    async fn write<T>(&self, call: impl Future<Output = T>) -> T {
+        let _guard = match &self.global_lock {
+            Some(lock) => Some(lock.write().await),
+            None => None,
+        };
+        tokio::time::sleep(STORE_LATENCY).await;
+        call.await
+    }
+}
+
+#[async_trait::async_trait]
+impl UserStore for RemoteUserStore {
+    async fn add_user(&self, user: User) -> Result<(), UserStoreError> {
+        self.write(self.inner.add_user(user)).await
+    }
+
+    async fn get_user(&self, email: Email) -> Result<User, UserStoreError> {
+        self.read(self.inner.get_user(email)).await
+    }
+
+    async fn validate_user(&self, email: Email, password: Password) -> Result<(), UserStoreError> {
+        self.read(self.inner.validate_user(email, password)).await
+    }
+
+    async fn update_password(&self, email: &Email, password: Password) -> Result<(), UserStoreError> {
+        self.write(self.inner.update_password(email, password)).await
+    }
+
+    async fn set_phone_number(&self, email: &Email, phone_number: PhoneNumber) -> Result<(), UserStoreError> {
+        self.write(self.inner.set_phone_number(email, phone_number)).await
+    }
+
+    async fn set_two_fa_channel(&self, email: &Email, channel: TwoFAChannel) -> Result<(), UserStoreError> {
+        self.write(self.inner.set_two_fa_channel(email, channel)).await
+    }
+
+    async fn get_access(&self, email: &Email) -> Result<Access, UserStoreError> {
+        self.read(self.inner.get_access(email)).await
+    }
+
+    async fn assign_role(&self, email: &Email, role: &str) -> Result<(), UserStoreError> {
+        self.write(self.inner.assign_role(email, role)).await
+    }
+
+    async fn remove_role(&self, email: &Email, role: &str) -> Result<(), UserStoreError> {
+        self.write(self.inner.remove_role(email, role)).await
+    }
+
+    async fn list_users(&self, search: Option<&str>, offset: u64, limit: u64) -> Result<UserPage, UserStoreError> {
+        self.read(self.inner.list_users(search, offset, limit)).await
+    }
+
+    async fn set_status(&self, email: &Email, status: AccountStatus, reason: Option<String>) -> Result<(), UserStoreError> {
+        self.write(self.inner.set_status(email, status, reason)).await
+    }
+
+    async fn get_status(&self, email: &Email) -> Result<AccountStatus, UserStoreError> {
+        self.read(self.inner.get_status(email)).await
+    }
+
+    async fn require_password_reset(&self, email: &Email) -> Result<(), UserStoreError> {
+        self.write(self.inner.require_password_reset(email)).await
+    }
+
+    async fn reset_two_fa(&self, email: &Email) -> Result<(), UserStoreError> {
+        self.write(self.inner.reset_two_fa(email)).await
+    }
+}
+
+/// Signs up and logs in one user, returning the latency of each request.
+async fn signup_and_logins(http_client: Client, address: String) -> Vec<Duration> {
+    let credentials = serde_json::json!({
+        "email": get_random_email(),
+        "password": "correct horse battery staple",
+        "requires2FA": false
+    });
+
+    let mut latencies = Vec::new();
+
+    let request_started = Instant::now();
+    let response = http_client.post(format!("{}/signup", address)).json(&credentials).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    latencies.push(request_started.elapsed());
+
+    for _ in 0..LOGINS_PER_USER {
+        let request_started = Instant::now();
+        let response = http_client.post(format!("{}/login", address)).json(&credentials).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        latencies.push(request_started.elapsed());
+    }
+
+    latencies
+}
+
+/// Signs up and logs in `USERS` users at once against a remote user store,
+/// with or without the global lock, and prints the throughput.
+async fn serve_concurrent_users(global_lock: bool) -> Duration {
+    let mut app = TestApp::new_with(
+        |settings| {
+            // Cheap hashing keeps the CPU out of the comparison.
+            settings.auth.password_hashing = PasswordHashingSettings { memory_kib: 8, iterations: 1, parallelism: 1 };
+            settings.database.max_connections = USERS as u32;
+        },
+        |inner| Arc::new(RemoteUserStore { inner, global_lock: global_lock.then(|| RwLock::new(())) })
+    ).await;
+
+    let started = Instant::now();
+    let mut users = JoinSet::new();
+    for _ in 0..USERS {
+        users.spawn(signup_and_logins(app.http_client.clone(), app.address.clone()));
+    }
+
+    let mut latencies: Vec<Duration> = Vec::new();
+    while let Some(user) = users.join_next().await {
+        latencies.extend(user.unwrap());
+    }
+
+    let elapsed = started.elapsed();
+    latencies.sort();
+
+    println!(
+        "{}: {} requests in {:.2?}, {:.1} requests/s, p50 {:.2?}, p99 {:.2?}",
+        if global_lock { "global lock" } else { "&self stores" },
+        latencies.len(),
+        elapsed,
+        latencies.len() as f64 / elapsed.as_secs_f64(),
+        latencies[latencies.len() / 2],
+        latencies[latencies.len() * 99 / 100]);
+
+    app.clean_up().await;
+    elapsed
+}
+
+/// Stores shared behind one lock served concurrent users one store call at
+/// a time. Print the numbers with
+///
+/// `cargo test --test api load -- --nocapture`
+#[tokio::test]
+async fn concurrent_users_are_not_serialized_by_the_user_store() {
+    let locked = serve_concurrent_users(true).await;
+    let unlocked = serve_concurrent_users(false).await;
+
+    let speedup = locked.as_secs_f64() / unlocked.as_secs_f64();
+    println!("{:.1}x faster without the global lock", speedup);
+
+    assert!(speedup >= MIN_SPEEDUP, "users were only served {:.1}x faster without the global lock", speedup);
+}
@@ -0,0 +1,136 @@
+use std::sync::{Arc, Mutex, OnceLock};
+
+use auth_service::{
+    domain::{Email, TwoFACodeStore},
+    routes::TwoFactorAuthResponse,
+    utils::{constants::JWT_COOKIE_NAME, settings::LogFormat, tracing::fmt_layer},
+};
+use secrecy::{ExposeSecret, Secret};
+use tracing_subscriber::{prelude::*, EnvFilter};
+
+use crate::helpers::{get_random_email, TestApp};
+
+/// Every event written by any test in this binary once the subscriber is
+/// installed.
+#[derive(Clone, Default)]
+struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+impl std::io::Write for CapturedLogs {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl CapturedLogs {
+    fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+    }
+}
+
+fn captured_logs() -> &'static CapturedLogs {
+    static LOGS: OnceLock<CapturedLogs> = OnceLock::new();
+
+    LOGS.get_or_init(|| {
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+
+        // cookie_store belongs to the test's HTTP client, which logs the
+        // cookies it receives.
+        tracing_subscriber::registry()
+            .with(EnvFilter::new("debug,cookie_store=off"))
+            .with(fmt_layer(LogFormat::Json, move || writer.clone()))
+            .init();
+
+        logs
+    })
+}
+
+#[tokio::test]
+async fn logs_never_contain_credentials_codes_or_tokens() {
+    let logs = captured_logs();
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    let password = "s3cret-passw0rd";
+
+    let response = app.signup(&serde_json::json!({
+        "email": random_email,
+        "password": password,
+        "requires2FA": true
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app.login(&serde_json::json!({
+        "email": random_email,
+        "password": "wrong-password"
+    })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.login(&serde_json::json!({
+        "email": random_email,
+        "password": password
+    })).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    let email = Email::parse(Secret::new(random_email.clone())).unwrap();
+    let (_, code) = app.two_fa_code_store.get_code(&email).await.unwrap();
+    let code = code.as_ref().expose_secret().to_owned();
+
+    let response = app.verify_2fa(&serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": code
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let response = app.verify_token(&serde_json::json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.logout().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Admin routes carry the email in the path and the query string.
+    let response = app.admin_get(&format!("/users?search={}", random_email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.admin_get(&format!("/users/{}", random_email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.admin_post(&format!("/users/{}/revoke-sessions", random_email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+
+    let output = logs.contents();
+    assert!(!output.is_empty());
+
+    for secret in [random_email.as_str(), password, &code, &login_attempt_id, &token] {
+        assert!(!output.contains(secret), "logs leaked {:?}", secret);
+    }
+
+    let events: Vec<serde_json::Value> = output
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("log line is not JSON"))
+        .collect();
+
+    assert!(events.iter().any(|event| event["error.chain"].is_string()
+        && event["error"] == "Incorrect credentials"));
+    assert!(events.iter().any(|event| event["span"]["route"] == "/admin/users/:email"));
+}
@@ -1,5 +1,10 @@
-use auth_service::{domain::{Email, TwoFACodeStore}, routes::TwoFactorAuthResponse, utils::constants::JWT_COOKIE_NAME
+use std::sync::Arc;
+
+use auth_service::{domain::{Email, EmailOutbox, TwoFACodeStore, UserStore, ADMIN_ROLE, USERS_ADMIN_PERMISSION}, routes::TwoFactorAuthResponse,
+    services::{Argon2Hasher, HashsetBannedTokenStore},
+    utils::{auth::validate_token, constants::JWT_COOKIE_NAME, settings::PasswordHashingSettings}
 };
+use chrono::{Duration, Utc};
 use secrecy::{ExposeSecret, Secret};
 
 use crate::helpers::{TestApp, get_random_email};
@@ -12,7 +17,7 @@ async fn should_return_422_login_if_malformed_credentials() {
 
     let valid_data = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "correct horse battery staple",
         "requires2FA": true
     });
 
@@ -27,7 +32,7 @@ async fn should_return_422_login_if_malformed_credentials() {
             }),
             serde_json::json!({
                 "emal": random_email,
-                "pass": "password123"
+                "pass": "correct horse battery staple"
             }),
             serde_json::json!({
                 "password": random_email,
@@ -54,7 +59,7 @@ async fn should_return_400_login_if_invalid_input() {
 
     let valid_data = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "correct horse battery staple",
         "requires2FA": true
     });
 
@@ -67,14 +72,14 @@ async fn should_return_400_login_if_invalid_input() {
     let invalid_login_data = [    
         serde_json::json!({
         "email": "     @     .com",
-        "password": "password123"
+        "password": "correct horse battery staple"
         }),
         serde_json::json!({
             "email": "",
             "password": ""
         }),
         serde_json::json!({
-            "password": "123",
+            "password": "   ",
             "email": "dsada@mail.com"
         }),
     ];
@@ -101,7 +106,7 @@ async fn should_return_401_login_if_incorrect_credentials() {
 
     let valid_data = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "correct horse battery staple",
         "requires2FA": false
     });
 
@@ -117,9 +122,13 @@ async fn should_return_401_login_if_incorrect_credentials() {
             "email": random_email,
             "password": "password321",
         }),
+        serde_json::json!({
+            "email": random_email,
+            "password": "123",
+        }),
         serde_json::json!({
             "email": "wert@mail.com",
-            "password": "password123"
+            "password": "correct horse battery staple"
         })
     ];
 
@@ -144,7 +153,7 @@ async fn should_return_200_login_if_valid_credentials_and_2fa_disabled() {
 
     let valid_signup_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "correct horse battery staple",
         "requires2FA": false
     });
 
@@ -157,7 +166,7 @@ async fn should_return_200_login_if_valid_credentials_and_2fa_disabled() {
 
     let login_body = serde_json::json!({
         "email": random_email,
-        "password": "password123"
+        "password": "correct horse battery staple"
     });
 
     let response = app.login(&login_body).await;
@@ -185,7 +194,7 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
 
     let valid_signup_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "correct horse battery staple",
         "requires2FA": true
     });
 
@@ -198,7 +207,7 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
 
     let login_body = serde_json::json!({
         "email": random_email,
-        "password": "password123"
+        "password": "correct horse battery staple"
     });
 
     let response = app.login(&login_body).await;
@@ -214,11 +223,174 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
 
     assert_eq!(json_body.message, "2FA required".to_owned());
 
-    let binding = app.two_fa_code_store.clone();
-    let test_store = binding.read().await;
     let email = Email::parse(Secret::new(random_email)).unwrap();
-    let result = test_store.get_code(&email).await.unwrap();
+    let result = app.two_fa_code_store.get_code(&email).await.unwrap();
 
     assert_eq!(result.0.as_ref().expose_secret().to_owned(), json_body.login_attempt_id);
+
+    let queued = app.email_outbox.claim_due(10, Utc::now() + Duration::minutes(5)).await.unwrap();
+    assert_eq!(queued.len(), 1);
+    assert_eq!(queued[0].recipient, email);
+    assert!(queued[0].email.text_body.contains(result.1.as_ref().expose_secret()));
+    let deliver_by = queued[0].deliver_by.expect("2FA email has no delivery deadline");
+    assert!(deliver_by > Utc::now() + Duration::minutes(9) && deliver_by <= Utc::now() + Duration::minutes(10));
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_drop_2fa_email_body_when_dead_lettered() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    let response = app.signup(&serde_json::json!({
+        "email": random_email,
+        "password": "correct horse battery staple",
+        "requires2FA": true
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app.login(&serde_json::json!({
+        "email": random_email,
+        "password": "correct horse battery staple"
+    })).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let queued = app.email_outbox.claim_due(10, Utc::now() + Duration::minutes(5)).await.unwrap();
+    assert_eq!(queued.len(), 1);
+    app.email_outbox.dead_letter(queued[0].id, "delivery deadline passed").await.unwrap();
+
+    let (status, subject, last_error, html_body, text_body): (String, String, Option<String>, Option<String>, Option<String>) =
+        sqlx::query_as("SELECT status, subject, last_error, html_body, text_body FROM email_outbox WHERE id = $1")
+            .bind(queued[0].id)
+            .fetch_one(&app.pg_pool)
+            .await
+            .unwrap();
+
+    assert_eq!(status, "dead");
+    assert_eq!(subject, queued[0].email.subject);
+    assert_eq!(last_error.as_deref(), Some("delivery deadline passed"));
+    assert!(html_body.is_none() && text_body.is_none());
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_206_with_localized_2fa_email() {
+    let mut app = TestApp::new().await;
+
+    let test_cases = [
+        (Some("en"), "Your login code"),
+        (None, "Tu código de inicio de sesión"),
+    ];
+
+    for (locale, subject) in test_cases {
+        let random_email = get_random_email();
+
+        let response = app.signup(&serde_json::json!({
+            "email": random_email,
+            "password": "correct horse battery staple",
+            "requires2FA": true,
+            "locale": locale
+        })).await;
+
+        assert_eq!(response.status().as_u16(), 201);
+
+        let response = app.http_client
+            .post(format!("{}/login", &app.address))
+            .header("Accept-Language", "es-MX,es;q=0.9,en;q=0.5")
+            .json(&serde_json::json!({
+                "email": random_email,
+                "password": "correct horse battery staple"
+            }))
+            .send()
+            .await
+            .expect("Failed to execute request login");
+
+        assert_eq!(response.status().as_u16(), 206, "Failed for locale: {:?}", locale);
+
+        let queued = app.email_outbox.claim_due(10, Utc::now() + Duration::minutes(5)).await.unwrap();
+        assert_eq!(queued.len(), 1);
+        assert_eq!(queued[0].email.subject, subject, "Failed for locale: {:?}", locale);
+    }
+
     app.clean_up().await;
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn should_upgrade_outdated_password_hash_on_login() {
+    let mut app = TestApp::new().await;
+    let random_email = get_random_email();
+
+    let credentials = serde_json::json!({
+        "email": random_email,
+        "password": "correct horse battery staple",
+        "requires2FA": false
+    });
+    assert_eq!(app.signup(&credentials).await.status().as_u16(), 201);
+
+    let outdated_hasher = Argon2Hasher::new(&PasswordHashingSettings { memory_kib: 1024, iterations: 1, parallelism: 1 }).unwrap();
+    let outdated_hash = outdated_hasher.hash(Secret::new("correct horse battery staple".to_owned())).await.unwrap();
+    sqlx::query("UPDATE users SET password_hash = $2 WHERE email = $1")
+        .bind(&random_email)
+        .bind(outdated_hash.expose_secret())
+        .execute(&app.pg_pool)
+        .await
+        .unwrap();
+    assert!(app.password_hasher.needs_rehash(&outdated_hash));
+
+    assert_eq!(app.login(&credentials).await.status().as_u16(), 200);
+
+    let (stored_hash,): (String,) = sqlx::query_as("SELECT password_hash FROM users WHERE email = $1")
+        .bind(&random_email)
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap();
+    let stored_hash = Secret::new(stored_hash);
+    assert_ne!(stored_hash.expose_secret(), outdated_hash.expose_secret());
+    assert!(!app.password_hasher.needs_rehash(&stored_hash));
+
+    // The upgraded hash still accepts the same password.
+    assert_eq!(app.login(&credentials).await.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_embed_roles_and_permissions_in_the_auth_token() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "correct horse battery staple",
+        "requires2FA": false
+    });
+    assert_eq!(app.signup(&signup_body).await.status().as_u16(), 201);
+
+    let email = Email::parse(Secret::new(random_email.clone())).unwrap();
+    app.user_store.assign_role(&email, ADMIN_ROLE).await.unwrap();
+
+    let response = app.login(&serde_json::json!({
+        "email": random_email,
+        "password": "correct horse battery staple"
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    let claims = validate_token(
+        &Secret::new(auth_cookie.value().to_owned()),
+        Arc::new(HashsetBannedTokenStore::default()),
+        None,
+        &app.settings.auth.jwt_secret
+    ).await.unwrap();
+    assert_eq!(claims.roles, vec![ADMIN_ROLE]);
+    assert!(claims.permissions.iter().any(|permission| permission == USERS_ADMIN_PERMISSION));
+
+    app.clean_up().await;
+}
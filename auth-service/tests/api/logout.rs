@@ -44,7 +44,7 @@ async fn should_return_200_logout_if_valid_cookie() {
 
     let valid_signup_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "correct horse battery staple",
         "requires2FA": false
     });
 
@@ -57,7 +57,7 @@ async fn should_return_200_logout_if_valid_cookie() {
 
     let login_body = serde_json::json!({
         "email": random_email,
-        "password": "password123"
+        "password": "correct horse battery staple"
     });
 
     let response = app.login(&login_body).await;
@@ -99,7 +99,7 @@ async fn should_return_400_logout_if_called_twice_in_a_row() {
 
     let valid_signup_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "correct horse battery staple",
         "requires2FA": false
     });
 
@@ -112,7 +112,7 @@ async fn should_return_400_logout_if_called_twice_in_a_row() {
 
     let login_body = serde_json::json!({
         "email": random_email,
-        "password": "password123"
+        "password": "correct horse battery staple"
     });
 
     let response = app.login(&login_body).await;
@@ -1,8 +1,21 @@
+mod activity;
+mod admin;
+mod change_password;
+mod health;
+mod import;
 mod helpers;
+mod logging;
+mod load;
 mod login;
 mod logout;
+mod metrics;
+mod phone;
+mod redis;
 mod root;
 // mod routes;
+mod shutdown;
 mod signup;
+mod trusted_devices;
 mod verify_2fa;
-mod verify_token;
\ No newline at end of file
+mod verify_token;
+mod webauthn;
\ No newline at end of file
@@ -0,0 +1,54 @@
+use auth_service::domain::{AccountStatus, Email, UserStore};
+use secrecy::Secret;
+
+use crate::helpers::{get_random_email, TestApp};
+
+fn has_line(body: &str, parts: &[&str]) -> bool {
+    body.lines().any(|line| parts.iter().all(|part| line.contains(part)))
+}
+
+#[tokio::test]
+async fn metrics_expose_requests_auth_events_and_store_latency() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "correct horse battery staple",
+        "requires2FA": false
+    });
+    assert_eq!(app.signup(&signup_body).await.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "wrong-password"
+    });
+    assert_eq!(app.login(&login_body).await.status().as_u16(), 401);
+
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "correct horse battery staple"
+    });
+    assert_eq!(app.login(&login_body).await.status().as_u16(), 200);
+
+    let parsed_email = Email::parse(Secret::new(email.clone())).unwrap();
+    app.user_store.set_status(&parsed_email, AccountStatus::Suspended, None).await.unwrap();
+    assert_eq!(app.login(&login_body).await.status().as_u16(), 403);
+
+    let response = app.get_metrics().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response.text().await.expect("Failed to read metrics");
+
+    assert!(has_line(&body, &["http_requests_total", r#"route="/signup""#, r#"status="201""#, r#"method="POST""#]));
+    assert!(has_line(&body, &["http_request_duration_seconds_bucket", r#"route="/login""#, r#"status="401""#]));
+    assert!(has_line(&body, &["signups_total"]));
+    assert!(has_line(&body, &["logins_total", r#"outcome="failure""#]));
+    assert!(has_line(&body, &["logins_total", r#"outcome="success""#]));
+    assert!(has_line(&body, &["logins_total", r#"outcome="suspended""#]));
+    assert!(has_line(&body, &[
+        "store_operation_duration_seconds_bucket", r#"backend="postgres""#, r#"store="users""#, r#"operation="add_user""#
+    ]));
+
+    app.clean_up().await;
+}
@@ -0,0 +1,158 @@
+use auth_service::{
+    domain::{Email, EmailOutbox, PhoneVerificationStore, TwoFACodeStore},
+    routes::TwoFactorAuthResponse,
+    ErrorResponse,
+};
+use chrono::{Duration, Utc};
+use secrecy::{ExposeSecret, Secret};
+
+use crate::helpers::{get_random_email, TestApp};
+
+async fn signup_and_login(app: &TestApp, requires_2fa: bool) -> String {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "correct horse battery staple",
+        "requires2FA": requires_2fa
+    });
+    assert_eq!(app.signup(&signup_body).await.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "correct horse battery staple"
+    });
+
+    let response = app.login(&login_body).await;
+
+    if requires_2fa {
+        assert_eq!(response.status().as_u16(), 206);
+
+        let json_body = response
+            .json::<TwoFactorAuthResponse>()
+            .await
+            .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+        let email = Email::parse(Secret::new(random_email.clone())).unwrap();
+        let (_, code) = app.two_fa_code_store.get_code(&email).await.unwrap();
+
+        let response = app.verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": json_body.login_attempt_id,
+            "2FACode": code.as_ref().expose_secret()
+        })).await;
+        assert_eq!(response.status().as_u16(), 200);
+    } else {
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    random_email
+}
+
+async fn add_verified_phone_number(app: &TestApp, email: &str) {
+    let response = app.add_phone_number(&serde_json::json!({ "phoneNumber": "+1 415 555 0100" })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let email = Email::parse(Secret::new(email.to_owned())).unwrap();
+    let (phone_number, code) = app.phone_verification_store.take_code(&email).await.unwrap();
+    assert_eq!(phone_number.as_ref().expose_secret(), "+14155550100");
+
+    // Put the code back so the route can take it.
+    app.phone_verification_store.add_code(email, phone_number, code.clone()).await.unwrap();
+
+    let response = app.verify_phone_number(&serde_json::json!({ "code": code.as_ref().expose_secret() })).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_return_400_if_not_logged_in() {
+    let mut app = TestApp::new().await;
+
+    let response = app.add_phone_number(&serde_json::json!({ "phoneNumber": "+14155550100" })).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_phone_number() {
+    let mut app = TestApp::new().await;
+    signup_and_login(&app, false).await;
+
+    for phone_number in ["4155550100", "+0 415 555 0100", "call me"] {
+        let response = app.add_phone_number(&serde_json::json!({ "phoneNumber": phone_number })).await;
+
+        assert_eq!(response.status().as_u16(), 400, "Failed for input: {}", phone_number);
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_incorrect_verification_code() {
+    let mut app = TestApp::new().await;
+    signup_and_login(&app, false).await;
+
+    let response = app.add_phone_number(&serde_json::json!({ "phoneNumber": "+14155550100" })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.verify_phone_number(&serde_json::json!({ "code": "000000" })).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.verify_phone_number(&serde_json::json!({ "code": "123456" })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // A wrong guess uses up the code.
+    let response = app.verify_phone_number(&serde_json::json!({ "code": "123456" })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_sms_channel_without_verified_phone() {
+    let mut app = TestApp::new().await;
+    signup_and_login(&app, false).await;
+
+    let response = app.set_two_fa_channel(&serde_json::json!({ "channel": "sms" })).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Invalid credentials".to_owned()
+    );
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_send_2fa_code_by_sms_once_chosen() {
+    let mut app = TestApp::new().await;
+    let random_email = signup_and_login(&app, true).await;
+    let lease_until = Utc::now() + Duration::minutes(5);
+
+    // The first login emailed its code.
+    let queued = app.email_outbox.claim_due(10, lease_until).await.unwrap();
+    assert_eq!(queued.len(), 1);
+
+    add_verified_phone_number(&app, &random_email).await;
+
+    let response = app.set_two_fa_channel(&serde_json::json!({ "channel": "sms" })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.login(&serde_json::json!({
+        "email": random_email,
+        "password": "correct horse battery staple"
+    })).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let email = Email::parse(Secret::new(random_email)).unwrap();
+    assert!(app.two_fa_code_store.get_code(&email).await.is_ok());
+    assert!(app.email_outbox.claim_due(10, lease_until).await.unwrap().is_empty());
+
+    app.clean_up().await;
+}
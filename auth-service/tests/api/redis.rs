@@ -0,0 +1,71 @@
+use std::time::Duration;
+
+use auth_service::{domain::BannedTokenStore, get_redis_connection, services::RedisBannedTokenStore, utils::constants::DEFAULT_REDIS_HOSTNAME};
+use secrecy::Secret;
+use tokio::{net::{TcpListener, TcpStream}, task::JoinSet};
+use uuid::Uuid;
+
+/// Forwards connections on `port` to the local Redis server. Dropping it
+/// closes every connection, which looks like a Redis restart to clients.
+struct RedisProxy {
+    tasks: JoinSet<()>,
+}
+
+impl RedisProxy {
+    async fn start(port: u16) -> Self {
+        let listener = TcpListener::bind(("127.0.0.1", port)).await.expect("Failed to bind proxy");
+        let mut tasks = JoinSet::new();
+
+        tasks.spawn(async move {
+            let mut connections = JoinSet::new();
+
+            while let Ok((mut client, _)) = listener.accept().await {
+                connections.spawn(async move {
+                    let mut server = TcpStream::connect((DEFAULT_REDIS_HOSTNAME, 6379)).await.unwrap();
+                    let _ = tokio::io::copy_bidirectional(&mut client, &mut server).await;
+                });
+            }
+        });
+
+        Self { tasks }
+    }
+
+    async fn stop(mut self) {
+        self.tasks.shutdown().await;
+    }
+}
+
+async fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port()
+}
+
+#[tokio::test]
+async fn redis_stores_reconnect_after_redis_restarts() {
+    let port = free_port().await;
+    let proxy = RedisProxy::start(port).await;
+
+    let conn = get_redis_connection(format!("127.0.0.1:{}", port))
+        .await
+        .expect("Failed to get Redis connection");
+    let store = RedisBannedTokenStore::new(conn);
+    let token = Secret::new(Uuid::new_v4().to_string());
+
+    store.store_banned_token(token.clone()).await.unwrap();
+
+    proxy.stop().await;
+    assert!(store.check_banned_token(token.clone()).await.is_err());
+
+    let _proxy = RedisProxy::start(port).await;
+
+    // The manager reconnects in the background after the failed command.
+    let mut banned = None;
+    for _ in 0..50 {
+        if let Ok(result) = store.check_banned_token(token.clone()).await {
+            banned = Some(result);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    assert_eq!(banned, Some(true), "store did not recover after Redis came back");
+}
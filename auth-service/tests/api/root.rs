@@ -1,3 +1,5 @@
+use auth_service::utils::{constants::test, tracing::REQUEST_ID_HEADER};
+
 use crate::helpers::TestApp;
 
 #[tokio::test]
@@ -10,4 +12,40 @@ async fn root_returns_auth_ui() {
     assert_eq!(response.headers().get("content-type").unwrap(), "text/html");
     
     app.clean_up().await;
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn root_allows_configured_origins_only() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_root_with_header("Origin", test::ALLOWED_ORIGIN).await;
+    assert_eq!(response.headers().get("access-control-allow-origin").unwrap(), test::ALLOWED_ORIGIN);
+
+    let response = app.get_root_with_header("Origin", "http://evil.example.com").await;
+    assert!(response.headers().get("access-control-allow-origin").is_none());
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn root_echoes_the_request_id() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_root_with_header(REQUEST_ID_HEADER, "req-123").await;
+
+    assert_eq!(response.headers().get(REQUEST_ID_HEADER).unwrap(), "req-123");
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn root_generates_a_request_id_when_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_root().await;
+
+    let request_id = response.headers().get(REQUEST_ID_HEADER).unwrap().to_str().unwrap();
+    assert!(uuid::Uuid::parse_str(request_id).is_ok());
+
+    app.clean_up().await;
+}
@@ -0,0 +1,121 @@
+use std::{sync::Arc, time::Duration};
+
+use auth_service::utils::constants::test;
+use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpStream};
+
+use crate::helpers::{get_random_email, TestApp};
+
+/// Opens a connection and sends a signup request without the last byte of
+/// its body, so the request stays in flight until `finish` is called.
+async fn start_signup(app: &TestApp) -> (TcpStream, String) {
+    let body = serde_json::json!({
+        "email": get_random_email(),
+        "password": "correct horse battery staple",
+        "requires2FA": false
+    })
+    .to_string();
+
+    let host = app.address.trim_start_matches("http://");
+    let mut stream = TcpStream::connect(host).await.expect("Failed to connect");
+    let head = format!(
+        "POST /signup HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n",
+        host,
+        body.len());
+
+    stream.write_all(head.as_bytes()).await.unwrap();
+    stream.write_all(&body.as_bytes()[..body.len() - 1]).await.unwrap();
+
+    let last_byte = body[body.len() - 1..].to_owned();
+    (stream, last_byte)
+}
+
+async fn finish(mut stream: TcpStream, last_byte: String) -> String {
+    stream.write_all(last_byte.as_bytes()).await.unwrap();
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.expect("Failed to read response");
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+#[tokio::test]
+async fn shutdown_lets_in_flight_requests_finish() {
+    let mut app = TestApp::new().await;
+
+    let (stream, last_byte) = start_signup(&app).await;
+    // Let the server start on the request before shutting down.
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    app.shutdown.shutdown();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    assert!(app.health.is_shutting_down());
+    assert!(!app.server.is_finished());
+
+    let response = finish(stream, last_byte).await;
+    assert!(response.starts_with("HTTP/1.1 201"), "unexpected response: {}", response);
+
+    let outcome = tokio::time::timeout(Duration::from_secs(test::READINESS_GRACE_SECS + test::DRAIN_TIMEOUT_SECS), &mut app.server)
+        .await
+        .expect("Server did not stop after draining");
+    assert!(outcome.unwrap().is_ok());
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn shutdown_stops_waiting_after_the_drain_timeout() {
+    let mut app = TestApp::new().await;
+
+    let (mut stream, _last_byte) = start_signup(&app).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    app.shutdown.shutdown();
+
+    let outcome = tokio::time::timeout(Duration::from_secs(test::READINESS_GRACE_SECS + test::DRAIN_TIMEOUT_SECS + 2), &mut app.server)
+        .await
+        .expect("Server did not stop after the drain timeout");
+    assert!(outcome.unwrap().is_ok());
+
+    // The connection was aborted, taking its handle on the app state with it.
+    let mut response = Vec::new();
+    tokio::time::timeout(Duration::from_secs(1), stream.read_to_end(&mut response))
+        .await
+        .expect("Connection was left open after the drain timeout")
+        .ok();
+    assert_eq!(Arc::strong_count(&app.health), 1);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn shutdown_keeps_serving_new_requests_during_the_readiness_grace() {
+    let mut app = TestApp::new().await;
+
+    app.shutdown.shutdown();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let response = app.get_health_ready().await;
+    assert_eq!(response.status().as_u16(), 503);
+
+    let (stream, last_byte) = start_signup(&app).await;
+    let response = finish(stream, last_byte).await;
+    assert!(response.starts_with("HTTP/1.1 201"), "unexpected response: {}", response);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn shutdown_stops_accepting_new_connections() {
+    let mut app = TestApp::new().await;
+
+    app.shutdown.shutdown();
+    tokio::time::timeout(Duration::from_secs(test::READINESS_GRACE_SECS + test::DRAIN_TIMEOUT_SECS), &mut app.server)
+        .await
+        .expect("Server did not stop")
+        .unwrap()
+        .unwrap();
+
+    assert!(TcpStream::connect(app.address.trim_start_matches("http://")).await.is_err());
+
+    app.clean_up().await;
+}
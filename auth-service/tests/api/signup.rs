@@ -1,5 +1,6 @@
 use crate::helpers::{self, TestApp};
-use auth_service::{routes::SignupResponse, ErrorResponse};
+use auth_service::{domain::{PasswordViolation, MIN_PASSWORD_LENGTH}, routes::SignupResponse, ErrorResponse};
+use tokio::task::JoinSet;
 
 #[tokio::test]
 async fn should_return_422_if_malformed_input() {
@@ -48,7 +49,7 @@ async fn should_return_201_if_valid_input() {
 
     let valid_data = serde_json::json!({
         "email": random_email,
-        "password": "string1234",
+        "password": "correct horse battery staple",
         "requires2FA": true
     });
 
@@ -81,12 +82,12 @@ async fn should_return_400_if_invalid_input() {
     let test_cases = [
         serde_json::json!({
             "email": "user.mail.com",
-            "password": "password123",
+            "password": "correct horse battery staple",
             "requires2FA": false
         }),
         serde_json::json!({
             "email": "",
-            "password": "password123",
+            "password": "correct horse battery staple",
             "requires2FA": true 
         }),
         serde_json::json!({
@@ -94,11 +95,6 @@ async fn should_return_400_if_invalid_input() {
             "password": "          ",
             "requires2FA": true
         }),
-        serde_json::json!({
-            "email": "user_test@mail.com",
-            "password": "pass",
-            "requires2FA": true
-        }),
         serde_json::json!({
             "email": "user_test@mail.com",
             "password": "",
@@ -108,6 +104,12 @@ async fn should_return_400_if_invalid_input() {
             "email": "    @      ",
             "password": "password1233",
             "requires2FA": true
+        }),
+        serde_json::json!({
+            "email": "user_test@mail.com",
+            "password": "password1233",
+            "requires2FA": true,
+            "locale": "xx"
         })
     ];
 
@@ -140,7 +142,7 @@ async fn should_return_409_if_email() {
 
     let test_case = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "correct horse battery staple",
         "requires2FA": true
     });
 
@@ -166,3 +168,133 @@ async fn should_return_409_if_email() {
 
     app.clean_up().await;
 }
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_signups_for_the_same_email_create_one_user() {
+    let mut app = TestApp::new().await;
+    let random_email = helpers::get_random_email();
+
+    let mut signups = JoinSet::new();
+    for i in 0..8 {
+        let http_client = app.http_client.clone();
+        let address = app.address.clone();
+        let credentials = serde_json::json!({
+            "email": random_email,
+            "password": format!("correct horse battery staple {}", i),
+            "requires2FA": false
+        });
+
+        signups.spawn(async move {
+            let response = http_client.post(format!("{}/signup", address)).json(&credentials).send().await.unwrap();
+            (credentials, response.status().as_u16())
+        });
+    }
+
+    let mut created = Vec::new();
+    while let Some(signup) = signups.join_next().await {
+        let (credentials, status) = signup.unwrap();
+        match status {
+            201 => created.push(credentials),
+            409 => {},
+            status => panic!("Unexpected status {} for concurrent signup", status),
+        }
+    }
+
+    assert_eq!(created.len(), 1, "Exactly one concurrent signup should win");
+
+    // The stored password is the winner's.
+    let response = app.login(&created[0]).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_with_every_violation_if_password_is_weak() {
+    let mut app = TestApp::new().await;
+
+    let test_case = serde_json::json!({
+        "email": "jane.doe@example.com",
+        "password": "jane.doe",
+        "requires2FA": false
+    });
+
+    let response = app.signup(&test_case).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let body = response
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse");
+    assert_eq!(body.error, "Weak password");
+
+    let violations: Vec<PasswordViolation> = body.violations.iter()
+        .map(|violation| violation.violation.clone())
+        .collect();
+    assert_eq!(violations, vec![
+        PasswordViolation::ContainsEmail,
+        PasswordViolation::TooWeak { score: 0, min: 3 },
+    ]);
+    assert!(body.violations.iter().all(|violation| !violation.message.is_empty()));
+
+    let common_password = serde_json::json!({
+        "email": helpers::get_random_email(),
+        "password": "password123",
+        "requires2FA": false
+    });
+    let body = app.signup(&common_password).await
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse");
+    assert!(body.violations.iter().any(|violation| violation.violation == PasswordViolation::Common));
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_with_too_short_if_password_is_short() {
+    let mut app = TestApp::new().await;
+
+    let test_case = serde_json::json!({
+        "email": helpers::get_random_email(),
+        "password": "Pa5s!",
+        "requires2FA": false
+    });
+
+    let response = app.signup(&test_case).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let body = response
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse");
+    assert_eq!(body.error, "Weak password");
+    assert!(body.violations.iter()
+        .any(|violation| violation.violation == PasswordViolation::TooShort { min: MIN_PASSWORD_LENGTH }));
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_password_was_breached() {
+    let mut app = TestApp::new().await;
+
+    let test_case = serde_json::json!({
+        "email": helpers::get_random_email(),
+        "password": helpers::BREACHED_PASSWORD,
+        "requires2FA": false
+    });
+
+    let response = app.signup(&test_case).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let body = response
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse");
+    assert_eq!(body.error, "Weak password");
+    assert_eq!(body.violations.iter().map(|violation| violation.violation.clone()).collect::<Vec<_>>(),
+        vec![PasswordViolation::Breached]);
+
+    app.clean_up().await;
+}
@@ -0,0 +1,141 @@
+use auth_service::{
+    domain::{Email, TwoFACodeStore},
+    routes::TwoFactorAuthResponse,
+    utils::constants::TRUSTED_DEVICE_COOKIE_NAME,
+};
+use secrecy::{ExposeSecret, Secret};
+
+use crate::helpers::{get_random_email, TestApp};
+
+async fn login_with_2fa(app: &TestApp, email: &str, remember_device: bool) -> reqwest::Response {
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "correct horse battery staple"
+    });
+
+    let response = app.login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    let parsed_email = Email::parse(Secret::new(email.to_owned())).unwrap();
+    let (_, code) = app.two_fa_code_store.get_code(&parsed_email).await.unwrap();
+
+    app.verify_2fa(&serde_json::json!({
+        "email": email,
+        "loginAttemptId": json_body.login_attempt_id,
+        "2FACode": code.as_ref().expose_secret(),
+        "rememberDevice": remember_device
+    })).await
+}
+
+async fn signup_2fa_user(app: &TestApp) -> String {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "correct horse battery staple",
+        "requires2FA": true
+    });
+    assert_eq!(app.signup(&signup_body).await.status().as_u16(), 201);
+
+    random_email
+}
+
+#[tokio::test]
+async fn should_not_set_trusted_device_cookie_by_default() {
+    let mut app = TestApp::new().await;
+    let random_email = signup_2fa_user(&app).await;
+
+    let response = login_with_2fa(&app, &random_email, false).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(!response.cookies().any(|cookie| cookie.name() == TRUSTED_DEVICE_COOKIE_NAME));
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_skip_2fa_on_trusted_device() {
+    let mut app = TestApp::new().await;
+    let random_email = signup_2fa_user(&app).await;
+
+    let response = login_with_2fa(&app, &random_email, true).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.cookies().any(|cookie| cookie.name() == TRUSTED_DEVICE_COOKIE_NAME));
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "correct horse battery staple"
+    });
+
+    let response = app.login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_require_2fa_for_other_user_on_trusted_device() {
+    let mut app = TestApp::new().await;
+    let random_email = signup_2fa_user(&app).await;
+    let other_email = signup_2fa_user(&app).await;
+
+    let response = login_with_2fa(&app, &random_email, true).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let login_body = serde_json::json!({
+        "email": other_email,
+        "password": "correct horse battery staple"
+    });
+
+    let response = app.login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_list_and_revoke_trusted_devices() {
+    let mut app = TestApp::new().await;
+    let random_email = signup_2fa_user(&app).await;
+
+    let response = login_with_2fa(&app, &random_email, true).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.list_trusted_devices().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let devices = response.json::<Vec<serde_json::Value>>().await.unwrap();
+    assert_eq!(devices.len(), 1);
+
+    let device_id = devices[0]["id"].as_str().unwrap().to_owned();
+
+    let response = app.revoke_trusted_device(&device_id).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.revoke_trusted_device(&device_id).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "correct horse battery staple"
+    });
+
+    let response = app.login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_list_trusted_devices_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app.list_trusted_devices().await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
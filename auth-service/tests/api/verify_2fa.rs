@@ -1,4 +1,4 @@
-use auth_service::{domain::{Email, TwoFACodeStore}, routes::TwoFactorAuthResponse, ErrorResponse, utils::constants::JWT_COOKIE_NAME,};
+use auth_service::{domain::{AccountStatus, Email, TwoFACodeStore, UserStore}, routes::TwoFactorAuthResponse, ErrorResponse, utils::constants::JWT_COOKIE_NAME,};
 use secrecy::{ExposeSecret, Secret};
 
 use crate::helpers::{get_random_email, TestApp};
@@ -99,7 +99,7 @@ async fn should_return_401_if_incorrect_credentials() {
 
     let valid_signup_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "correct horse battery staple",
         "requires2FA": true
     });
 
@@ -112,7 +112,7 @@ async fn should_return_401_if_incorrect_credentials() {
 
     let login_body = serde_json::json!({
         "email": random_email,
-        "password": "password123"
+        "password": "correct horse battery staple"
     });
 
     let response = app.login(&login_body).await;
@@ -154,7 +154,7 @@ async fn should_return_401_if_old_code() {
 
     let valid_signup_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "correct horse battery staple",
         "requires2FA": true
     });
 
@@ -167,7 +167,7 @@ async fn should_return_401_if_old_code() {
 
     let login_body = serde_json::json!({
         "email": random_email,
-        "password": "password123"
+        "password": "correct horse battery staple"
     });
 
     let response = app.login(&login_body).await;
@@ -185,7 +185,7 @@ async fn should_return_401_if_old_code() {
     assert!(!json_body.login_attempt_id.is_empty());
 
     let email = Email::parse(Secret::new(random_email.clone())).unwrap();
-    let result = app.two_fa_code_store.read().await.get_code(&email).await.unwrap();
+    let result = app.two_fa_code_store.get_code(&email).await.unwrap();
 
     let response = app.login(&login_body).await;
 
@@ -229,7 +229,7 @@ async fn should_return_200_if_correct_code() {
 
     let valid_signup_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "correct horse battery staple",
         "requires2FA": true
     });
 
@@ -242,7 +242,7 @@ async fn should_return_200_if_correct_code() {
 
     let login_body = serde_json::json!({
         "email": random_email,
-        "password": "password123"
+        "password": "correct horse battery staple"
     });
 
     let response = app.login(&login_body).await;
@@ -260,7 +260,7 @@ async fn should_return_200_if_correct_code() {
     assert!(!json_body.login_attempt_id.is_empty());
 
     let email = Email::parse(Secret::new(random_email.clone())).unwrap();
-    let result = app.two_fa_code_store.read().await.get_code(&email).await.unwrap();
+    let result = app.two_fa_code_store.get_code(&email).await.unwrap();
 
     let code = result.1.as_ref();
 
@@ -295,7 +295,7 @@ async fn should_return_401_if_same_code_twice() {
 
     let valid_signup_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "correct horse battery staple",
         "requires2FA": true
     });
 
@@ -308,7 +308,7 @@ async fn should_return_401_if_same_code_twice() {
 
     let login_body = serde_json::json!({
         "email": random_email,
-        "password": "password123"
+        "password": "correct horse battery staple"
     });
 
     let response = app.login(&login_body).await;
@@ -326,7 +326,7 @@ async fn should_return_401_if_same_code_twice() {
     assert!(!json_body.login_attempt_id.is_empty());
 
     let email = Email::parse(Secret::new(random_email.clone())).unwrap();
-    let result = app.two_fa_code_store.read().await.get_code(&email).await.unwrap();
+    let result = app.two_fa_code_store.get_code(&email).await.unwrap();
 
     let code = result.1.as_ref();
 
@@ -359,3 +359,53 @@ async fn should_return_401_if_same_code_twice() {
 
     app.clean_up().await;
 }
+
+#[tokio::test]
+async fn should_return_403_if_account_suspended_after_login() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    let response = app.signup(&serde_json::json!({
+        "email": random_email,
+        "password": "correct horse battery staple",
+        "requires2FA": true
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app.login(&serde_json::json!({
+        "email": random_email,
+        "password": "correct horse battery staple"
+    })).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await.expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    let email = Email::parse(Secret::new(random_email.clone())).unwrap();
+    app.user_store.set_status(&email, AccountStatus::Suspended, None).await.unwrap();
+    let (_, code) = app.two_fa_code_store.get_code(&email).await.unwrap();
+
+    let response = app.verify_2fa(&serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": json_body.login_attempt_id,
+        "2FACode": code.as_ref().expose_secret()
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(
+        response.json::<ErrorResponse>().await.unwrap().error,
+        "Account suspended"
+    );
+
+    // The code was right, but no login happened, so none is recorded.
+    let events: Vec<String> = sqlx::query_scalar("SELECT event FROM audit_log WHERE subject = $1 ORDER BY id")
+        .bind(&random_email)
+        .fetch_all(&app.pg_pool)
+        .await
+        .unwrap();
+    assert_eq!(events, ["signup", "two_fa_issued"]);
+
+    app.clean_up().await;
+}
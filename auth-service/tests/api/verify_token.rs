@@ -28,7 +28,7 @@ async fn should_return_200_verifytk_valid_token() {
 
     let valid_user = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "correct horse battery staple",
         "requires2FA": false
     });
 
@@ -41,7 +41,7 @@ async fn should_return_200_verifytk_valid_token() {
 
     let valid_user_lg = serde_json::json!({
         "email": random_email,
-        "password": "password123"
+        "password": "correct horse battery staple"
     });
 
     let response = app.login(&valid_user_lg).await;
@@ -90,7 +90,7 @@ async fn should_return_401_if_banned_token() {
 
     let valid_user = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "correct horse battery staple",
         "requires2FA": false
     });
 
@@ -103,7 +103,7 @@ async fn should_return_401_if_banned_token() {
 
     let valid_user_lg = serde_json::json!({
         "email": random_email,
-        "password": "password123"
+        "password": "correct horse battery staple"
     });
 
     let response = app.login(&valid_user_lg).await;
@@ -0,0 +1,279 @@
+use auth_service::{
+    domain::{Email, LoginAttemptId, TwoFACodeStore},
+    routes::{WebAuthnAuthResponse, WebAuthnRegisterStartResponse},
+    utils::constants::{test, JWT_COOKIE_NAME},
+};
+use reqwest::Url;
+use secrecy::{ExposeSecret, Secret};
+use webauthn_authenticator_rs::{softpasskey::SoftPasskey, WebauthnAuthenticator};
+
+use crate::helpers::{get_random_email, TestApp};
+
+type SoftAuthenticator = WebauthnAuthenticator<SoftPasskey>;
+
+async fn signup_and_login(app: &TestApp, email: &str, requires_2fa: bool) {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "correct horse battery staple",
+        "requires2FA": requires_2fa
+    });
+    assert_eq!(app.signup(&signup_body).await.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "correct horse battery staple"
+    });
+    let expected_status = if requires_2fa { 206 } else { 200 };
+    assert_eq!(app.login(&login_body).await.status().as_u16(), expected_status);
+}
+
+async fn register_passkey(app: &TestApp, authenticator: &mut SoftAuthenticator) {
+    let response = app.webauthn_register_start().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let start = response
+        .json::<WebAuthnRegisterStartResponse>()
+        .await
+        .expect("Could not deserialize response body to WebAuthnRegisterStartResponse");
+
+    let credential = authenticator
+        .do_registration(Url::parse(test::WEBAUTHN_RP_ORIGIN).unwrap(), start.challenge)
+        .expect("Software authenticator failed to register");
+
+    let response = app.webauthn_register_finish(&serde_json::json!({ "credential": credential })).await;
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+#[tokio::test]
+async fn should_return_400_register_start_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app.webauthn_register_start().await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_200_passwordless_login_with_registered_passkey() {
+    let mut app = TestApp::new().await;
+    let mut authenticator = WebauthnAuthenticator::new(SoftPasskey::new(true));
+
+    let random_email = get_random_email();
+    signup_and_login(&app, &random_email, false).await;
+    register_passkey(&app, &mut authenticator).await;
+
+    let response = app.logout().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.webauthn_login_start(&serde_json::json!({ "email": random_email })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let start = response
+        .json::<WebAuthnAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to WebAuthnAuthResponse");
+
+    let assertion = authenticator
+        .do_authentication(Url::parse(test::WEBAUTHN_RP_ORIGIN).unwrap(), start.challenge)
+        .expect("Software authenticator failed to authenticate");
+
+    let response = app.webauthn_login_finish(&serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": start.login_attempt_id,
+        "credential": assertion
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    assert!(!auth_cookie.value().is_empty());
+
+    let events: Vec<String> = sqlx::query_scalar("SELECT event FROM audit_log WHERE subject = $1 ORDER BY id")
+        .bind(&random_email)
+        .fetch_all(&app.pg_pool)
+        .await
+        .unwrap();
+    assert_eq!(events, ["signup", "login_succeeded", "logout", "passkey_verified", "login_succeeded"]);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_206_with_webauthn_challenge_if_2fa_user_has_passkey() {
+    let mut app = TestApp::new().await;
+    let mut authenticator = WebauthnAuthenticator::new(SoftPasskey::new(true));
+
+    let random_email = get_random_email();
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "correct horse battery staple"
+    });
+    signup_and_login(&app, &random_email, true).await;
+
+    // The first login still goes through the emailed code.
+    let email = Email::parse(Secret::new(random_email.clone())).unwrap();
+    let (login_attempt_id, code) = app.two_fa_code_store.get_code(&email).await.unwrap();
+    let response = app.verify_2fa(&serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id.as_ref().expose_secret(),
+        "2FACode": code.as_ref().expose_secret()
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    register_passkey(&app, &mut authenticator).await;
+    assert_eq!(app.logout().await.status().as_u16(), 200);
+
+    let response = app.login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let challenge = response
+        .json::<WebAuthnAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to WebAuthnAuthResponse");
+    assert_eq!(challenge.message, "WebAuthn assertion required");
+
+    let assertion = authenticator
+        .do_authentication(Url::parse(test::WEBAUTHN_RP_ORIGIN).unwrap(), challenge.challenge)
+        .expect("Software authenticator failed to authenticate");
+
+    let response = app.webauthn_login_finish(&serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": challenge.login_attempt_id,
+        "credential": assertion
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.cookies().any(|cookie| cookie.name() == JWT_COOKIE_NAME));
+
+    let events: Vec<String> = sqlx::query_scalar("SELECT event FROM audit_log WHERE subject = $1 ORDER BY id")
+        .bind(&random_email)
+        .fetch_all(&app.pg_pool)
+        .await
+        .unwrap();
+    assert_eq!(events[events.len() - 3..], ["two_fa_issued", "two_fa_verified", "login_succeeded"]);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_login_attempt_id_does_not_match() {
+    let mut app = TestApp::new().await;
+    let mut authenticator = WebauthnAuthenticator::new(SoftPasskey::new(true));
+
+    let random_email = get_random_email();
+    signup_and_login(&app, &random_email, false).await;
+    register_passkey(&app, &mut authenticator).await;
+
+    let response = app.webauthn_login_start(&serde_json::json!({ "email": random_email })).await;
+    let start = response
+        .json::<WebAuthnAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to WebAuthnAuthResponse");
+
+    let assertion = authenticator
+        .do_authentication(Url::parse(test::WEBAUTHN_RP_ORIGIN).unwrap(), start.challenge)
+        .expect("Software authenticator failed to authenticate");
+
+    let response = app.webauthn_login_finish(&serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": LoginAttemptId::default().as_ref().expose_secret(),
+        "credential": assertion
+    })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_keep_a_pending_login_when_another_one_starts() {
+    let mut app = TestApp::new().await;
+    let mut authenticator = WebauthnAuthenticator::new(SoftPasskey::new(true));
+
+    let random_email = get_random_email();
+    signup_and_login(&app, &random_email, false).await;
+    register_passkey(&app, &mut authenticator).await;
+
+    let first = app.webauthn_login_start(&serde_json::json!({ "email": random_email })).await
+        .json::<WebAuthnAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to WebAuthnAuthResponse");
+
+    let response = app.webauthn_login_start(&serde_json::json!({ "email": random_email })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let assertion = authenticator
+        .do_authentication(Url::parse(test::WEBAUTHN_RP_ORIGIN).unwrap(), first.challenge)
+        .expect("Software authenticator failed to authenticate");
+
+    let response = app.webauthn_login_finish(&serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": first.login_attempt_id,
+        "credential": assertion
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_a_decoy_challenge_if_user_has_no_passkey() {
+    let mut app = TestApp::new().await;
+    let mut authenticator = WebauthnAuthenticator::new(SoftPasskey::new(true));
+
+    let random_email = get_random_email();
+    signup_and_login(&app, &random_email, false).await;
+    register_passkey(&app, &mut authenticator).await;
+
+    let with_passkey = app.webauthn_login_start(&serde_json::json!({ "email": random_email })).await
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+
+    let without_passkey_email = get_random_email();
+    signup_and_login(&app, &without_passkey_email, false).await;
+
+    for email in [without_passkey_email, get_random_email()] {
+        let response = app.webauthn_login_start(&serde_json::json!({ "email": email })).await;
+        assert_eq!(response.status().as_u16(), 200);
+
+        let decoy = response.json::<serde_json::Value>().await.unwrap();
+        assert_eq!(shape_of(&decoy), shape_of(&with_passkey));
+
+        let allowed = &decoy["challenge"]["publicKey"]["allowCredentials"];
+        assert!(!allowed.as_array().unwrap().is_empty());
+
+        let again = app.webauthn_login_start(&serde_json::json!({ "email": email })).await
+            .json::<serde_json::Value>()
+            .await
+            .unwrap();
+        assert_eq!(&again["challenge"]["publicKey"]["allowCredentials"], allowed);
+
+        let start: WebAuthnAuthResponse = serde_json::from_value(with_passkey.clone()).unwrap();
+        let assertion = authenticator
+            .do_authentication(Url::parse(test::WEBAUTHN_RP_ORIGIN).unwrap(), start.challenge)
+            .expect("Software authenticator failed to authenticate");
+
+        let response = app.webauthn_login_finish(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": decoy["loginAttemptId"],
+            "credential": assertion
+        })).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    app.clean_up().await;
+}
+
+/// Field names all the way down, ignoring values.
+fn shape_of(value: &serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(fields) => fields
+            .iter()
+            .map(|(name, value)| (name.clone(), shape_of(value)))
+            .collect(),
+        serde_json::Value::Array(items) => items.first().map(shape_of).into_iter().collect(),
+        _ => serde_json::Value::Null,
+    }
+}